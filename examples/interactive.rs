@@ -131,8 +131,9 @@ fn main() -> Result<()> {
             }
             "metrics" => {
                 println!("Metrics:");
-                println!("  Avg Loss: {:.4}", metrics.avg_loss());
-                println!("  Avg Gradient Norm: {:.4}", metrics.avg_gradient_norm());
+                for line in metrics.summary().to_string().lines() {
+                    println!("  {}", line);
+                }
                 println!();
             }
             "config" => {
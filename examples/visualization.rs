@@ -61,6 +61,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         max_history: 50,
         update_interval_ms: 500,
         interactive: true,
+        ..Default::default()
     };
 
     let mut visualizer = TrainingVisualizer::new(vis_config, config.training.pretraining_epochs);
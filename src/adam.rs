@@ -1,5 +1,6 @@
 use ndarray::Array2;
 
+#[derive(Clone)]
 pub struct Adam {
     beta1: f32,
     beta2: f32,
@@ -0,0 +1,299 @@
+//! Byte-level BPE tokenization compatible with GPT-2's `vocab.json` and `merges.txt`.
+//!
+//! This is a standalone tokenizer for reusing existing GPT-2-style vocabularies; the
+//! model itself still tokenizes with the word-level [`crate::Vocab`].
+
+use crate::error::{LlmError, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A byte-level BPE tokenizer loaded from GPT-2-format files.
+///
+/// Text is split into chunks the way GPT-2's pre-tokenizer does (letters, digits and
+/// other symbols, each with an optional leading space, plus English contractions and
+/// runs of whitespace), each chunk's bytes are mapped to printable characters, and the
+/// merge rules are applied lowest rank first.
+#[derive(Debug, Clone)]
+pub struct BpeTokenizer {
+    encoder: HashMap<String, usize>,
+    decoder: HashMap<usize, String>,
+    merges: HashMap<(String, String), usize>,
+    byte_encoder: Vec<char>,
+    byte_decoder: HashMap<char, u8>,
+}
+
+impl BpeTokenizer {
+    /// Load a tokenizer from a GPT-2 `vocab.json` (token to id) and `merges.txt` (one
+    /// space-separated pair per line, highest priority first, optionally preceded by a
+    /// `#version` line).
+    ///
+    /// # Errors
+    /// Returns a vocabulary error naming the file and line if either file is unreadable
+    /// or malformed, or if a merge produces a token missing from the vocabulary.
+    pub fn from_gpt2_files(vocab_json: &Path, merges_txt: &Path) -> Result<Self> {
+        let read = |path: &Path| {
+            std::fs::read_to_string(path).map_err(|e| {
+                LlmError::vocabulary(format!("Failed to read {}: {}", path.display(), e))
+            })
+        };
+        let vocab = read(vocab_json)?;
+        let merges = read(merges_txt)?;
+
+        let encoder: HashMap<String, usize> = serde_json::from_str(&vocab).map_err(|e| {
+            LlmError::vocabulary(format!(
+                "Invalid vocab.json {}: {}",
+                vocab_json.display(),
+                e
+            ))
+        })?;
+        if encoder.is_empty() {
+            return Err(LlmError::vocabulary(format!(
+                "vocab.json {} contains no tokens",
+                vocab_json.display()
+            )));
+        }
+
+        let mut ranks = HashMap::new();
+        for (line_no, line) in merges.lines().enumerate() {
+            if line.trim().is_empty() || (line_no == 0 && line.starts_with("#version")) {
+                continue;
+            }
+            let parts: Vec<&str> = line.split(' ').collect();
+            let [left, right] = parts[..] else {
+                return Err(LlmError::vocabulary(format!(
+                    "Invalid merges.txt {} line {}: expected two tokens separated by a space, got '{}'",
+                    merges_txt.display(),
+                    line_no + 1,
+                    line
+                )));
+            };
+            let merged = format!("{}{}", left, right);
+            if !encoder.contains_key(&merged) {
+                return Err(LlmError::vocabulary(format!(
+                    "merges.txt {} line {} produces '{}', which is not in vocab.json",
+                    merges_txt.display(),
+                    line_no + 1,
+                    merged
+                )));
+            }
+            let rank = ranks.len();
+            ranks
+                .entry((left.to_string(), right.to_string()))
+                .or_insert(rank);
+        }
+
+        let byte_encoder = bytes_to_unicode();
+        let byte_decoder = byte_encoder
+            .iter()
+            .enumerate()
+            .map(|(byte, &c)| (c, byte as u8))
+            .collect();
+        let decoder = encoder
+            .iter()
+            .map(|(token, &id)| (id, token.clone()))
+            .collect();
+
+        Ok(Self {
+            encoder,
+            decoder,
+            merges: ranks,
+            byte_encoder,
+            byte_decoder,
+        })
+    }
+
+    /// Number of tokens in the vocabulary.
+    pub fn vocab_size(&self) -> usize {
+        self.encoder.len()
+    }
+
+    /// Number of merge rules.
+    pub fn num_merges(&self) -> usize {
+        self.merges.len()
+    }
+
+    /// Encode `text` into token ids.
+    ///
+    /// # Errors
+    /// Returns a token error if a symbol left after merging is not in the vocabulary,
+    /// which only happens with vocabularies that do not cover all 256 bytes.
+    pub fn encode(&self, text: &str) -> Result<Vec<usize>> {
+        let mut ids = Vec::new();
+        for chunk in pre_tokenize(text) {
+            let mapped: String = chunk
+                .bytes()
+                .map(|b| self.byte_encoder[b as usize])
+                .collect();
+            for symbol in self.merge(&mapped) {
+                let id = self.encoder.get(&symbol).ok_or_else(|| {
+                    LlmError::token(format!("BPE symbol '{}' is not in the vocabulary", symbol))
+                })?;
+                ids.push(*id);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Decode token ids back into text. Invalid UTF-8 is replaced with U+FFFD.
+    ///
+    /// # Errors
+    /// Returns a token error for an id outside the vocabulary.
+    pub fn decode(&self, ids: &[usize]) -> Result<String> {
+        let mut bytes = Vec::new();
+        for id in ids {
+            let token = self
+                .decoder
+                .get(id)
+                .ok_or_else(|| LlmError::token(format!("Token ID {} not found", id)))?;
+            // Tokens that are not byte-mapped text (e.g. "<|endoftext|>") pass through
+            for c in token.chars() {
+                match self.byte_decoder.get(&c) {
+                    Some(&b) => bytes.push(b),
+                    None => bytes.extend_from_slice(c.to_string().as_bytes()),
+                }
+            }
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Apply the merge rules to one byte-mapped chunk.
+    fn merge(&self, chunk: &str) -> Vec<String> {
+        let mut symbols: Vec<String> = chunk.chars().map(String::from).collect();
+        loop {
+            let best = symbols
+                .windows(2)
+                .filter_map(|pair| {
+                    self.merges
+                        .get(&(pair[0].clone(), pair[1].clone()))
+                        .map(|&rank| (rank, pair[0].clone(), pair[1].clone()))
+                })
+                .min_by_key(|(rank, _, _)| *rank);
+            let Some((_, left, right)) = best else {
+                return symbols;
+            };
+
+            let mut merged = Vec::with_capacity(symbols.len());
+            let mut i = 0;
+            while i < symbols.len() {
+                if i + 1 < symbols.len() && symbols[i] == left && symbols[i + 1] == right {
+                    merged.push(format!("{}{}", left, right));
+                    i += 2;
+                } else {
+                    merged.push(symbols[i].clone());
+                    i += 1;
+                }
+            }
+            symbols = merged;
+        }
+    }
+}
+
+/// GPT-2's reversible mapping from bytes to printable characters: printable Latin-1
+/// bytes map to themselves, the rest to code points from U+0100 up (so space is `Ġ`).
+fn bytes_to_unicode() -> Vec<char> {
+    let printable = |b: u8| matches!(b, b'!'..=b'~' | 0xA1..=0xAC | 0xAE..=0xFF);
+    let mut next = 256u32;
+    (0..=255u8)
+        .map(|b| {
+            if printable(b) {
+                b as char
+            } else {
+                let c = char::from_u32(next).expect("code points below 0x200 are valid");
+                next += 1;
+                c
+            }
+        })
+        .collect()
+}
+
+/// Length of an English contraction suffix (`'s`, `'t`, `'re`, `'ve`, `'m`, `'ll`,
+/// `'d`) following an apostrophe, if `rest` starts with one.
+fn contraction_len(rest: &[char]) -> Option<usize> {
+    match rest {
+        ['r', 'e', ..] | ['v', 'e', ..] | ['l', 'l', ..] => Some(2),
+        ['s', ..] | ['t', ..] | ['m', ..] | ['d', ..] => Some(1),
+        _ => None,
+    }
+}
+
+/// Split text into chunks following GPT-2's pre-tokenization pattern.
+fn pre_tokenize(text: &str) -> Vec<String> {
+    #[derive(PartialEq)]
+    enum Class {
+        Letter,
+        Number,
+        Other,
+    }
+    let class = |c: char| {
+        if c.is_alphabetic() {
+            Class::Letter
+        } else if c.is_numeric() {
+            Class::Number
+        } else {
+            Class::Other
+        }
+    };
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut chunks = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        if chars[i] == '\'' {
+            if let Some(len) = contraction_len(&chars[i + 1..]) {
+                i += len + 1;
+                chunks.push(chars[start..i].iter().collect());
+                continue;
+            }
+        }
+
+        if chars[i].is_whitespace() {
+            let mut end = i;
+            while end < chars.len() && chars[end].is_whitespace() {
+                end += 1;
+            }
+            // A single space directly before a word belongs to that word
+            let ws_end = if end < chars.len() && chars[end - 1] == ' ' {
+                end - 1
+            } else {
+                end
+            };
+            if ws_end > start {
+                chunks.push(chars[start..ws_end].iter().collect());
+                i = ws_end;
+                continue;
+            }
+            i += 1;
+        }
+
+        let run = class(chars[i]);
+        i += 1;
+        while i < chars.len() && !chars[i].is_whitespace() && class(chars[i]) == run {
+            i += 1;
+        }
+        chunks.push(chars[start..i].iter().collect());
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pre_tokenize_matches_gpt2_chunks() {
+        assert_eq!(
+            pre_tokenize("Hello world, it's 2024!  ok\n"),
+            vec!["Hello", " world", ",", " it", "'s", " 2024", "!", " ", " ok", "\n"]
+        );
+    }
+
+    #[test]
+    fn test_bytes_to_unicode_is_a_bijection() {
+        let table = bytes_to_unicode();
+        assert_eq!(table[b' ' as usize], 'Ġ');
+        assert_eq!(table[b'a' as usize], 'a');
+        let unique: std::collections::HashSet<char> = table.iter().copied().collect();
+        assert_eq!(unique.len(), 256);
+    }
+}
@@ -2,11 +2,32 @@
 //!
 //! Provides save/load functionality for trained model parameters and state.
 
+use crate::config::Config;
+use crate::dataset_loader::DatasetCursor;
 use crate::error::{LlmError, Result};
+use crate::vocab::Vocab;
 use bincode::{Decode, Encode};
 use ndarray::Array2;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::thread::JoinHandle;
+
+/// Bytes every checkpoint written by this build starts with, followed by
+/// [`CHECKPOINT_FORMAT_VERSION`] as a little-endian `u32`.
+pub const CHECKPOINT_MAGIC: &[u8; 8] = b"RGPTCKPT";
+
+/// Checkpoint file format written by this build.
+///
+/// Bump on any change to the encoded layout and teach [`Checkpoint::load`] to migrate
+/// the previous version:
+/// - 1: no header, just the checkpoint (epoch, loss, parameters and metadata, later
+///   also the dataset cursor)
+/// - 2: a copy of the metadata before the checkpoint, which later gained shapes and
+///   vocabulary
+/// - 3: starts with [`CHECKPOINT_MAGIC`] and the format version
+pub const CHECKPOINT_FORMAT_VERSION: u32 = 3;
 
 /// Checkpoint for saving model state.
 #[derive(Serialize, Deserialize, Clone, Encode, Decode)]
@@ -17,21 +38,57 @@ pub struct Checkpoint {
     pub loss: f32,
     /// Model parameters (serialized)
     pub parameters: Vec<Vec<f32>>,
+    /// Shape of each entry in `parameters`
+    pub shapes: Vec<(usize, usize)>,
+    /// Vocabulary the parameters were trained with, in id order
+    pub vocab: Vec<String>,
+    /// Dataset position to resume training from
+    pub cursor: DatasetCursor,
     /// Metadata about the checkpoint
     pub metadata: CheckpointMetadata,
 }
 
 /// Metadata for a checkpoint.
-#[derive(Serialize, Deserialize, Clone, Debug, Encode, Decode)]
+///
+/// Saved files start with a copy of the metadata, so it can be read with
+/// [`Checkpoint::load_metadata`] without decoding the parameters.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Encode, Decode)]
 pub struct CheckpointMetadata {
+    /// File format version, see [`CHECKPOINT_FORMAT_VERSION`]
+    pub format_version: u32,
+    /// Model version/epoch, mirrored from the checkpoint when saved
+    pub epoch: usize,
+    /// Training loss, mirrored from the checkpoint when saved
+    pub loss: f32,
     /// Timestamp of checkpoint creation
     pub created_at: String,
-    /// Model configuration
+    /// Model configuration; [`crate::LLM::save_pretrained`] stores the full [`Config`]
+    /// as JSON, read back with [`CheckpointMetadata::parsed_config`]
     pub config: String,
     /// Training step
     pub step: usize,
 }
 
+impl CheckpointMetadata {
+    /// Embed `config` as JSON.
+    pub fn set_config(&mut self, config: &Config) -> Result<()> {
+        self.config = serde_json::to_string(config)
+            .map_err(|e| LlmError::serialization(format!("Failed to serialize config: {}", e)))?;
+        Ok(())
+    }
+
+    /// The [`Config`] embedded by [`CheckpointMetadata::set_config`].
+    ///
+    /// # Errors
+    /// Returns `ConfigError` if the checkpoint holds a free-form label (as written by
+    /// [`Checkpoint::new`]) instead of a serialized config.
+    pub fn parsed_config(&self) -> Result<Config> {
+        serde_json::from_str(&self.config).map_err(|e| {
+            LlmError::ConfigError(format!("Checkpoint has no embedded model config: {}", e))
+        })
+    }
+}
+
 impl Checkpoint {
     /// Create a new checkpoint.
     pub fn new(epoch: usize, loss: f32, config: &str) -> Self {
@@ -39,7 +96,13 @@ impl Checkpoint {
             epoch,
             loss,
             parameters: Vec::new(),
+            shapes: Vec::new(),
+            vocab: Vec::new(),
+            cursor: DatasetCursor::default(),
             metadata: CheckpointMetadata {
+                format_version: CHECKPOINT_FORMAT_VERSION,
+                epoch,
+                loss,
                 created_at: chrono::Local::now().to_rfc3339(),
                 config: config.to_string(),
                 step: epoch,
@@ -50,29 +113,406 @@ impl Checkpoint {
     /// Add a parameter matrix to the checkpoint.
     pub fn add_parameter(&mut self, matrix: &Array2<f32>) {
         self.parameters.push(matrix.iter().copied().collect());
+        self.shapes.push(matrix.dim());
+    }
+
+    /// Rebuild parameter `index` as a matrix.
+    pub fn parameter(&self, index: usize) -> Result<Array2<f32>> {
+        let (Some(values), Some(&shape)) = (self.parameters.get(index), self.shapes.get(index))
+        else {
+            return Err(LlmError::Other(format!(
+                "Checkpoint has no parameter {}",
+                index
+            )));
+        };
+        Array2::from_shape_vec(shape, values.clone())
+            .map_err(|_| LlmError::shape_mismatch(shape.0 * shape.1, values.len()))
+    }
+
+    /// Compare the saved vocabulary with `vocab`, matching tokens by word.
+    pub fn reconcile_vocab(&self, vocab: &Vocab) -> VocabReconciliation {
+        let saved_ids: std::collections::HashMap<&str, usize> = self
+            .vocab
+            .iter()
+            .enumerate()
+            .map(|(id, word)| (word.as_str(), id))
+            .collect();
+        let old_ids: Vec<Option<usize>> = vocab
+            .words
+            .iter()
+            .map(|word| saved_ids.get(word.as_str()).copied())
+            .collect();
+        let added = vocab
+            .words
+            .iter()
+            .zip(&old_ids)
+            .filter(|(_, old_id)| old_id.is_none())
+            .map(|(word, _)| word.clone())
+            .collect();
+        let dropped = self
+            .vocab
+            .iter()
+            .filter(|word| vocab.encode(word).is_none())
+            .cloned()
+            .collect();
+
+        VocabReconciliation {
+            old_ids,
+            added,
+            dropped,
+        }
+    }
+
+    /// Metadata as written to the file header, with the current format version, epoch
+    /// and loss.
+    fn header(&self) -> CheckpointMetadata {
+        CheckpointMetadata {
+            format_version: CHECKPOINT_FORMAT_VERSION,
+            epoch: self.epoch,
+            loss: self.loss,
+            ..self.metadata.clone()
+        }
     }
 
     /// Save checkpoint to file.
     pub fn save(&self, path: &Path) -> Result<()> {
-        let serialized =
-            bincode::encode_to_vec(self, bincode::config::standard()).map_err(|e| {
+        let file = std::fs::File::create(path).map_err(LlmError::IoError)?;
+        let mut writer = BufWriter::new(file);
+        writer
+            .write_all(CHECKPOINT_MAGIC)
+            .and_then(|_| writer.write_all(&CHECKPOINT_FORMAT_VERSION.to_le_bytes()))
+            .map_err(LlmError::IoError)?;
+        let config = bincode::config::standard();
+        bincode::encode_into_std_write(self.header(), &mut writer, config)
+            .and_then(|_| bincode::encode_into_std_write(self, &mut writer, config))
+            .map_err(|e| {
                 LlmError::serialization(format!("Failed to serialize checkpoint: {}", e))
             })?;
-        std::fs::write(path, serialized).map_err(LlmError::IoError)?;
+        writer.flush().map_err(LlmError::IoError)?;
         tracing::info!("Checkpoint saved to {:?}", path);
         Ok(())
     }
 
-    /// Load checkpoint from file.
+    /// Load checkpoint from file, migrating older format versions.
+    ///
+    /// # Errors
+    /// Returns `SerializationError` if the file is corrupt or was written in a format
+    /// version this build does not know.
     pub fn load(path: &Path) -> Result<Self> {
         let data = std::fs::read(path).map_err(LlmError::IoError)?;
-        let (checkpoint, _) =
-            bincode::decode_from_slice::<Self, _>(&data, bincode::config::standard()).map_err(
-                |e| LlmError::serialization(format!("Failed to deserialize checkpoint: {}", e)),
-            )?;
+        let checkpoint = match data.strip_prefix(CHECKPOINT_MAGIC.as_slice()) {
+            Some(rest) => {
+                let body = versioned_body(rest, path)?;
+                let config = bincode::config::standard();
+                bincode::decode_from_slice::<CheckpointMetadata, _>(body, config)
+                    .and_then(|(_, read)| {
+                        bincode::decode_from_slice::<Self, _>(&body[read..], config)
+                    })
+                    .map(|(checkpoint, _)| checkpoint)
+                    .map_err(|e| {
+                        LlmError::serialization(format!("Failed to deserialize checkpoint: {}", e))
+                    })?
+            }
+            None => {
+                let (version, checkpoint) = decode_legacy(&data).ok_or_else(|| {
+                    LlmError::serialization(format!(
+                        "Failed to deserialize checkpoint {:?}: not a checkpoint file or corrupt",
+                        path
+                    ))
+                })?;
+                tracing::info!(
+                    "Migrated checkpoint {:?} from format version {}",
+                    path,
+                    version
+                );
+                checkpoint
+            }
+        };
         tracing::info!("Checkpoint loaded from {:?}", path);
         Ok(checkpoint)
     }
+
+    /// Read only the metadata header of a saved checkpoint, leaving the parameters
+    /// undecoded. Files older than format version 3 are loaded in full.
+    pub fn load_metadata(path: &Path) -> Result<CheckpointMetadata> {
+        let file = std::fs::File::open(path).map_err(LlmError::IoError)?;
+        let mut reader = BufReader::new(file);
+        let mut prefix = [0u8; CHECKPOINT_MAGIC.len() + 4];
+        if reader.read_exact(&mut prefix).is_err() || !prefix.starts_with(CHECKPOINT_MAGIC) {
+            return Self::load(path).map(|checkpoint| checkpoint.metadata);
+        }
+        versioned_body(&prefix[CHECKPOINT_MAGIC.len()..], path)?;
+        bincode::decode_from_std_read(&mut reader, bincode::config::standard()).map_err(|e| {
+            LlmError::serialization(format!("Failed to read checkpoint metadata: {}", e))
+        })
+    }
+}
+
+/// Check the format version following [`CHECKPOINT_MAGIC`] and return the bytes after it.
+fn versioned_body<'a>(data: &'a [u8], path: &Path) -> Result<&'a [u8]> {
+    let (version, body) = data
+        .split_first_chunk::<4>()
+        .ok_or_else(|| LlmError::serialization(format!("Checkpoint {:?} is truncated", path)))?;
+    match u32::from_le_bytes(*version) {
+        CHECKPOINT_FORMAT_VERSION => Ok(body),
+        version => Err(LlmError::serialization(format!(
+            "Unsupported checkpoint version {} in {:?} (this build reads versions 1 to {})",
+            version, path, CHECKPOINT_FORMAT_VERSION
+        ))),
+    }
+}
+
+/// Weights and vocabulary only, for deploying a trained model.
+///
+/// Unlike a [`Checkpoint`] it carries no training cursor or metadata, so it is the
+/// smallest file a model can be restored from. Write one with
+/// [`crate::LLM::export_inference_bundle`].
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct InferenceBundle {
+    /// Vocabulary the weights were trained with, in id order
+    pub vocab: Vec<String>,
+    /// Shape of each entry in `parameters`
+    pub shapes: Vec<(usize, usize)>,
+    /// Every layer's weights in row-major order, in [`crate::Layer::weights`] order
+    pub parameters: Vec<Vec<f32>>,
+}
+
+impl InferenceBundle {
+    /// Strip a checkpoint down to its weights and vocabulary.
+    pub fn from_checkpoint(checkpoint: Checkpoint) -> Self {
+        Self {
+            vocab: checkpoint.vocab,
+            shapes: checkpoint.shapes,
+            parameters: checkpoint.parameters,
+        }
+    }
+
+    /// A checkpoint holding the bundle's weights, loadable with
+    /// [`crate::LLM::load_checkpoint`].
+    pub fn to_checkpoint(&self) -> Checkpoint {
+        let mut checkpoint = Checkpoint::new(0, 0.0, "inference");
+        checkpoint.vocab = self.vocab.clone();
+        checkpoint.shapes = self.shapes.clone();
+        checkpoint.parameters = self.parameters.clone();
+        checkpoint
+    }
+
+    /// Save the bundle to file.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = std::fs::File::create(path).map_err(LlmError::IoError)?;
+        let mut writer = BufWriter::new(file);
+        bincode::encode_into_std_write(self, &mut writer, bincode::config::standard()).map_err(
+            |e| LlmError::serialization(format!("Failed to serialize inference bundle: {}", e)),
+        )?;
+        writer.flush().map_err(LlmError::IoError)?;
+        tracing::info!("Inference bundle saved to {:?}", path);
+        Ok(())
+    }
+
+    /// Load a bundle from file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path).map_err(LlmError::IoError)?;
+        bincode::decode_from_std_read(&mut BufReader::new(file), bincode::config::standard())
+            .map_err(|e| {
+                LlmError::serialization(format!("Failed to deserialize inference bundle: {}", e))
+            })
+    }
+}
+
+/// Upper bound on the bytes a legacy layout may claim while guessing which one a file
+/// uses, so a wrong guess fails on a garbage length instead of allocating it.
+const LEGACY_DECODE_LIMIT: usize = 1 << 30;
+
+/// Metadata of a format version 1 checkpoint.
+#[derive(Encode, Decode)]
+struct MetadataV1 {
+    created_at: String,
+    config: String,
+    step: usize,
+}
+
+/// Format version 1, as first released: no header and no dataset cursor.
+#[derive(Encode, Decode)]
+struct CheckpointV1 {
+    epoch: usize,
+    loss: f32,
+    parameters: Vec<Vec<f32>>,
+    metadata: MetadataV1,
+}
+
+/// Format version 1 after the dataset cursor was added.
+#[derive(Encode, Decode)]
+struct CheckpointV1Cursor {
+    epoch: usize,
+    loss: f32,
+    parameters: Vec<Vec<f32>>,
+    cursor: DatasetCursor,
+    metadata: MetadataV1,
+}
+
+/// Metadata of a format version 2 checkpoint, also written as its header.
+#[derive(Encode, Decode)]
+struct MetadataV2 {
+    epoch: usize,
+    loss: f32,
+    created_at: String,
+    config: String,
+    step: usize,
+}
+
+/// Format version 2 body, before shapes and vocabulary were recorded.
+#[derive(Encode, Decode)]
+struct CheckpointV2 {
+    epoch: usize,
+    loss: f32,
+    parameters: Vec<Vec<f32>>,
+    cursor: DatasetCursor,
+    metadata: MetadataV2,
+}
+
+/// Format version 2 body with shapes and vocabulary.
+#[derive(Encode, Decode)]
+struct CheckpointV2Vocab {
+    epoch: usize,
+    loss: f32,
+    parameters: Vec<Vec<f32>>,
+    shapes: Vec<(usize, usize)>,
+    vocab: Vec<String>,
+    cursor: DatasetCursor,
+    metadata: MetadataV2,
+}
+
+/// Current metadata with the given fields from an older checkpoint.
+fn migrated_metadata(
+    epoch: usize,
+    loss: f32,
+    created_at: String,
+    config: String,
+    step: usize,
+) -> CheckpointMetadata {
+    CheckpointMetadata {
+        format_version: CHECKPOINT_FORMAT_VERSION,
+        epoch,
+        loss,
+        created_at,
+        config,
+        step,
+    }
+}
+
+/// Decode `data` as `T`, requiring every byte to be consumed.
+fn decode_exact<T: Decode<()>>(data: &[u8]) -> Option<T> {
+    let config = bincode::config::standard().with_limit::<LEGACY_DECODE_LIMIT>();
+    match bincode::decode_from_slice(data, config) {
+        Ok((value, read)) if read == data.len() => Some(value),
+        _ => None,
+    }
+}
+
+/// Decode `data` as a `H` header followed by a `T` body, requiring every byte to be
+/// consumed.
+fn decode_with_header<H: Decode<()>, T: Decode<()>>(data: &[u8]) -> Option<(H, T)> {
+    let config = bincode::config::standard().with_limit::<LEGACY_DECODE_LIMIT>();
+    let (header, read) = bincode::decode_from_slice::<H, _>(data, config).ok()?;
+    Some((header, decode_exact(&data[read..])?))
+}
+
+/// Decode a checkpoint written without [`CHECKPOINT_MAGIC`], returning its format
+/// version and the checkpoint upgraded to the current layout.
+///
+/// Older layouts carry no version marker, so each known layout is tried, newest
+/// first. Shapes and vocabulary are left empty when the file has none, so loading
+/// assumes the model's own, and a checkpoint without a cursor resumes from the start
+/// of its epoch.
+fn decode_legacy(data: &[u8]) -> Option<(u32, Checkpoint)> {
+    if let Some((header, mut checkpoint)) =
+        decode_with_header::<CheckpointMetadata, Checkpoint>(data)
+    {
+        if header.format_version == 2 {
+            checkpoint.metadata.format_version = CHECKPOINT_FORMAT_VERSION;
+            return Some((2, checkpoint));
+        }
+    }
+    if let Some((_, old)) = decode_with_header::<MetadataV2, CheckpointV2Vocab>(data) {
+        let m = old.metadata;
+        return Some((
+            2,
+            Checkpoint {
+                epoch: old.epoch,
+                loss: old.loss,
+                parameters: old.parameters,
+                shapes: old.shapes,
+                vocab: old.vocab,
+                cursor: old.cursor,
+                metadata: migrated_metadata(m.epoch, m.loss, m.created_at, m.config, m.step),
+            },
+        ));
+    }
+    if let Some((_, old)) = decode_with_header::<MetadataV2, CheckpointV2>(data) {
+        let m = old.metadata;
+        return Some((
+            2,
+            Checkpoint {
+                epoch: old.epoch,
+                loss: old.loss,
+                parameters: old.parameters,
+                shapes: Vec::new(),
+                vocab: Vec::new(),
+                cursor: old.cursor,
+                metadata: migrated_metadata(m.epoch, m.loss, m.created_at, m.config, m.step),
+            },
+        ));
+    }
+    let (epoch, loss, parameters, cursor, m) =
+        if let Some(old) = decode_exact::<CheckpointV1Cursor>(data) {
+            (
+                old.epoch,
+                old.loss,
+                old.parameters,
+                old.cursor,
+                old.metadata,
+            )
+        } else {
+            let old = decode_exact::<CheckpointV1>(data)?;
+            let cursor = DatasetCursor::new(old.epoch, 0);
+            (old.epoch, old.loss, old.parameters, cursor, old.metadata)
+        };
+    Some((
+        1,
+        Checkpoint {
+            epoch,
+            loss,
+            parameters,
+            shapes: Vec::new(),
+            vocab: Vec::new(),
+            cursor,
+            metadata: migrated_metadata(epoch, loss, m.created_at, m.config, m.step),
+        },
+    ))
+}
+
+/// How a checkpoint's vocabulary lines up with the current one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VocabReconciliation {
+    /// For each current token id, the id of the same word in the checkpoint vocabulary
+    pub old_ids: Vec<Option<usize>>,
+    /// Current tokens missing from the checkpoint, which start from fresh parameters
+    pub added: Vec<String>,
+    /// Checkpoint tokens missing from the current vocabulary, whose parameters are lost
+    pub dropped: Vec<String>,
+}
+
+impl VocabReconciliation {
+    /// Whether both vocabularies hold the same words with the same ids.
+    pub fn is_identity(&self) -> bool {
+        self.added.is_empty()
+            && self.dropped.is_empty()
+            && self
+                .old_ids
+                .iter()
+                .enumerate()
+                .all(|(id, old_id)| *old_id == Some(id))
+    }
 }
 
 /// Checkpoint manager for handling multiple checkpoints.
@@ -80,6 +520,13 @@ pub struct CheckpointManager {
     checkpoint_dir: std::path::PathBuf,
     keep_best: bool,
     max_checkpoints: usize,
+    writer: Mutex<Option<AsyncWriter>>,
+}
+
+/// Background worker that writes queued checkpoints one at a time.
+struct AsyncWriter {
+    sender: mpsc::Sender<Checkpoint>,
+    handle: JoinHandle<Result<()>>,
 }
 
 impl CheckpointManager {
@@ -90,6 +537,7 @@ impl CheckpointManager {
             checkpoint_dir: checkpoint_dir.to_path_buf(),
             keep_best,
             max_checkpoints,
+            writer: Mutex::new(None),
         })
     }
 
@@ -105,6 +553,66 @@ impl CheckpointManager {
         Ok(())
     }
 
+    /// Queue a checkpoint to be serialized and written on a background thread.
+    ///
+    /// Writes are processed in order by a single worker, so saves never race on the same
+    /// file. Call [`CheckpointManager::flush`] to wait for outstanding writes.
+    pub fn save_async(&self, checkpoint: Checkpoint) -> Result<()> {
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| LlmError::Other("Checkpoint writer lock poisoned".to_string()))?;
+
+        let writer = writer.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel::<Checkpoint>();
+            let worker = CheckpointManager {
+                checkpoint_dir: self.checkpoint_dir.clone(),
+                keep_best: self.keep_best,
+                max_checkpoints: self.max_checkpoints,
+                writer: Mutex::new(None),
+            };
+            let handle = std::thread::spawn(move || {
+                let mut result = Ok(());
+                for checkpoint in receiver {
+                    if let Err(e) = worker.save(&checkpoint) {
+                        tracing::error!("Async checkpoint save failed: {}", e);
+                        if result.is_ok() {
+                            result = Err(e);
+                        }
+                    }
+                }
+                result
+            });
+            AsyncWriter { sender, handle }
+        });
+
+        writer
+            .sender
+            .send(checkpoint)
+            .map_err(|_| LlmError::Other("Checkpoint writer has stopped".to_string()))
+    }
+
+    /// Wait for all checkpoints queued with [`CheckpointManager::save_async`] to be
+    /// written, returning the first error encountered.
+    pub fn flush(&self) -> Result<()> {
+        let writer = self
+            .writer
+            .lock()
+            .map_err(|_| LlmError::Other("Checkpoint writer lock poisoned".to_string()))?
+            .take();
+
+        match writer {
+            Some(AsyncWriter { sender, handle }) => {
+                // Closing the channel lets the worker finish the queue and exit
+                drop(sender);
+                handle
+                    .join()
+                    .map_err(|_| LlmError::Other("Checkpoint writer panicked".to_string()))?
+            }
+            None => Ok(()),
+        }
+    }
+
     /// Load the best checkpoint.
     pub fn load_best(&self) -> Result<Checkpoint> {
         let mut checkpoints = self.list_checkpoints()?;
@@ -117,6 +625,71 @@ impl CheckpointManager {
         }
     }
 
+    /// Average the parameters of the checkpoints at `paths` into one ("model soup").
+    ///
+    /// Every checkpoint must have the same parameter shapes and parameter lengths; the
+    /// lengths matter for migrated v1/v2 checkpoints, which carry no shapes. The result
+    /// keeps the first checkpoint's vocabulary, epoch and cursor, with its loss set to
+    /// the mean loss. Returns `ShapeMismatch` if the shapes or lengths differ, and
+    /// `VocabularyError` if the vocabularies differ, since their embedding and output
+    /// rows would not line up.
+    pub fn average_checkpoints(&self, paths: &[PathBuf]) -> Result<Checkpoint> {
+        let Some((first, rest)) = paths.split_first() else {
+            return Err(LlmError::ValidationError(
+                "No checkpoints to average".to_string(),
+            ));
+        };
+
+        let lengths = |checkpoint: &Checkpoint| -> Vec<usize> {
+            checkpoint.parameters.iter().map(Vec::len).collect()
+        };
+        let mut average = Checkpoint::load(first)?;
+        let expected_lengths = lengths(&average);
+        let mut total_loss = average.loss;
+        for path in rest {
+            let checkpoint = Checkpoint::load(path)?;
+            let actual_lengths = lengths(&checkpoint);
+            if checkpoint.shapes != average.shapes || actual_lengths != expected_lengths {
+                return Err(LlmError::shape_mismatch(
+                    format!(
+                        "shapes {:?}, parameter lengths {:?}",
+                        average.shapes, expected_lengths
+                    ),
+                    format!(
+                        "shapes {:?}, parameter lengths {:?} in {}",
+                        checkpoint.shapes,
+                        actual_lengths,
+                        path.display()
+                    ),
+                ));
+            }
+            if checkpoint.vocab != average.vocab {
+                return Err(LlmError::VocabularyError(format!(
+                    "vocabulary of {} differs from that of {}; checkpoints must share one \
+                     vocabulary to be averaged",
+                    path.display(),
+                    first.display()
+                )));
+            }
+            for (sum, values) in average.parameters.iter_mut().zip(&checkpoint.parameters) {
+                for (s, v) in sum.iter_mut().zip(values) {
+                    *s += v;
+                }
+            }
+            total_loss += checkpoint.loss;
+        }
+
+        let count = paths.len() as f32;
+        for values in &mut average.parameters {
+            for value in values.iter_mut() {
+                *value /= count;
+            }
+        }
+        average.loss = total_loss / count;
+        average.metadata.loss = average.loss;
+        Ok(average)
+    }
+
     /// List all available checkpoints with their losses.
     fn list_checkpoints(&self) -> Result<Vec<(std::path::PathBuf, f32)>> {
         let mut checkpoints = Vec::new();
@@ -126,8 +699,8 @@ impl CheckpointManager {
             let path = entry.path();
 
             if path.extension().is_some_and(|ext| ext == "bin") {
-                if let Ok(checkpoint) = Checkpoint::load(&path) {
-                    checkpoints.push((path, checkpoint.loss));
+                if let Ok(metadata) = Checkpoint::load_metadata(&path) {
+                    checkpoints.push((path, metadata.loss));
                 }
             }
         }
@@ -150,6 +723,14 @@ impl CheckpointManager {
     }
 }
 
+impl Drop for CheckpointManager {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            tracing::error!("Failed to flush checkpoints: {}", e);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,4 +741,306 @@ mod tests {
         assert_eq!(checkpoint.epoch, 0);
         assert_eq!(checkpoint.loss, 1.5);
     }
+
+    #[test]
+    fn test_embedded_config_round_trips() {
+        let mut config = Config::default();
+        config.model.num_blocks = 2;
+        config.model.seed = Some(7);
+        config.training.mix_ratio = Some(0.25);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.bin");
+        let mut checkpoint = Checkpoint::new(1, 0.5, "unused");
+        assert!(checkpoint.metadata.parsed_config().is_err());
+        checkpoint.metadata.set_config(&config).unwrap();
+        checkpoint.save(&path).unwrap();
+
+        let metadata = Checkpoint::load_metadata(&path).unwrap();
+        assert_eq!(metadata.parsed_config().unwrap(), config);
+    }
+
+    #[test]
+    fn test_save_async_then_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = CheckpointManager::new(dir.path(), false, 5).unwrap();
+
+        let mut checkpoint = Checkpoint::new(3, 0.75, "test_config");
+        checkpoint.add_parameter(&Array2::ones((2, 2)));
+        checkpoint.cursor = DatasetCursor::new(3, 7);
+        manager.save_async(checkpoint.clone()).unwrap();
+        manager.save_async(checkpoint).unwrap();
+        manager.flush().unwrap();
+
+        let path = dir.path().join("checkpoint_epoch_0003.bin");
+        let loaded = Checkpoint::load(&path).unwrap();
+        assert_eq!(loaded.epoch, 3);
+        assert_eq!(loaded.loss, 0.75);
+        assert_eq!(loaded.parameters, vec![vec![1.0; 4]]);
+        assert_eq!(loaded.cursor, DatasetCursor::new(3, 7));
+    }
+
+    #[test]
+    fn test_average_checkpoints_takes_elementwise_mean() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = CheckpointManager::new(dir.path(), false, 5).unwrap();
+
+        let first = dir.path().join("a.bin");
+        let mut checkpoint = Checkpoint::new(1, 1.0, "test_config");
+        checkpoint.add_parameter(&Array2::from_shape_vec((1, 2), vec![1.0, 2.0]).unwrap());
+        checkpoint.save(&first).unwrap();
+
+        let second = dir.path().join("b.bin");
+        let mut checkpoint = Checkpoint::new(2, 3.0, "test_config");
+        checkpoint.add_parameter(&Array2::from_shape_vec((1, 2), vec![3.0, 6.0]).unwrap());
+        checkpoint.save(&second).unwrap();
+
+        let average = manager
+            .average_checkpoints(&[first.clone(), second])
+            .unwrap();
+        assert_eq!(average.parameters, vec![vec![2.0, 4.0]]);
+        assert_eq!(average.loss, 2.0);
+        assert_eq!(average.metadata.loss, 2.0);
+
+        let mismatched = dir.path().join("c.bin");
+        let mut checkpoint = Checkpoint::new(3, 1.0, "test_config");
+        checkpoint.add_parameter(&Array2::zeros((2, 1)));
+        checkpoint.save(&mismatched).unwrap();
+        assert!(matches!(
+            manager.average_checkpoints(&[first.clone(), mismatched]),
+            Err(LlmError::ShapeMismatch { .. })
+        ));
+
+        // Same shapes, but the rows belong to different words
+        let save_with_vocab = |name: &str, words: &[&str]| {
+            let path = dir.path().join(name);
+            let mut checkpoint = Checkpoint::new(1, 1.0, "test_config");
+            checkpoint.add_parameter(&Array2::from_shape_vec((1, 2), vec![1.0, 2.0]).unwrap());
+            checkpoint.vocab = words.iter().map(|w| w.to_string()).collect();
+            checkpoint.save(&path).unwrap();
+            path
+        };
+        let sun_moon = save_with_vocab("d.bin", &["sun", "moon"]);
+        let moon_sun = save_with_vocab("e.bin", &["moon", "sun"]);
+        assert!(matches!(
+            manager.average_checkpoints(&[sun_moon, moon_sun]),
+            Err(LlmError::VocabularyError(_))
+        ));
+    }
+
+    #[test]
+    fn test_average_checkpoints_without_shapes_checks_parameter_lengths() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = CheckpointManager::new(dir.path(), false, 5).unwrap();
+
+        // Migrated v1/v2 checkpoints carry no shapes, so only the lengths can disagree
+        let save = |name: &str, parameters: Vec<Vec<f32>>| {
+            let path = dir.path().join(name);
+            let mut checkpoint = Checkpoint::new(1, 1.0, "test_config");
+            checkpoint.parameters = parameters;
+            checkpoint.save(&path).unwrap();
+            path
+        };
+        let base = save("a.bin", vec![vec![1.0, 2.0], vec![3.0]]);
+        let same = save("b.bin", vec![vec![3.0, 4.0], vec![5.0]]);
+        let shorter = save("c.bin", vec![vec![1.0], vec![3.0]]);
+        let fewer = save("d.bin", vec![vec![1.0, 2.0]]);
+
+        let average = manager.average_checkpoints(&[base.clone(), same]).unwrap();
+        assert_eq!(average.parameters, vec![vec![2.0, 3.0], vec![4.0]]);
+        for mismatched in [shorter, fewer] {
+            assert!(matches!(
+                manager.average_checkpoints(&[base.clone(), mismatched]),
+                Err(LlmError::ShapeMismatch { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_load_metadata_reads_header_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.bin");
+
+        let mut checkpoint = Checkpoint::new(2, 1.25, "test_config");
+        checkpoint.add_parameter(&Array2::ones((64, 64)));
+        checkpoint.epoch = 4;
+        checkpoint.loss = 0.5;
+        checkpoint.save(&path).unwrap();
+
+        let metadata = Checkpoint::load_metadata(&path).unwrap();
+        assert_eq!(metadata.epoch, 4);
+        assert_eq!(metadata.loss, 0.5);
+        assert_eq!(metadata.step, 2);
+        assert_eq!(metadata.config, "test_config");
+
+        // Cut the file right after the header: the parameters are gone, but the
+        // metadata is still readable
+        let header =
+            bincode::encode_to_vec(checkpoint.header(), bincode::config::standard()).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &data[..CHECKPOINT_MAGIC.len() + 4 + header.len()]).unwrap();
+        assert_eq!(Checkpoint::load_metadata(&path).unwrap(), metadata);
+        assert!(Checkpoint::load(&path).is_err());
+    }
+
+    fn encode<T: Encode>(value: T) -> Vec<u8> {
+        bincode::encode_to_vec(value, bincode::config::standard()).unwrap()
+    }
+
+    fn load_bytes(data: &[u8]) -> Result<Checkpoint> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.bin");
+        std::fs::write(&path, data).unwrap();
+        Checkpoint::load(&path)
+    }
+
+    // The legacy files below are built from tuples, which bincode encodes exactly like
+    // the structs older builds wrote, field by field
+
+    #[test]
+    fn test_load_migrates_headerless_v1() {
+        let parameters = vec![vec![1.0f32, 2.0, 3.0, 4.0]];
+        let metadata = (
+            "2024-01-01T00:00:00+00:00".to_string(),
+            "cfg".to_string(),
+            5usize,
+        );
+
+        // As first released: epoch, loss, parameters, metadata
+        let data = [encode((
+            5usize,
+            0.25f32,
+            parameters.clone(),
+            metadata.clone(),
+        ))]
+        .concat();
+        let loaded = load_bytes(&data).unwrap();
+        assert_eq!(loaded.metadata.format_version, CHECKPOINT_FORMAT_VERSION);
+        assert_eq!((loaded.epoch, loaded.loss), (5, 0.25));
+        assert_eq!((loaded.metadata.epoch, loaded.metadata.loss), (5, 0.25));
+        assert_eq!(loaded.parameters, parameters);
+        assert_eq!(loaded.metadata.config, "cfg");
+        assert_eq!(loaded.metadata.step, 5);
+        assert_eq!(loaded.cursor, DatasetCursor::new(5, 0));
+        assert!(loaded.shapes.is_empty() && loaded.vocab.is_empty());
+
+        // With the dataset cursor between parameters and metadata
+        let data = [encode((
+            5usize,
+            0.25f32,
+            parameters.clone(),
+            (5usize, 3usize),
+            metadata,
+        ))]
+        .concat();
+        let loaded = load_bytes(&data).unwrap();
+        assert_eq!(loaded.parameters, parameters);
+        assert_eq!(loaded.cursor, DatasetCursor::new(5, 3));
+        assert_eq!(loaded.metadata.created_at, "2024-01-01T00:00:00+00:00");
+    }
+
+    #[test]
+    fn test_load_migrates_v2_header_without_version() {
+        let parameters = vec![vec![1.0f32, 2.0], vec![3.0]];
+        let metadata = (
+            4usize,
+            0.5f32,
+            "2024-01-01T00:00:00+00:00".to_string(),
+            "cfg".to_string(),
+            9usize,
+        );
+        let cursor = (4usize, 2usize);
+
+        // Header, then epoch, loss, parameters, cursor, metadata
+        let data = [
+            encode(metadata.clone()),
+            encode((4usize, 0.5f32, parameters.clone(), cursor, metadata.clone())),
+        ]
+        .concat();
+        let loaded = load_bytes(&data).unwrap();
+        assert_eq!(loaded.metadata.format_version, CHECKPOINT_FORMAT_VERSION);
+        assert_eq!(loaded.parameters, parameters);
+        assert_eq!(loaded.cursor, DatasetCursor::new(4, 2));
+        assert_eq!(loaded.metadata.step, 9);
+        assert!(loaded.shapes.is_empty() && loaded.vocab.is_empty());
+
+        // With shapes and vocabulary after the parameters
+        let shapes = vec![(1usize, 2usize), (1, 1)];
+        let vocab = vec!["a".to_string(), "b".to_string()];
+        let data = [
+            encode(metadata.clone()),
+            encode((
+                4usize,
+                0.5f32,
+                parameters.clone(),
+                shapes.clone(),
+                vocab.clone(),
+                cursor,
+                metadata,
+            )),
+        ]
+        .concat();
+        let loaded = load_bytes(&data).unwrap();
+        assert_eq!(loaded.shapes, shapes);
+        assert_eq!(loaded.vocab, vocab);
+        assert_eq!(loaded.parameter(0).unwrap().dim(), (1, 2));
+        assert_eq!(loaded.cursor, DatasetCursor::new(4, 2));
+
+        // With the format version in the header but no magic in front
+        let mut checkpoint = Checkpoint::new(4, 0.5, "cfg");
+        checkpoint.add_parameter(&Array2::<f32>::ones((2, 2)));
+        checkpoint.metadata.format_version = 2;
+        let data = [encode(checkpoint.metadata.clone()), encode(&checkpoint)].concat();
+        let loaded = load_bytes(&data).unwrap();
+        assert_eq!(loaded.metadata.format_version, CHECKPOINT_FORMAT_VERSION);
+        assert_eq!(loaded.parameters, checkpoint.parameters);
+        assert_eq!(loaded.shapes, checkpoint.shapes);
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_versions_and_garbage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.bin");
+        Checkpoint::new(1, 0.5, "cfg").save(&path).unwrap();
+        let mut data = std::fs::read(&path).unwrap();
+        assert!(data.starts_with(CHECKPOINT_MAGIC));
+
+        data[CHECKPOINT_MAGIC.len()..CHECKPOINT_MAGIC.len() + 4]
+            .copy_from_slice(&99u32.to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+        for err in [
+            Checkpoint::load(&path).err().unwrap(),
+            Checkpoint::load_metadata(&path).err().unwrap(),
+        ] {
+            assert!(
+                err.to_string()
+                    .contains("Unsupported checkpoint version 99"),
+                "{}",
+                err
+            );
+        }
+
+        assert!(load_bytes(&[0xff; 64]).is_err());
+        assert!(load_bytes(&[]).is_err());
+    }
+
+    #[test]
+    fn test_load_metadata_reads_legacy_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.bin");
+        let metadata = (
+            "2024-01-01T00:00:00+00:00".to_string(),
+            "cfg".to_string(),
+            7usize,
+        );
+        std::fs::write(
+            &path,
+            encode((3usize, 1.5f32, vec![vec![0.0f32]], metadata)),
+        )
+        .unwrap();
+
+        let metadata = Checkpoint::load_metadata(&path).unwrap();
+        assert_eq!((metadata.epoch, metadata.loss, metadata.step), (3, 1.5, 7));
+        assert_eq!(metadata.format_version, CHECKPOINT_FORMAT_VERSION);
+    }
 }
@@ -3,11 +3,19 @@
 //! Supports loading from TOML/YAML files and environment variables with builder pattern.
 
 use crate::error::{LlmError, Result};
+use crate::feed_forward::FfnType;
+use crate::llm::{CurriculumStrategy, GradientClip};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::path::Path;
+use std::str::FromStr;
+
+/// Progress bar template used when `progress_template` is unset.
+pub const DEFAULT_PROGRESS_TEMPLATE: &str = "{msg}\n[{bar:40.cyan/blue}] {pos}/{len}";
 
 /// Main configuration structure for the LLM.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     /// Model configuration
     pub model: ModelConfig,
@@ -20,7 +28,7 @@ pub struct Config {
 }
 
 /// Model-specific configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ModelConfig {
     /// Embedding dimension (default: 128)
     pub embedding_dim: usize,
@@ -32,10 +40,32 @@ pub struct ModelConfig {
     pub num_blocks: usize,
     /// Vocabulary size (0 = dynamic from data)
     pub vocab_size: usize,
+    /// Seed for weight initialization (None = time-based)
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Feed-forward variant used in every transformer block ("relu" or "geglu")
+    #[serde(default)]
+    pub ffn_type: FfnType,
+    /// Factor applied to attention and feed-forward outputs before their residuals are
+    /// added; below 1.0 stabilizes deep stacks
+    #[serde(default = "default_residual_scale")]
+    pub residual_scale: f32,
+    /// Number of attention heads; attention is single-head in this build, so only 1 is
+    /// accepted (default: 1)
+    #[serde(default = "default_num_heads")]
+    pub num_heads: usize,
+}
+
+fn default_residual_scale() -> f32 {
+    1.0
+}
+
+fn default_num_heads() -> usize {
+    1
 }
 
 /// Training configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TrainingConfig {
     /// Pre-training epochs
     pub pretraining_epochs: usize,
@@ -47,16 +77,51 @@ pub struct TrainingConfig {
     pub finetuning_lr: f32,
     /// Gradient clipping threshold
     pub gradient_clip: f32,
+    /// Clip each layer's incoming gradient separately instead of the output gradient once
+    #[serde(default)]
+    pub clip_per_layer: bool,
     /// Batch size
     pub batch_size: usize,
     /// Enable checkpoint saving
     pub checkpoint_enabled: bool,
     /// Checkpoint interval (epochs)
     pub checkpoint_interval: usize,
+    /// Number of highest- and lowest-loss examples to report per epoch (0 disables)
+    #[serde(default)]
+    pub track_examples: usize,
+    /// Pack short samples together up to the maximum sequence length, masking attention
+    /// across sample boundaries
+    #[serde(default)]
+    pub pack_sequences: bool,
+    /// Probability of feeding the ground-truth token at each position during causal
+    /// training; below 1.0 the model's own predictions are fed back (scheduled sampling)
+    #[serde(default = "default_teacher_forcing_ratio")]
+    pub teacher_forcing_ratio: f32,
+    /// Order in which samples are visited each epoch; `None` keeps the data order
+    #[serde(default)]
+    pub curriculum: Option<CurriculumStrategy>,
+    /// Train in one phase that draws each sample from the pre-training data with this
+    /// probability and from the chat data otherwise, for `pretraining_epochs` at
+    /// `pretraining_lr`; `None` trains the two phases in turn
+    #[serde(default)]
+    pub mix_ratio: Option<f32>,
+    /// Keep the embeddings frozen for this many epochs at the start of each phase
+    #[serde(default)]
+    pub freeze_embeddings_epochs: usize,
+    /// Prompts to complete and log every `sample_interval` epochs
+    #[serde(default)]
+    pub sample_prompts: Vec<String>,
+    /// Epochs between logged completions of `sample_prompts`; 0 disables sampling
+    #[serde(default)]
+    pub sample_interval: usize,
+}
+
+fn default_teacher_forcing_ratio() -> f32 {
+    1.0
 }
 
 /// Data configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DataConfig {
     /// Path to pretraining data
     pub pretraining_data: String,
@@ -64,10 +129,26 @@ pub struct DataConfig {
     pub chat_training_data: String,
     /// Data format: "json" or "csv"
     pub format: String,
+    /// Treat the first row of CSV files as a header and skip it
+    #[serde(default)]
+    pub csv_has_headers: bool,
+    /// Normalize text (NFC, collapsed whitespace) before tokenization
+    #[serde(default)]
+    pub normalize_text: bool,
+    /// Lowercase text during normalization
+    #[serde(default)]
+    pub lowercase: bool,
+    /// Train on at most this many samples of each dataset; `None` or zero uses all
+    #[serde(default)]
+    pub max_samples: Option<usize>,
+    /// Split pre-training samples at sentence boundaries, keeping each chunk within
+    /// `max_seq_len` tokens
+    #[serde(default)]
+    pub split_sentences: bool,
 }
 
 /// Output configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OutputConfig {
     /// Output directory for checkpoints
     pub checkpoint_dir: String,
@@ -75,6 +156,16 @@ pub struct OutputConfig {
     pub log_level: String,
     /// Enable progress bars
     pub show_progress: bool,
+    /// indicatif template for progress bars; empty uses [`DEFAULT_PROGRESS_TEMPLATE`]
+    #[serde(default)]
+    pub progress_template: String,
+    /// Minimum milliseconds between progress bar redraws
+    #[serde(default = "default_progress_refresh_ms")]
+    pub progress_refresh_ms: u64,
+}
+
+fn default_progress_refresh_ms() -> u64 {
+    50
 }
 
 impl Default for Config {
@@ -97,10 +188,32 @@ impl Default for ModelConfig {
             max_seq_len: 80,
             num_blocks: 3,
             vocab_size: 0,
+            seed: None,
+            ffn_type: FfnType::default(),
+            residual_scale: default_residual_scale(),
+            num_heads: default_num_heads(),
         }
     }
 }
 
+impl ModelConfig {
+    /// Resolve the initialization seed: a CLI value takes precedence over the configured
+    /// seed, and a time-based seed is used when neither is set.
+    pub fn resolve_seed(&self, cli_seed: Option<u64>) -> u64 {
+        cli_seed
+            .or(self.seed)
+            .unwrap_or_else(crate::rng::time_based_seed)
+    }
+}
+
+impl DataConfig {
+    /// Build the text normalizer described by this configuration, if enabled.
+    pub fn normalizer(&self) -> Option<crate::TextNormalizer> {
+        self.normalize_text
+            .then(|| crate::TextNormalizer::new(self.lowercase))
+    }
+}
+
 impl Default for TrainingConfig {
     fn default() -> Self {
         Self {
@@ -109,9 +222,18 @@ impl Default for TrainingConfig {
             pretraining_lr: 0.0005,
             finetuning_lr: 0.0001,
             gradient_clip: 5.0,
+            clip_per_layer: false,
             batch_size: 32,
             checkpoint_enabled: true,
             checkpoint_interval: 10,
+            track_examples: 0,
+            pack_sequences: false,
+            teacher_forcing_ratio: default_teacher_forcing_ratio(),
+            curriculum: None,
+            mix_ratio: None,
+            freeze_embeddings_epochs: 0,
+            sample_prompts: Vec::new(),
+            sample_interval: 0,
         }
     }
 }
@@ -122,6 +244,11 @@ impl Default for DataConfig {
             pretraining_data: "data/pretraining_data.json".to_string(),
             chat_training_data: "data/chat_training_data.json".to_string(),
             format: "json".to_string(),
+            csv_has_headers: false,
+            normalize_text: false,
+            lowercase: false,
+            max_samples: None,
+            split_sentences: false,
         }
     }
 }
@@ -132,6 +259,49 @@ impl Default for OutputConfig {
             checkpoint_dir: "./checkpoints".to_string(),
             log_level: "info".to_string(),
             show_progress: true,
+            progress_template: String::new(),
+            progress_refresh_ms: default_progress_refresh_ms(),
+        }
+    }
+}
+
+impl OutputConfig {
+    /// Progress bar style built from `progress_template`.
+    ///
+    /// # Errors
+    /// Returns `ConfigError` if the template does not parse.
+    pub fn progress_style(&self) -> Result<ProgressStyle> {
+        let template = if self.progress_template.is_empty() {
+            DEFAULT_PROGRESS_TEMPLATE
+        } else {
+            &self.progress_template
+        };
+        ProgressStyle::default_bar()
+            .template(template)
+            .map_err(|e| {
+                LlmError::ConfigError(format!("Invalid progress_template {:?}: {}", template, e))
+            })
+    }
+
+    /// A progress bar of `len` steps using the configured style and refresh rate.
+    pub fn progress_bar(&self, len: u64) -> Result<ProgressBar> {
+        let refresh_hz = (1000 / self.progress_refresh_ms.max(1)).clamp(1, u8::MAX as u64);
+        let pb = ProgressBar::with_draw_target(
+            Some(len),
+            ProgressDrawTarget::stderr_with_hz(refresh_hz as u8),
+        );
+        pb.set_style(self.progress_style()?);
+        Ok(pb)
+    }
+}
+
+impl TrainingConfig {
+    /// The clipping mode selected by `gradient_clip` and `clip_per_layer`.
+    pub fn gradient_clip_mode(&self) -> GradientClip {
+        if self.clip_per_layer {
+            GradientClip::PerLayer(self.gradient_clip)
+        } else {
+            GradientClip::Global(self.gradient_clip)
         }
     }
 }
@@ -154,47 +324,272 @@ impl Config {
     }
 
     /// Load configuration from environment variables.
+    ///
+    /// Starts from [`Config::default`] and applies [`Config::apply_env`].
     pub fn from_env() -> Result<Self> {
+        let mut config = Config::default();
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    /// Override fields from `LLM_<SECTION>_<FIELD>` environment variables (and a `.env`
+    /// file, if present), e.g. `LLM_TRAINING_BATCH_SIZE` for `training.batch_size`.
+    ///
+    /// `LLM_MODEL_SEED` may be empty for time-based seeding. The older
+    /// `LLM_EMBEDDING_DIM`, `LLM_HIDDEN_DIM`, `LLM_MAX_SEQ_LEN` and `LLM_PRETRAINING_LR`
+    /// names are still read, with the nested names taking precedence.
+    ///
+    /// # Errors
+    /// Returns `ConfigError` naming the variable whose value does not parse.
+    pub fn apply_env(&mut self) -> Result<()> {
         dotenv::dotenv().ok();
+        self.apply_env_with(|name| std::env::var(name).ok())
+    }
 
-        let mut config = Config::default();
+    /// [`Config::apply_env`] reading variables through `lookup` instead of the process
+    /// environment, which returns the value of a variable or `None` if it is unset.
+    ///
+    /// # Errors
+    /// Returns `ConfigError` naming the variable whose value does not parse.
+    pub fn apply_env_with(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
+        env_override(&lookup, "LLM_EMBEDDING_DIM", &mut self.model.embedding_dim)?;
+        env_override(&lookup, "LLM_HIDDEN_DIM", &mut self.model.hidden_dim)?;
+        env_override(&lookup, "LLM_MAX_SEQ_LEN", &mut self.model.max_seq_len)?;
+        env_override(
+            &lookup,
+            "LLM_PRETRAINING_LR",
+            &mut self.training.pretraining_lr,
+        )?;
 
-        if let Ok(val) = std::env::var("LLM_EMBEDDING_DIM") {
-            config.model.embedding_dim = val.parse().map_err(|_| {
-                LlmError::ConfigError("Invalid LLM_EMBEDDING_DIM value".to_string())
-            })?;
+        let model = &mut self.model;
+        env_override(&lookup, "LLM_MODEL_EMBEDDING_DIM", &mut model.embedding_dim)?;
+        env_override(&lookup, "LLM_MODEL_HIDDEN_DIM", &mut model.hidden_dim)?;
+        env_override(&lookup, "LLM_MODEL_MAX_SEQ_LEN", &mut model.max_seq_len)?;
+        env_override(&lookup, "LLM_MODEL_NUM_BLOCKS", &mut model.num_blocks)?;
+        env_override(&lookup, "LLM_MODEL_VOCAB_SIZE", &mut model.vocab_size)?;
+        env_override(&lookup, "LLM_MODEL_FFN_TYPE", &mut model.ffn_type)?;
+        env_override(
+            &lookup,
+            "LLM_MODEL_RESIDUAL_SCALE",
+            &mut model.residual_scale,
+        )?;
+        env_override(&lookup, "LLM_MODEL_NUM_HEADS", &mut model.num_heads)?;
+        if let Some(val) = lookup("LLM_MODEL_SEED") {
+            model.seed = if val.trim().is_empty() {
+                None
+            } else {
+                Some(parse_env("LLM_MODEL_SEED", &val)?)
+            };
         }
 
-        if let Ok(val) = std::env::var("LLM_HIDDEN_DIM") {
-            config.model.hidden_dim = val
-                .parse()
-                .map_err(|_| LlmError::ConfigError("Invalid LLM_HIDDEN_DIM value".to_string()))?;
+        let training = &mut self.training;
+        env_override(
+            &lookup,
+            "LLM_TRAINING_PRETRAINING_EPOCHS",
+            &mut training.pretraining_epochs,
+        )?;
+        env_override(
+            &lookup,
+            "LLM_TRAINING_FINETUNING_EPOCHS",
+            &mut training.finetuning_epochs,
+        )?;
+        env_override(
+            &lookup,
+            "LLM_TRAINING_PRETRAINING_LR",
+            &mut training.pretraining_lr,
+        )?;
+        env_override(
+            &lookup,
+            "LLM_TRAINING_FINETUNING_LR",
+            &mut training.finetuning_lr,
+        )?;
+        env_override(
+            &lookup,
+            "LLM_TRAINING_GRADIENT_CLIP",
+            &mut training.gradient_clip,
+        )?;
+        env_override(
+            &lookup,
+            "LLM_TRAINING_CLIP_PER_LAYER",
+            &mut training.clip_per_layer,
+        )?;
+        env_override(&lookup, "LLM_TRAINING_BATCH_SIZE", &mut training.batch_size)?;
+        env_override(
+            &lookup,
+            "LLM_TRAINING_CHECKPOINT_ENABLED",
+            &mut training.checkpoint_enabled,
+        )?;
+        env_override(
+            &lookup,
+            "LLM_TRAINING_CHECKPOINT_INTERVAL",
+            &mut training.checkpoint_interval,
+        )?;
+        env_override(
+            &lookup,
+            "LLM_TRAINING_TRACK_EXAMPLES",
+            &mut training.track_examples,
+        )?;
+        env_override(
+            &lookup,
+            "LLM_TRAINING_PACK_SEQUENCES",
+            &mut training.pack_sequences,
+        )?;
+        env_override(
+            &lookup,
+            "LLM_TRAINING_TEACHER_FORCING_RATIO",
+            &mut training.teacher_forcing_ratio,
+        )?;
+        if let Some(val) = lookup("LLM_TRAINING_CURRICULUM") {
+            training.curriculum = if val.trim().is_empty() {
+                None
+            } else {
+                Some(parse_env("LLM_TRAINING_CURRICULUM", &val)?)
+            };
         }
-
-        if let Ok(val) = std::env::var("LLM_MAX_SEQ_LEN") {
-            config.model.max_seq_len = val
-                .parse()
-                .map_err(|_| LlmError::ConfigError("Invalid LLM_MAX_SEQ_LEN value".to_string()))?;
+        env_override(
+            &lookup,
+            "LLM_TRAINING_FREEZE_EMBEDDINGS_EPOCHS",
+            &mut training.freeze_embeddings_epochs,
+        )?;
+        if let Some(val) = lookup("LLM_TRAINING_SAMPLE_PROMPTS") {
+            // Prompts are separated by '|'; an empty value clears them
+            training.sample_prompts = val
+                .split('|')
+                .map(str::trim)
+                .filter(|prompt| !prompt.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        env_override(
+            &lookup,
+            "LLM_TRAINING_SAMPLE_INTERVAL",
+            &mut training.sample_interval,
+        )?;
+        if let Some(val) = lookup("LLM_TRAINING_MIX_RATIO") {
+            training.mix_ratio = if val.trim().is_empty() {
+                None
+            } else {
+                Some(parse_env("LLM_TRAINING_MIX_RATIO", &val)?)
+            };
         }
 
-        if let Ok(val) = std::env::var("LLM_PRETRAINING_LR") {
-            config.training.pretraining_lr = val.parse().map_err(|_| {
-                LlmError::ConfigError("Invalid LLM_PRETRAINING_LR value".to_string())
-            })?;
+        let data = &mut self.data;
+        env_override(
+            &lookup,
+            "LLM_DATA_PRETRAINING_DATA",
+            &mut data.pretraining_data,
+        )?;
+        env_override(
+            &lookup,
+            "LLM_DATA_CHAT_TRAINING_DATA",
+            &mut data.chat_training_data,
+        )?;
+        env_override(&lookup, "LLM_DATA_FORMAT", &mut data.format)?;
+        env_override(
+            &lookup,
+            "LLM_DATA_CSV_HAS_HEADERS",
+            &mut data.csv_has_headers,
+        )?;
+        env_override(&lookup, "LLM_DATA_NORMALIZE_TEXT", &mut data.normalize_text)?;
+        env_override(&lookup, "LLM_DATA_LOWERCASE", &mut data.lowercase)?;
+        env_override(
+            &lookup,
+            "LLM_DATA_SPLIT_SENTENCES",
+            &mut data.split_sentences,
+        )?;
+        if let Some(val) = lookup("LLM_DATA_MAX_SAMPLES") {
+            data.max_samples = if val.trim().is_empty() {
+                None
+            } else {
+                Some(parse_env("LLM_DATA_MAX_SAMPLES", &val)?)
+            };
         }
 
-        Ok(config)
+        let output = &mut self.output;
+        env_override(
+            &lookup,
+            "LLM_OUTPUT_CHECKPOINT_DIR",
+            &mut output.checkpoint_dir,
+        )?;
+        env_override(&lookup, "LLM_OUTPUT_LOG_LEVEL", &mut output.log_level)?;
+        env_override(
+            &lookup,
+            "LLM_OUTPUT_SHOW_PROGRESS",
+            &mut output.show_progress,
+        )?;
+        env_override(
+            &lookup,
+            "LLM_OUTPUT_PROGRESS_TEMPLATE",
+            &mut output.progress_template,
+        )?;
+        env_override(
+            &lookup,
+            "LLM_OUTPUT_PROGRESS_REFRESH_MS",
+            &mut output.progress_refresh_ms,
+        )?;
+
+        Ok(())
+    }
+
+    /// Serialize the configuration as pretty-printed TOML.
+    pub fn to_toml_string(&self) -> Result<String> {
+        toml::to_string_pretty(self)
+            .map_err(|e| LlmError::ConfigError(format!("Failed to serialize config: {}", e)))
     }
 
     /// Save configuration to a TOML file.
     pub fn save_toml(&self, path: &Path) -> Result<()> {
-        let content = toml::to_string_pretty(self)
-            .map_err(|e| LlmError::ConfigError(format!("Failed to serialize config: {}", e)))?;
+        let content = self.to_toml_string()?;
         std::fs::write(path, content)
             .map_err(|e| LlmError::ConfigError(format!("Failed to write config file: {}", e)))?;
         Ok(())
     }
 
+    /// Save configuration in the format named by the file extension: `.toml`, `.json`,
+    /// or `.yaml`/`.yml`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = match extension(path).as_deref() {
+            Some("toml") => toml::to_string_pretty(self).map_err(|e| e.to_string()),
+            Some("json") => serde_json::to_string_pretty(self).map_err(|e| e.to_string()),
+            Some("yaml" | "yml") => serde_yaml::to_string(self).map_err(|e| e.to_string()),
+            _ => return Err(unsupported_extension(path)),
+        }
+        .map_err(|e| LlmError::ConfigError(format!("Failed to serialize config: {}", e)))?;
+        std::fs::write(path, content)
+            .map_err(|e| LlmError::ConfigError(format!("Failed to write config file: {}", e)))
+    }
+
+    /// Load configuration in the format named by the file extension: `.toml`, `.json`,
+    /// or `.yaml`/`.yml`.
+    pub fn load(path: &Path) -> Result<Self> {
+        match extension(path).as_deref() {
+            Some("toml") => Self::from_toml(path),
+            Some("yaml" | "yml") => Self::from_yaml(path),
+            Some("json") => {
+                let content = std::fs::read_to_string(path).map_err(|e| {
+                    LlmError::ConfigError(format!("Failed to read config file: {}", e))
+                })?;
+                serde_json::from_str(&content).map_err(|e| {
+                    LlmError::ConfigError(format!("Failed to parse JSON config: {}", e))
+                })
+            }
+            _ => Err(unsupported_extension(path)),
+        }
+    }
+
+    /// Write the default configuration to `path` as a starting point for new users.
+    ///
+    /// Refuses to replace an existing file unless `force` is set.
+    pub fn write_default(path: &Path, force: bool) -> Result<()> {
+        if path.exists() && !force {
+            return Err(LlmError::ConfigError(format!(
+                "{} already exists (use --force to overwrite)",
+                path.display()
+            )));
+        }
+        Config::default().save(path)
+    }
+
     /// Validate the configuration.
     pub fn validate(&self) -> Result<()> {
         if self.model.embedding_dim == 0 {
@@ -208,6 +603,17 @@ impl Config {
         if self.model.max_seq_len == 0 {
             return Err(LlmError::ConfigError("max_seq_len must be > 0".to_string()));
         }
+        if self.model.num_heads != 1 {
+            return Err(LlmError::ConfigError(format!(
+                "num_heads must be 1: attention is single-head in this build (got {})",
+                self.model.num_heads
+            )));
+        }
+        if !(self.model.residual_scale >= 0.0 && self.model.residual_scale.is_finite()) {
+            return Err(LlmError::ConfigError(
+                "residual_scale must be finite and >= 0".to_string(),
+            ));
+        }
         if self.training.pretraining_lr <= 0.0 {
             return Err(LlmError::ConfigError(
                 "pretraining_lr must be > 0".to_string(),
@@ -218,13 +624,67 @@ impl Config {
                 "finetuning_lr must be > 0".to_string(),
             ));
         }
+        if !(0.0..=1.0).contains(&self.training.teacher_forcing_ratio) {
+            return Err(LlmError::ConfigError(
+                "teacher_forcing_ratio must be between 0 and 1".to_string(),
+            ));
+        }
+        if let Some(mix_ratio) = self.training.mix_ratio {
+            if !(0.0..=1.0).contains(&mix_ratio) {
+                return Err(LlmError::ConfigError(
+                    "mix_ratio must be between 0 and 1".to_string(),
+                ));
+            }
+        }
+        if self.output.progress_refresh_ms == 0 {
+            return Err(LlmError::ConfigError(
+                "progress_refresh_ms must be > 0".to_string(),
+            ));
+        }
+        self.output.progress_style()?;
         Ok(())
     }
 }
 
+/// Replace `field` with the parsed value of variable `name` from `lookup`, if set.
+fn env_override<T>(lookup: impl Fn(&str) -> Option<String>, name: &str, field: &mut T) -> Result<()>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    if let Some(val) = lookup(name) {
+        *field = parse_env(name, &val)?;
+    }
+    Ok(())
+}
+
+fn parse_env<T>(name: &str, val: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    val.trim()
+        .parse()
+        .map_err(|e| LlmError::ConfigError(format!("Invalid {} value {:?}: {}", name, val, e)))
+}
+
+/// Lowercased extension of `path`, if any.
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+}
+
+fn unsupported_extension(path: &Path) -> LlmError {
+    LlmError::ConfigError(format!(
+        "Unsupported config format for {} (expected .toml, .json, .yaml or .yml)",
+        path.display()
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
 
     #[test]
     fn test_default_config() {
@@ -241,4 +701,102 @@ mod tests {
         config.model.embedding_dim = 0;
         assert!(config.validate().is_err());
     }
+
+    #[test]
+    fn test_env_overrides_nested_fields() {
+        let vars = [
+            ("LLM_MODEL_NUM_BLOCKS", "5"),
+            ("LLM_MODEL_FFN_TYPE", "geglu"),
+            ("LLM_MODEL_SEED", "42"),
+            ("LLM_TRAINING_BATCH_SIZE", "8"),
+            ("LLM_TRAINING_FINETUNING_LR", "0.002"),
+            ("LLM_TRAINING_CLIP_PER_LAYER", "true"),
+            ("LLM_DATA_FORMAT", "csv"),
+            ("LLM_OUTPUT_LOG_LEVEL", "debug"),
+            ("LLM_HIDDEN_DIM", "64"),
+        ];
+        let mut env: HashMap<&str, &str> = vars.into_iter().collect();
+        let from_env = |env: &HashMap<&str, &str>| {
+            let mut config = Config::default();
+            config
+                .apply_env_with(|name| env.get(name).map(|val| val.to_string()))
+                .map(|_| config)
+        };
+
+        let config = from_env(&env).unwrap();
+        assert_eq!(config.model.num_blocks, 5);
+        assert_eq!(config.model.ffn_type, FfnType::GeGlu);
+        assert_eq!(config.model.seed, Some(42));
+        assert_eq!(config.model.hidden_dim, 64);
+        assert_eq!(config.training.batch_size, 8);
+        assert_eq!(config.training.finetuning_lr, 0.002);
+        assert!(config.training.clip_per_layer);
+        assert_eq!(config.data.format, "csv");
+        assert_eq!(config.output.log_level, "debug");
+        assert_eq!(
+            config.model.embedding_dim,
+            Config::default().model.embedding_dim
+        );
+
+        env.insert("LLM_TRAINING_BATCH_SIZE", "eight");
+        match from_env(&env) {
+            Err(LlmError::ConfigError(message)) => {
+                assert!(message.contains("LLM_TRAINING_BATCH_SIZE"), "{}", message);
+                assert!(message.contains("\"eight\""), "{}", message);
+            }
+            other => panic!("expected ConfigError, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_invalid_progress_template_fails_validation() {
+        let mut config = Config::default();
+        config.output.progress_template = "{msg} {bar:40x}".to_string();
+        assert!(matches!(config.validate(), Err(LlmError::ConfigError(_))));
+
+        config.output.progress_template = "{msg} {wide_bar} {pos}/{len}".to_string();
+        assert!(config.validate().is_ok());
+        assert!(config.output.progress_bar(10).is_ok());
+
+        config.output.progress_refresh_ms = 0;
+        assert!(matches!(config.validate(), Err(LlmError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_write_default_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["config.toml", "config.json", "config.yaml"] {
+            let path = dir.path().join(name);
+            Config::write_default(&path, false).unwrap();
+            assert_eq!(Config::load(&path).unwrap(), Config::default(), "{}", name);
+        }
+
+        assert!(Config::write_default(&dir.path().join("config.ini"), false).is_err());
+    }
+
+    #[test]
+    fn test_write_default_refuses_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "keep me").unwrap();
+
+        assert!(Config::write_default(&path, false).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+
+        Config::write_default(&path, true).unwrap();
+        assert_eq!(Config::from_toml(&path).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_seed_override_precedence() {
+        let mut model = ModelConfig {
+            seed: Some(7),
+            ..Default::default()
+        };
+        assert_eq!(model.resolve_seed(Some(42)), 42);
+        assert_eq!(model.resolve_seed(None), 7);
+
+        model.seed = None;
+        assert_eq!(model.resolve_seed(Some(42)), 42);
+    }
 }
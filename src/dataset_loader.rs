@@ -4,9 +4,18 @@
 //! error handling and data validation.
 
 use crate::error::{LlmError, Result};
+use crate::normalizer::TextNormalizer;
+use crate::vocab::Vocab;
+use crate::MAX_SEQ_LEN;
+use bincode::{Decode, Encode};
 use csv::ReaderBuilder;
+use flate2::read::GzDecoder;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
 /// Dataset container for pre-training and instruction-tuning data.
 #[derive(Debug, Clone)]
@@ -17,6 +26,194 @@ pub struct Dataset {
     pub chat_training_data: Vec<String>,
 }
 
+/// Token volume of a dataset under a given vocabulary.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenStats {
+    /// Number of word pieces across all samples, including out-of-vocabulary ones
+    pub total_tokens: usize,
+    /// Number of distinct word pieces
+    pub unique_tokens: usize,
+    /// Mean number of word pieces per sample
+    pub mean_seq_len: f32,
+    /// Median number of word pieces per sample
+    pub median_seq_len: f32,
+    /// Sequence-length quantiles: minimum, 25th, 50th and 75th percentile, and maximum
+    /// (nearest-rank)
+    pub seq_len_quantiles: [usize; 5],
+    /// Fraction of word pieces missing from the vocabulary
+    pub oov_rate: f32,
+}
+
+/// A data augmentation operation applied by [`Dataset::augment`].
+///
+/// Each operation works on whitespace-separated words with its own seeded RNG, so the
+/// same operations always produce the same variants. Structural words - `</s>` and role
+/// markers such as `User:` - are never altered.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AugmentOp {
+    /// Drop each word with probability `prob`, keeping at least one
+    TokenDropout { prob: f32, seed: u64 },
+    /// With probability `prob`, swap each word with a later word at most `window`
+    /// positions away
+    WindowSwap { window: usize, prob: f32, seed: u64 },
+    /// Replace each word that has synonyms with a random one, with probability `prob`
+    Synonyms {
+        map: HashMap<String, Vec<String>>,
+        prob: f32,
+        seed: u64,
+    },
+}
+
+impl AugmentOp {
+    /// Produce a variant of `text`.
+    fn apply(&self, text: &str, rng: &mut StdRng) -> String {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let mutable: Vec<usize> = (0..words.len())
+            .filter(|&i| !is_structural(words[i]))
+            .collect();
+
+        let variant: Vec<&str> = match self {
+            AugmentOp::TokenDropout { prob, .. } => {
+                let mut dropped: HashSet<usize> = mutable
+                    .iter()
+                    .copied()
+                    .filter(|_| rng.random_bool(f64::from(prob.clamp(0.0, 1.0))))
+                    .collect();
+                if dropped.len() == mutable.len() {
+                    // Keep at least one content word
+                    if let Some(first) = mutable.first() {
+                        dropped.remove(first);
+                    }
+                }
+                (0..words.len())
+                    .filter(|i| !dropped.contains(i))
+                    .map(|i| words[i])
+                    .collect()
+            }
+            AugmentOp::WindowSwap { window, prob, .. } => {
+                let mut swapped = words.clone();
+                for (k, &i) in mutable.iter().enumerate() {
+                    let reachable: Vec<usize> = mutable[k + 1..]
+                        .iter()
+                        .copied()
+                        .take_while(|&j| j - i <= *window)
+                        .collect();
+                    if !reachable.is_empty() && rng.random_bool(f64::from(prob.clamp(0.0, 1.0))) {
+                        let j = reachable[rng.random_range(0..reachable.len())];
+                        swapped.swap(i, j);
+                    }
+                }
+                swapped
+            }
+            AugmentOp::Synonyms { map, prob, .. } => words
+                .iter()
+                .map(|&word| match map.get(word) {
+                    Some(synonyms)
+                        if !synonyms.is_empty()
+                            && !is_structural(word)
+                            && rng.random_bool(f64::from(prob.clamp(0.0, 1.0))) =>
+                    {
+                        synonyms[rng.random_range(0..synonyms.len())].as_str()
+                    }
+                    _ => word,
+                })
+                .collect(),
+        };
+
+        variant.join(" ")
+    }
+
+    fn seed(&self) -> u64 {
+        match self {
+            AugmentOp::TokenDropout { seed, .. }
+            | AugmentOp::WindowSwap { seed, .. }
+            | AugmentOp::Synonyms { seed, .. } => *seed,
+        }
+    }
+}
+
+/// Greedily concatenate `samples` into sequences of at most `max_seq_len` tokens.
+fn pack_samples(samples: &[String], max_seq_len: usize) -> Vec<String> {
+    let mut packed = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+
+    for sample in samples {
+        let mut sample = sample.trim().to_string();
+        if !sample.ends_with("</s>") {
+            sample.push_str(" </s>");
+        }
+        let len = Vocab::split_text(&sample).len();
+
+        if current_len > 0 && current_len + len > max_seq_len {
+            packed.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        if current_len > 0 {
+            current.push(' ');
+        }
+        current.push_str(&sample);
+        current_len += len;
+    }
+    if current_len > 0 {
+        packed.push(current);
+    }
+    packed
+}
+
+/// Split `sample` at sentence boundaries - a word ending in `.`, `!` or `?` followed by
+/// a word starting with a capital letter - into chunks of at most `max_seq_len` tokens.
+///
+/// Sentences longer than the limit are split between words; a trailing `</s>` is kept
+/// on every chunk and counts toward the limit.
+fn split_sentences(sample: &str, max_seq_len: usize) -> Vec<String> {
+    let mut words: Vec<&str> = sample.split_whitespace().collect();
+    let terminated = words.last() == Some(&"</s>");
+    if terminated {
+        words.pop();
+    }
+    let reserved = usize::from(terminated);
+
+    let mut chunks = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut current_len = 0;
+    let mut flush = |current: &mut Vec<&str>, current_len: &mut usize| {
+        if !current.is_empty() {
+            if terminated {
+                current.push("</s>");
+            }
+            chunks.push(current.join(" "));
+            current.clear();
+            *current_len = 0;
+        }
+    };
+
+    for (i, &word) in words.iter().enumerate() {
+        let len = Vocab::split_text(word).len();
+        if current_len > 0 && current_len + len + reserved > max_seq_len {
+            flush(&mut current, &mut current_len);
+        }
+        current.push(word);
+        current_len += len;
+
+        let ends_sentence = word.ends_with(['.', '!', '?'])
+            && words
+                .get(i + 1)
+                .and_then(|next| next.chars().next())
+                .is_some_and(char::is_uppercase);
+        if ends_sentence {
+            flush(&mut current, &mut current_len);
+        }
+    }
+    flush(&mut current, &mut current_len);
+    chunks
+}
+
+/// Words that mark sequence structure rather than content.
+fn is_structural(word: &str) -> bool {
+    word == "</s>" || (word.len() > 1 && word.ends_with(':'))
+}
+
 /// Supported data formats.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(clippy::upper_case_acronyms)]
@@ -25,11 +222,15 @@ pub enum DatasetType {
     JSON,
     /// CSV format
     CSV,
+    /// CSV format whose first row is a header, skipped when loading
+    CSVWithHeaders,
 }
 
 impl Dataset {
     /// Create a new dataset by loading from files.
     ///
+    /// Files ending in `.gz` are decompressed transparently before parsing.
+    ///
     /// # Arguments
     /// * `pretraining_data_path` - Path to pre-training data file
     /// * `chat_training_data_path` - Path to chat training data file
@@ -42,13 +243,35 @@ impl Dataset {
         chat_training_data_path: impl AsRef<Path>,
         type_of_data: DatasetType,
     ) -> Result<Self> {
-        let pretraining_data: Vec<String>;
-        let chat_training_data: Vec<String>;
+        Self::new_with_normalizer(
+            pretraining_data_path,
+            chat_training_data_path,
+            type_of_data,
+            None,
+        )
+    }
+
+    /// Create a new dataset, normalizing every sample with `normalizer` if given.
+    ///
+    /// Use the same normalizer for [`crate::LLM::normalizer`] so inference-time input is
+    /// tokenized consistently with the training data.
+    ///
+    /// # Errors
+    /// Returns an error if files cannot be read or parsed.
+    pub fn new_with_normalizer(
+        pretraining_data_path: impl AsRef<Path>,
+        chat_training_data_path: impl AsRef<Path>,
+        type_of_data: DatasetType,
+        normalizer: Option<&TextNormalizer>,
+    ) -> Result<Self> {
+        let mut pretraining_data: Vec<String>;
+        let mut chat_training_data: Vec<String>;
 
         match type_of_data {
-            DatasetType::CSV => {
-                pretraining_data = get_data_from_csv(pretraining_data_path)?;
-                chat_training_data = get_data_from_csv(chat_training_data_path)?;
+            DatasetType::CSV | DatasetType::CSVWithHeaders => {
+                let has_headers = type_of_data == DatasetType::CSVWithHeaders;
+                pretraining_data = get_data_from_csv(pretraining_data_path, has_headers)?;
+                chat_training_data = get_data_from_csv(chat_training_data_path, has_headers)?;
             }
             DatasetType::JSON => {
                 pretraining_data = get_data_from_json(pretraining_data_path)?;
@@ -56,6 +279,15 @@ impl Dataset {
             }
         }
 
+        if let Some(normalizer) = normalizer {
+            for sample in pretraining_data
+                .iter_mut()
+                .chain(chat_training_data.iter_mut())
+            {
+                *sample = normalizer.normalize(sample);
+            }
+        }
+
         // Validate data is not empty
         if pretraining_data.is_empty() && chat_training_data.is_empty() {
             return Err(LlmError::DataLoadError(
@@ -75,11 +307,380 @@ impl Dataset {
         })
     }
 
+    /// Load and concatenate the samples of several files, in the order given.
+    ///
+    /// # Errors
+    /// Returns an error naming the first file that cannot be read or parsed.
+    pub fn from_many(paths: &[PathBuf], type_of_data: DatasetType) -> Result<Vec<String>> {
+        let mut data = Vec::new();
+        for path in paths {
+            let samples = match type_of_data {
+                DatasetType::CSV => get_data_from_csv(path, false),
+                DatasetType::CSVWithHeaders => get_data_from_csv(path, true),
+                DatasetType::JSON => get_data_from_json(path),
+            }
+            .map_err(|e| {
+                let reason = match e {
+                    LlmError::DataLoadError(msg) => msg,
+                    other => other.to_string(),
+                };
+                LlmError::DataLoadError(format!("{}: {}", path.display(), reason))
+            })?;
+            data.extend(samples);
+        }
+
+        tracing::debug!("Loaded {} samples from {} files", data.len(), paths.len());
+        Ok(data)
+    }
+
+    /// Append augmented variants of every sample.
+    ///
+    /// Each operation is applied separately to the original samples, and only variants
+    /// that differ from their source are appended, to the same split as the source.
+    /// Returns the number of samples added.
+    pub fn augment(&mut self, ops: &[AugmentOp]) -> usize {
+        let mut added = 0;
+        for data in [&mut self.pretraining_data, &mut self.chat_training_data] {
+            let originals = data.len();
+            for op in ops {
+                let mut rng = StdRng::seed_from_u64(op.seed());
+                for i in 0..originals {
+                    let variant = op.apply(&data[i], &mut rng);
+                    if variant != data[i] {
+                        data.push(variant);
+                        added += 1;
+                    }
+                }
+            }
+        }
+
+        tracing::info!("Augmentation added {} samples", added);
+        added
+    }
+
+    /// Pack consecutive samples of each split into sequences of at most `max_seq_len`
+    /// tokens, each sample terminated by `</s>`.
+    ///
+    /// Samples keep their order; one longer than `max_seq_len` stays a sequence of its
+    /// own. Train packed data with [`crate::LLM::document_masking`] enabled so attention
+    /// does not cross sample boundaries. Returns the number of sequences removed.
+    pub fn pack(&mut self, max_seq_len: usize) -> usize {
+        let before = self.total_samples();
+        for data in [&mut self.pretraining_data, &mut self.chat_training_data] {
+            *data = pack_samples(data, max_seq_len);
+        }
+
+        let removed = before - self.total_samples();
+        tracing::info!(
+            "Packed {} samples into {} sequences",
+            before,
+            self.total_samples()
+        );
+        removed
+    }
+
+    /// Split every pre-training sample into one sample per sentence, so no training
+    /// sequence starts or ends mid-sentence, keeping each under `max_seq_len` tokens.
+    ///
+    /// A sentence ends at `.`, `!` or `?` when the next word starts with a capital
+    /// letter; sentences longer than `max_seq_len` are split between words. Returns the
+    /// number of samples added.
+    pub fn split_sentences(&mut self, max_seq_len: usize) -> usize {
+        let before = self.pretraining_data.len();
+        self.pretraining_data = self
+            .pretraining_data
+            .iter()
+            .flat_map(|sample| split_sentences(sample, max_seq_len))
+            .collect();
+        self.pretraining_data.len().saturating_sub(before)
+    }
+
+    /// Keep only the first `max_samples` samples of each split, for quick smoke tests.
+    ///
+    /// Order is preserved; zero keeps everything.
+    pub fn truncate(&mut self, max_samples: usize) {
+        if max_samples == 0 {
+            return;
+        }
+        self.pretraining_data.truncate(max_samples);
+        self.chat_training_data.truncate(max_samples);
+    }
+
+    /// One epoch of samples drawn from both splits, for training in a single blended
+    /// phase instead of pre-training followed by instruction tuning.
+    ///
+    /// Each of the [`Dataset::total_samples`] positions comes from the pre-training split
+    /// with probability `mix_ratio` (clamped to `[0, 1]`) and from the chat split
+    /// otherwise. Each split is visited in a fresh shuffled order, starting over once
+    /// exhausted; if one split is empty every sample comes from the other.
+    pub fn mixed_epoch(&self, mix_ratio: f32, rng: &mut impl Rng) -> Vec<&str> {
+        let mut pretraining: Vec<&str> = self.pretraining_data.iter().map(|s| s.as_str()).collect();
+        let mut chat: Vec<&str> = self.chat_training_data.iter().map(|s| s.as_str()).collect();
+        pretraining.shuffle(rng);
+        chat.shuffle(rng);
+
+        let mix_ratio = f64::from(mix_ratio.clamp(0.0, 1.0));
+        let (mut next_pretraining, mut next_chat) = (0, 0);
+        (0..self.total_samples())
+            .map(|_| {
+                let from_pretraining =
+                    chat.is_empty() || (!pretraining.is_empty() && rng.random_bool(mix_ratio));
+                let (source, next) = if from_pretraining {
+                    (&pretraining, &mut next_pretraining)
+                } else {
+                    (&chat, &mut next_chat)
+                };
+                let sample = source[*next % source.len()];
+                *next += 1;
+                sample
+            })
+            .collect()
+    }
+
+    /// Split into `k` `(train, validation)` pairs for k-fold cross-validation.
+    ///
+    /// Samples are shuffled with `seed` within each split and dealt round-robin to the
+    /// folds (pre-training samples first, then chat samples), so each split stays in its
+    /// own field, the validation sets are disjoint and together cover every sample, and
+    /// their sizes differ by at most one. Each training set holds the remaining samples.
+    /// A `k` of 0 yields no folds.
+    pub fn k_folds(&self, k: usize, seed: u64) -> Vec<(Dataset, Dataset)> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut pretraining: Vec<&String> = self.pretraining_data.iter().collect();
+        let mut chat: Vec<&String> = self.chat_training_data.iter().collect();
+        pretraining.shuffle(&mut rng);
+        chat.shuffle(&mut rng);
+
+        let empty = || Dataset {
+            pretraining_data: Vec::new(),
+            chat_training_data: Vec::new(),
+        };
+        let mut folds: Vec<(Dataset, Dataset)> = (0..k).map(|_| (empty(), empty())).collect();
+        let offset = pretraining.len();
+        for (i, sample) in pretraining.into_iter().enumerate() {
+            for (fold, (train, validation)) in folds.iter_mut().enumerate() {
+                let target = if i % k == fold { validation } else { train };
+                target.pretraining_data.push(sample.clone());
+            }
+        }
+        for (i, sample) in chat.into_iter().enumerate() {
+            for (fold, (train, validation)) in folds.iter_mut().enumerate() {
+                let target = if (offset + i) % k == fold {
+                    validation
+                } else {
+                    train
+                };
+                target.chat_training_data.push(sample.clone());
+            }
+        }
+        folds
+    }
+
+    /// Split into `(train, validation, test)` sets holding fractions `train` and `val`
+    /// of the samples and the remainder.
+    ///
+    /// Each split (pre-training and chat) is shuffled with `seed` and divided on its own,
+    /// with sizes rounded to the nearest sample.
+    ///
+    /// # Errors
+    /// Returns a validation error unless both fractions are in `[0, 1]` and sum to at
+    /// most 1.
+    pub fn three_way_split(
+        &self,
+        train: f32,
+        val: f32,
+        seed: u64,
+    ) -> Result<(Dataset, Dataset, Dataset)> {
+        let in_range = |ratio: f32| (0.0..=1.0).contains(&ratio);
+        if !in_range(train) || !in_range(val) || train + val > 1.0 {
+            return Err(LlmError::ValidationError(format!(
+                "Split ratios must be in [0, 1] and sum to at most 1, got train {} and val {}",
+                train, val
+            )));
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut split = |samples: &[String]| {
+            let mut samples = samples.to_vec();
+            samples.shuffle(&mut rng);
+            let num_train = ((samples.len() as f32 * train).round() as usize).min(samples.len());
+            let num_val =
+                ((samples.len() as f32 * val).round() as usize).min(samples.len() - num_train);
+            let test = samples.split_off(num_train + num_val);
+            let val = samples.split_off(num_train);
+            (samples, val, test)
+        };
+        let (pretraining_train, pretraining_val, pretraining_test) = split(&self.pretraining_data);
+        let (chat_train, chat_val, chat_test) = split(&self.chat_training_data);
+
+        Ok((
+            Dataset {
+                pretraining_data: pretraining_train,
+                chat_training_data: chat_train,
+            },
+            Dataset {
+                pretraining_data: pretraining_val,
+                chat_training_data: chat_val,
+            },
+            Dataset {
+                pretraining_data: pretraining_test,
+                chat_training_data: chat_test,
+            },
+        ))
+    }
+
     /// Get the total number of training samples.
     pub fn total_samples(&self) -> usize {
         self.pretraining_data.len() + self.chat_training_data.len()
     }
 
+    /// Lazily tokenize the samples of both splits (pre-training first) into batches of
+    /// `batch_size` token sequences, tokenizing each batch only when it is requested.
+    ///
+    /// Words missing from `vocab` are dropped, as in [`crate::LLM::tokenize`]. The last
+    /// batch may be smaller; a `batch_size` of 0 is treated as 1.
+    pub fn iter_batches<'a>(
+        &'a self,
+        vocab: &'a Vocab,
+        batch_size: usize,
+    ) -> impl Iterator<Item = Vec<Vec<usize>>> + 'a {
+        let batch_size = batch_size.max(1);
+        let mut samples = self
+            .pretraining_data
+            .iter()
+            .chain(self.chat_training_data.iter())
+            .map(move |sample| {
+                Vocab::split_text(sample)
+                    .iter()
+                    .filter_map(|piece| vocab.encode(piece))
+                    .collect::<Vec<usize>>()
+            });
+        std::iter::from_fn(move || {
+            let batch: Vec<Vec<usize>> = samples.by_ref().take(batch_size).collect();
+            (!batch.is_empty()).then_some(batch)
+        })
+    }
+
+    /// Count the tokens of every sample as [`crate::LLM::tokenize`] would split them.
+    pub fn token_statistics(&self, vocab: &Vocab) -> TokenStats {
+        let mut lengths = Vec::with_capacity(self.total_samples());
+        let mut unique = HashSet::new();
+        let mut oov_tokens = 0;
+
+        for sample in self
+            .pretraining_data
+            .iter()
+            .chain(self.chat_training_data.iter())
+        {
+            let pieces = Vocab::split_text(sample);
+            lengths.push(pieces.len());
+            oov_tokens += pieces.iter().filter(|p| !vocab.contains(p)).count();
+            unique.extend(pieces);
+        }
+
+        let total_tokens: usize = lengths.iter().sum();
+        lengths.sort_unstable();
+        let median_seq_len = match lengths.len() {
+            0 => 0.0,
+            n if n % 2 == 0 => (lengths[n / 2 - 1] + lengths[n / 2]) as f32 / 2.0,
+            n => lengths[n / 2] as f32,
+        };
+
+        let quantile = |q: f32| match lengths.len() {
+            0 => 0,
+            n => lengths[((q * n as f32).ceil() as usize).clamp(1, n) - 1],
+        };
+
+        TokenStats {
+            total_tokens,
+            unique_tokens: unique.len(),
+            mean_seq_len: total_tokens as f32 / lengths.len().max(1) as f32,
+            median_seq_len,
+            seq_len_quantiles: [
+                quantile(0.0),
+                quantile(0.25),
+                quantile(0.5),
+                quantile(0.75),
+                quantile(1.0),
+            ],
+            oov_rate: oov_tokens as f32 / total_tokens.max(1) as f32,
+        }
+    }
+
+    /// Human-readable corpus report: sample counts, vocabulary size, and the
+    /// [`Dataset::token_statistics`] under `vocab`.
+    pub fn stats_report(&self, vocab: &Vocab) -> String {
+        let stats = self.token_statistics(vocab);
+        let [min, p25, p50, p75, max] = stats.seq_len_quantiles;
+        format!(
+            "Samples: {} ({} pre-training, {} chat)\n\
+             Vocabulary size: {}\n\
+             Total tokens: {}\n\
+             Unique tokens: {}\n\
+             OOV rate: {:.2}%\n\
+             Sequence length: mean {:.1}, median {:.1}\n\
+             Sequence length quantiles: min {}, p25 {}, p50 {}, p75 {}, max {}",
+            self.total_samples(),
+            self.pretraining_data.len(),
+            self.chat_training_data.len(),
+            vocab.size(),
+            stats.total_tokens,
+            stats.unique_tokens,
+            stats.oov_rate * 100.0,
+            stats.mean_seq_len,
+            stats.median_seq_len,
+            min,
+            p25,
+            p50,
+            p75,
+            max
+        )
+    }
+
+    /// Per-sample problems, as `(index, reason)` in index order, without changing the
+    /// data. Indices count pre-training samples first, then chat samples.
+    ///
+    /// Flags samples that are empty, longer than [`MAX_SEQ_LEN`] tokens under `vocab`,
+    /// exact duplicates of an earlier sample, or that contain control characters other
+    /// than whitespace. A sample can be flagged for several reasons.
+    pub fn integrity_report(&self, vocab: &Vocab) -> Vec<(usize, String)> {
+        let mut issues = Vec::new();
+        let mut first_seen: HashMap<&str, usize> = HashMap::new();
+
+        let samples = self
+            .pretraining_data
+            .iter()
+            .chain(self.chat_training_data.iter());
+        for (index, sample) in samples.enumerate() {
+            if sample.trim().is_empty() {
+                issues.push((index, "empty".to_string()));
+            }
+
+            let tokens = Vocab::split_text(sample)
+                .iter()
+                .filter(|piece| vocab.encode(piece).is_some())
+                .count();
+            if tokens > MAX_SEQ_LEN {
+                issues.push((
+                    index,
+                    format!("too long: {} tokens (max {})", tokens, MAX_SEQ_LEN),
+                ));
+            }
+
+            match first_seen.get(sample.as_str()) {
+                Some(&first) => issues.push((index, format!("duplicate of sample {}", first))),
+                None => {
+                    first_seen.insert(sample, index);
+                }
+            }
+
+            if sample.chars().any(|c| c.is_control() && !c.is_whitespace()) {
+                issues.push((index, "contains control characters".to_string()));
+            }
+        }
+        issues
+    }
+
     /// Validate dataset integrity.
     pub fn validate(&self) -> Result<()> {
         if self.pretraining_data.is_empty() && self.chat_training_data.is_empty() {
@@ -104,10 +705,160 @@ impl Dataset {
     }
 }
 
+/// Position of a training run within its dataset, stored in checkpoints so an interrupted
+/// run resumes at the exact sample it stopped at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct DatasetCursor {
+    /// Current epoch (0-based)
+    pub epoch: usize,
+    /// Position of the next sample to train on in the epoch's [`crate::LLM::epoch_order`]
+    pub sample_index: usize,
+}
+
+impl DatasetCursor {
+    /// Create a cursor at the given position.
+    pub fn new(epoch: usize, sample_index: usize) -> Self {
+        Self {
+            epoch,
+            sample_index,
+        }
+    }
+
+    /// Move past the current sample, wrapping to the next epoch after `num_samples`.
+    pub fn advance(&mut self, num_samples: usize) {
+        self.sample_index += 1;
+        if self.sample_index >= num_samples {
+            self.epoch += 1;
+            self.sample_index = 0;
+        }
+    }
+}
+
+/// Supervised dataset of `(text, label)` pairs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LabeledDataset {
+    /// Text samples paired with their integer class label
+    pub samples: Vec<(String, usize)>,
+}
+
+impl LabeledDataset {
+    /// Load `(text, label)` pairs from a CSV file with a header row, selecting the text
+    /// and label columns by name.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read, a column is missing, or a label is
+    /// not a non-negative integer. Label errors report the offending row number.
+    pub fn from_csv(path: impl AsRef<Path>, text_column: &str, label_column: &str) -> Result<Self> {
+        let path = path.as_ref();
+        let file = open_data_file(path)
+            .map_err(|e| LlmError::DataLoadError(format!("Failed to open CSV file: {}", e)))?;
+
+        let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file);
+        let headers = rdr
+            .headers()
+            .map_err(|e| LlmError::DataLoadError(format!("Failed to read CSV header: {}", e)))?
+            .clone();
+
+        let column_index = |name: &str| {
+            headers.iter().position(|h| h == name).ok_or_else(|| {
+                LlmError::DataLoadError(format!("CSV file has no '{}' column", name))
+            })
+        };
+        let text_idx = column_index(text_column)?;
+        let label_idx = column_index(label_column)?;
+
+        let mut samples = Vec::new();
+        for result in rdr.records() {
+            let record = result.map_err(|e| {
+                LlmError::DataLoadError(format!("Failed to read CSV record: {}", e))
+            })?;
+            let row = record.position().map_or(0, |p| p.line());
+
+            let text = record.get(text_idx).ok_or_else(|| {
+                LlmError::DataLoadError(format!("Row {} is missing the text column", row))
+            })?;
+            let label = record.get(label_idx).unwrap_or_default().trim();
+            let label = label.parse::<usize>().map_err(|_| {
+                LlmError::DataLoadError(format!("Invalid label '{}' on row {}", label, row))
+            })?;
+
+            samples.push((text.to_string(), label));
+        }
+
+        tracing::debug!("Loaded {} labeled samples from CSV file", samples.len());
+        Ok(Self { samples })
+    }
+
+    /// Load `(text, label)` pairs from a JSON array of objects, selecting the text and
+    /// label fields by name.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be parsed or an entry is missing a field or
+    /// has a non-integer label. Entries are numbered from 1.
+    pub fn from_json(path: impl AsRef<Path>, text_field: &str, label_field: &str) -> Result<Self> {
+        let data_json = read_data_file(path.as_ref())
+            .map_err(|e| LlmError::DataLoadError(format!("Failed to read JSON file: {}", e)))?;
+        let entries: Vec<serde_json::Map<String, serde_json::Value>> =
+            serde_json::from_str(&data_json)
+                .map_err(|e| LlmError::DataLoadError(format!("Failed to parse JSON: {}", e)))?;
+
+        let mut samples = Vec::with_capacity(entries.len());
+        for (i, entry) in entries.iter().enumerate() {
+            let row = i + 1;
+            let text = entry
+                .get(text_field)
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| {
+                    LlmError::DataLoadError(format!("Entry {} has no text '{}'", row, text_field))
+                })?;
+            let label = entry
+                .get(label_field)
+                .and_then(|v| v.as_u64())
+                .ok_or_else(|| {
+                    LlmError::DataLoadError(format!("Invalid label on entry {}", row))
+                })?;
+            samples.push((text.to_string(), label as usize));
+        }
+
+        tracing::debug!("Loaded {} labeled samples from JSON file", samples.len());
+        Ok(Self { samples })
+    }
+
+    /// Number of labeled samples.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Whether the dataset has no samples.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+/// Open a data file, transparently decompressing it when its extension is `.gz`.
+fn open_data_file(path: &Path) -> std::io::Result<Box<dyn Read>> {
+    let file = fs::File::open(path)?;
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+    {
+        Ok(Box::new(GzDecoder::new(file)))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Read a (possibly gzip-compressed) data file to a string.
+fn read_data_file(path: &Path) -> std::io::Result<String> {
+    let mut content = String::new();
+    open_data_file(path)?.read_to_string(&mut content)?;
+    Ok(content)
+}
+
 /// Load data from a JSON file.
 fn get_data_from_json(path: impl AsRef<Path>) -> Result<Vec<String>> {
     let path = path.as_ref();
-    let data_json = fs::read_to_string(path)
+    let data_json = read_data_file(path)
         .map_err(|e| LlmError::DataLoadError(format!("Failed to read JSON file: {}", e)))?;
 
     let data: Vec<String> = serde_json::from_str(&data_json)
@@ -117,13 +868,15 @@ fn get_data_from_json(path: impl AsRef<Path>) -> Result<Vec<String>> {
     Ok(data)
 }
 
-/// Load data from a CSV file.
-fn get_data_from_csv(path: impl AsRef<Path>) -> Result<Vec<String>> {
+/// Load data from a CSV file, skipping the first row if `has_headers`.
+fn get_data_from_csv(path: impl AsRef<Path>, has_headers: bool) -> Result<Vec<String>> {
     let path = path.as_ref();
-    let file = fs::File::open(path)
+    let file = open_data_file(path)
         .map_err(|e| LlmError::DataLoadError(format!("Failed to open CSV file: {}", e)))?;
 
-    let mut rdr = ReaderBuilder::new().has_headers(false).from_reader(file);
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(has_headers)
+        .from_reader(file);
     let mut data = Vec::new();
 
     for result in rdr.records() {
@@ -163,4 +916,54 @@ mod tests {
         };
         assert!(empty_dataset.validate().is_err());
     }
+
+    #[test]
+    fn test_dataset_cursor_wraps_epochs() {
+        let mut cursor = DatasetCursor::new(0, 1);
+        cursor.advance(3);
+        assert_eq!(cursor, DatasetCursor::new(0, 2));
+        cursor.advance(3);
+        assert_eq!(cursor, DatasetCursor::new(1, 0));
+    }
+
+    #[test]
+    fn test_labeled_dataset_from_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("labeled.csv");
+        fs::write(&path, "text,label\ngood movie,1\nbad movie,0\n").unwrap();
+
+        let dataset = LabeledDataset::from_csv(&path, "text", "label").unwrap();
+        assert_eq!(
+            dataset.samples,
+            vec![("good movie".to_string(), 1), ("bad movie".to_string(), 0)]
+        );
+    }
+
+    #[test]
+    fn test_labeled_dataset_reports_bad_label_row() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("labeled.csv");
+        fs::write(&path, "text,label\ngood movie,1\nbad movie,negative\n").unwrap();
+
+        let err = LabeledDataset::from_csv(&path, "text", "label").unwrap_err();
+        assert!(err.to_string().contains("row 3"), "{}", err);
+        assert!(err.to_string().contains("negative"));
+
+        assert!(LabeledDataset::from_csv(&path, "text", "class").is_err());
+    }
+
+    #[test]
+    fn test_labeled_dataset_from_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("labeled.json");
+        fs::write(
+            &path,
+            r#"[{"text": "hi", "label": 2}, {"text": "yo", "label": 0}]"#,
+        )
+        .unwrap();
+
+        let dataset = LabeledDataset::from_json(&path, "text", "label").unwrap();
+        assert_eq!(dataset.len(), 2);
+        assert_eq!(dataset.samples[0], ("hi".to_string(), 2));
+    }
 }
@@ -1,8 +1,27 @@
-use ndarray::{s, Array2};
+use std::collections::HashMap;
+
+use ndarray::{s, Array1, Array2, ArrayView1, Axis};
 use rand_distr::{Distribution, Normal};
 
-use crate::{adam::Adam, llm::Layer, vocab::Vocab, EMBEDDING_DIM, MAX_SEQ_LEN};
+use crate::{
+    adam::Adam,
+    error::{LlmError, Result},
+    llm::{clip_scale, Layer},
+    vocab::Vocab,
+    EMBEDDING_DIM, MAX_SEQ_LEN,
+};
 
+/// Cosine similarity of two vectors; 0 when either is all zeros.
+pub(crate) fn cosine_similarity(a: ArrayView1<f32>, b: ArrayView1<f32>) -> f32 {
+    let norms = a.dot(&a).sqrt() * b.dot(&b).sqrt();
+    if norms > 0.0 {
+        a.dot(&b) / norms
+    } else {
+        0.0
+    }
+}
+
+#[derive(Clone)]
 pub struct Embeddings {
     pub token_embeddings: Array2<f32>,
     pub positional_embeddings: Array2<f32>,
@@ -34,14 +53,97 @@ impl Embeddings {
         }
     }
 
+    /// Warm-start token embeddings from pretrained word vectors (e.g. GloVe).
+    ///
+    /// Rows for tokens in `vocab` that have a vector are overwritten; all other rows keep
+    /// their random initialization. Returns the number of tokens matched.
+    ///
+    /// # Errors
+    /// Returns `ShapeMismatch` if any matching vector's length differs from the embedding
+    /// dimension. No rows are modified in that case.
+    pub fn load_pretrained(
+        &mut self,
+        vectors: &HashMap<String, Vec<f32>>,
+        vocab: &Vocab,
+    ) -> Result<usize> {
+        let embedding_dim = self.token_embeddings.ncols();
+        let matches: Vec<(usize, &Vec<f32>)> = vocab
+            .words
+            .iter()
+            .enumerate()
+            .filter(|(id, _)| *id < self.token_embeddings.nrows())
+            .filter_map(|(id, word)| vectors.get(word).map(|vector| (id, vector)))
+            .collect();
+
+        if let Some((_, vector)) = matches.iter().find(|(_, v)| v.len() != embedding_dim) {
+            return Err(LlmError::shape_mismatch(embedding_dim, vector.len()));
+        }
+
+        for (id, vector) in &matches {
+            self.token_embeddings
+                .row_mut(*id)
+                .assign(&ndarray::ArrayView1::from(vector.as_slice()));
+        }
+
+        tracing::debug!(
+            "Loaded pretrained vectors for {} of {} tokens",
+            matches.len(),
+            vocab.size()
+        );
+        Ok(matches.len())
+    }
+
+    /// Solve the analogy "`a` is to `b` as `c` is to ?" by finding the `k` tokens whose
+    /// embeddings are most cosine-similar to `vec(b) - vec(a) + vec(c)`.
+    ///
+    /// The three input words are excluded. Returns `(word, similarity)` pairs, most
+    /// similar first, or nothing if any input word is not in `vocab`.
+    pub fn analogy(
+        &self,
+        a: &str,
+        b: &str,
+        c: &str,
+        vocab: &Vocab,
+        k: usize,
+    ) -> Vec<(String, f32)> {
+        let ids = [a, b, c].map(|word| {
+            vocab
+                .encode(word)
+                .filter(|&id| id < self.token_embeddings.nrows())
+        });
+        let [Some(a), Some(b), Some(c)] = ids else {
+            return Vec::new();
+        };
+
+        let target: Array1<f32> = &self.token_embeddings.row(b) - &self.token_embeddings.row(a)
+            + self.token_embeddings.row(c);
+        let mut ranked: Vec<(usize, f32)> = self
+            .token_embeddings
+            .rows()
+            .into_iter()
+            .enumerate()
+            .filter(|(id, _)| ![a, b, c].contains(id))
+            .map(|(id, row)| (id, cosine_similarity(row, target.view())))
+            .collect();
+        ranked.sort_by(|x, y| y.1.total_cmp(&x.1).then(x.0.cmp(&y.0)));
+
+        ranked
+            .into_iter()
+            .take(k)
+            .filter_map(|(id, similarity)| {
+                vocab.decode.get(&id).map(|word| (word.clone(), similarity))
+            })
+            .collect()
+    }
+
     fn init_embeddings(vocab_size: usize, embedding_dim: usize) -> Array2<f32> {
-        let mut rng = rand::rng();
+        let mut rng = crate::rng::init_rng();
         let normal = Normal::new(0.0, 0.02).unwrap(); // Increased for better learning
         Array2::from_shape_fn((vocab_size, embedding_dim), |_| normal.sample(&mut rng))
     }
 
     fn init_positional_embeddings(max_seq_len: usize, embedding_dim: usize) -> Array2<f32> {
-        let mut rng = rand::rng();
+        let mut rng = crate::rng::init_rng();
         let normal = Normal::new(0.0, 0.02).unwrap(); // Increased for better learning
         Array2::from_shape_fn((max_seq_len, embedding_dim), |_| normal.sample(&mut rng))
     }
@@ -96,6 +198,15 @@ impl Layer for Embeddings {
     }
 
     fn backward(&mut self, grads: &Array2<f32>, lr: f32) -> Array2<f32> {
+        self.backward_clipped(grads, lr, None)
+    }
+
+    fn backward_clipped(
+        &mut self,
+        grads: &Array2<f32>,
+        lr: f32,
+        max_norm: Option<f32>,
+    ) -> Array2<f32> {
         let input = self.cached_input.as_ref().unwrap();
         let token_ids: Vec<usize> = input.iter().map(|&x| x as usize).collect();
         let grads = grads.view(); // (sequence_length, embedding_dim)
@@ -127,6 +238,11 @@ impl Layer for Embeddings {
             }
         }
 
+        if let Some(scale) = clip_scale(token_grads.iter().chain(&positional_grads), max_norm) {
+            token_grads *= scale;
+            positional_grads *= scale;
+        }
+
         self.token_optimizer
             .step(&mut self.token_embeddings, &token_grads, lr);
         self.positional_optimizer
@@ -139,4 +255,56 @@ impl Layer for Embeddings {
     fn parameters(&self) -> usize {
         self.token_embeddings.len() + self.positional_embeddings.len()
     }
+
+    fn weights(&self) -> Vec<&Array2<f32>> {
+        vec![&self.token_embeddings, &self.positional_embeddings]
+    }
+
+    fn weights_mut(&mut self) -> Vec<&mut Array2<f32>> {
+        vec![&mut self.token_embeddings, &mut self.positional_embeddings]
+    }
+
+    fn reinitialize(&mut self) {
+        let (vocab_size, embedding_dim) = self.token_embeddings.dim();
+        let max_seq_len = self.positional_embeddings.nrows();
+        self.token_embeddings = Self::init_embeddings(vocab_size, embedding_dim);
+        self.positional_embeddings = Self::init_positional_embeddings(max_seq_len, embedding_dim);
+        self.cached_input = None;
+        self.token_optimizer = Adam::new((vocab_size, embedding_dim));
+        self.positional_optimizer = Adam::new((max_seq_len, embedding_dim));
+    }
+
+    fn resize_vocab(&mut self, vocab_size: usize) {
+        let current = self.token_embeddings.nrows();
+        if vocab_size <= current {
+            return;
+        }
+
+        let embedding_dim = self.token_embeddings.ncols();
+        let new_rows = Self::init_embeddings(vocab_size - current, embedding_dim);
+        self.token_embeddings = ndarray::concatenate![Axis(0), self.token_embeddings, new_rows];
+
+        // Keep the optimizer moments aligned with the grown matrix
+        let zeros = Array2::<f32>::zeros((vocab_size - current, embedding_dim));
+        self.token_optimizer.m = ndarray::concatenate![Axis(0), self.token_optimizer.m, zeros];
+        self.token_optimizer.v = ndarray::concatenate![Axis(0), self.token_optimizer.v, zeros];
+    }
+
+    fn remap_vocab(&mut self, old_ids: &[Option<usize>]) {
+        let embedding_dim = self.token_embeddings.ncols();
+        let mut remapped = Self::init_embeddings(old_ids.len(), embedding_dim);
+        for (id, old_id) in old_ids.iter().enumerate() {
+            if let Some(old_id) = old_id.filter(|&old| old < self.token_embeddings.nrows()) {
+                remapped
+                    .row_mut(id)
+                    .assign(&self.token_embeddings.row(old_id));
+            }
+        }
+        self.token_embeddings = remapped;
+        self.token_optimizer = Adam::new((old_ids.len(), embedding_dim));
+    }
+
+    fn output_dim(&self) -> Option<usize> {
+        Some(self.token_embeddings.ncols())
+    }
 }
@@ -1,9 +1,57 @@
-use ndarray::{Array2, Axis};
+use ndarray::{concatenate, s, Array2, Axis};
 use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
 
-use crate::{adam::Adam, llm::Layer};
+use crate::{
+    adam::Adam,
+    llm::{clip_scale, Layer},
+};
 
+/// Feed-forward network variant.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FfnType {
+    /// `W2 · ReLU(W1·x + b1) + b2`
+    #[default]
+    Relu,
+    /// GeGLU: the hidden projection is split into value and gate halves and
+    /// `W2 · (value ⊙ GELU(gate)) + b2` is returned. Uses twice the `W1` columns.
+    GeGlu,
+}
+
+impl std::str::FromStr for FfnType {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "relu" => Ok(FfnType::Relu),
+            "geglu" => Ok(FfnType::GeGlu),
+            other => Err(format!(
+                "unknown feed-forward type {:?} (expected \"relu\" or \"geglu\")",
+                other
+            )),
+        }
+    }
+}
+
+/// sqrt(2 / pi), used by the tanh approximation of GELU
+const GELU_COEFF: f32 = 0.797_884_6;
+
+fn gelu(x: f32) -> f32 {
+    0.5 * x * (1.0 + (GELU_COEFF * (x + 0.044715 * x.powi(3))).tanh())
+}
+
+fn gelu_derivative(x: f32) -> f32 {
+    let t = (GELU_COEFF * (x + 0.044715 * x.powi(3))).tanh();
+    0.5 * (1.0 + t) + 0.5 * x * (1.0 - t * t) * GELU_COEFF * (1.0 + 3.0 * 0.044715 * x * x)
+}
+
+#[derive(Clone)]
 pub struct FeedForward {
+    /// Factor applied to the feed-forward output before the residual is added
+    pub residual_scale: f32,
+    ffn_type: FfnType,
+    hidden_dim: usize,
     w1: Array2<f32>,
     b1: Array2<f32>,
     w2: Array2<f32>,
@@ -23,7 +71,17 @@ pub struct FeedForward {
 impl FeedForward {
     /// Initialize a feedforward layer with random weights
     pub fn new(embedding_dim: usize, hidden_dim: usize) -> Self {
-        let mut rng = rand::rng();
+        Self::with_type(embedding_dim, hidden_dim, FfnType::Relu)
+    }
+
+    /// Initialize a feedforward layer of the given variant with random weights
+    pub fn with_type(embedding_dim: usize, hidden_dim: usize, ffn_type: FfnType) -> Self {
+        let mut rng = crate::rng::init_rng();
+        // GeGLU projects to the value and gate halves at once
+        let projection_dim = match ffn_type {
+            FfnType::Relu => hidden_dim,
+            FfnType::GeGlu => 2 * hidden_dim,
+        };
 
         // Xavier/He initialization for w1: std = sqrt(2 / fan_in)
         let std_w1 = (2.0 / embedding_dim as f32).sqrt();
@@ -34,46 +92,92 @@ impl FeedForward {
         let normal_w2 = Normal::new(0.0, std_w2).unwrap();
 
         FeedForward {
-            w1: Array2::from_shape_fn((embedding_dim, hidden_dim), |_| normal_w1.sample(&mut rng)),
-            b1: Array2::zeros((1, hidden_dim)), // Bias initialized to 0
+            residual_scale: 1.0,
+            ffn_type,
+            hidden_dim,
+            w1: Array2::from_shape_fn((embedding_dim, projection_dim), |_| {
+                normal_w1.sample(&mut rng)
+            }),
+            b1: Array2::zeros((1, projection_dim)), // Bias initialized to 0
             w2: Array2::from_shape_fn((hidden_dim, embedding_dim), |_| normal_w2.sample(&mut rng)),
             b2: Array2::zeros((1, embedding_dim)), // Bias initialized to 0
             input: None,
             hidden_pre_activation: None,
             hidden_post_activation: None,
-            optimizer_w1: Adam::new((embedding_dim, hidden_dim)),
-            optimizer_b1: Adam::new((1, hidden_dim)),
+            optimizer_w1: Adam::new((embedding_dim, projection_dim)),
+            optimizer_b1: Adam::new((1, projection_dim)),
             optimizer_w2: Adam::new((hidden_dim, embedding_dim)),
             optimizer_b2: Adam::new((1, embedding_dim)),
         }
     }
+
+    /// Hidden layer before and after the nonlinearity.
+    fn hidden(&self, input: &Array2<f32>) -> (Array2<f32>, Array2<f32>) {
+        let hidden_pre_activation = input.dot(&self.w1) + &self.b1;
+        let hidden_post_activation = match self.ffn_type {
+            FfnType::Relu => hidden_pre_activation.mapv(|x| x.max(0.0)),
+            FfnType::GeGlu => {
+                let value = hidden_pre_activation.slice(s![.., ..self.hidden_dim]);
+                let gate = hidden_pre_activation.slice(s![.., self.hidden_dim..]);
+                &value * &gate.mapv(gelu)
+            }
+        };
+        (hidden_pre_activation, hidden_post_activation)
+    }
 }
 
 impl Layer for FeedForward {
     fn layer_type(&self) -> &str {
-        "FeedForward"
+        match self.ffn_type {
+            FfnType::Relu => "FeedForward",
+            FfnType::GeGlu => "FeedForward(GeGLU)",
+        }
     }
 
     fn backward(&mut self, grads: &Array2<f32>, lr: f32) -> Array2<f32> {
+        self.backward_clipped(grads, lr, None)
+    }
+
+    fn backward_clipped(
+        &mut self,
+        grads: &Array2<f32>,
+        lr: f32,
+        max_norm: Option<f32>,
+    ) -> Array2<f32> {
+        let residual_grads = grads;
+        let grads = &(grads * self.residual_scale);
+
         // Unwrap cached values
         let input = self.input.as_ref().expect("forward must be run first");
         let hidden_pre_activation = self.hidden_pre_activation.as_ref().unwrap();
         let hidden_post_activation = self.hidden_post_activation.as_ref().unwrap();
 
         // Compute gradients for W2 and b2
-        let grad_w2 = hidden_post_activation.t().dot(grads);
-        let grad_b2 = grads.sum_axis(Axis(0)).insert_axis(Axis(0)); // Shape: [1, embedding_dim]
+        let mut grad_w2 = hidden_post_activation.t().dot(grads);
+        let mut grad_b2 = grads.sum_axis(Axis(0)).insert_axis(Axis(0)); // Shape: [1, embedding_dim]
 
         // Gradient w.r.t. hidden_post_activation
         let grad_hidden_post_activation = grads.dot(&self.w2.t());
 
-        // Gradient through ReLU
-        let relu_grad = hidden_pre_activation.mapv(|x| if x > 0.0 { 1.0 } else { 0.0 });
-        let grad_hidden_pre_activation = grad_hidden_post_activation * relu_grad;
+        let grad_hidden_pre_activation = match self.ffn_type {
+            FfnType::Relu => {
+                // Gradient through ReLU
+                let relu_grad = hidden_pre_activation.mapv(|x| if x > 0.0 { 1.0 } else { 0.0 });
+                grad_hidden_post_activation * relu_grad
+            }
+            FfnType::GeGlu => {
+                // Gradient through value ⊙ GELU(gate), for the value and gate halves
+                let value = hidden_pre_activation.slice(s![.., ..self.hidden_dim]);
+                let gate = hidden_pre_activation.slice(s![.., self.hidden_dim..]);
+                let grad_value = &grad_hidden_post_activation * &gate.mapv(gelu);
+                let grad_gate = &grad_hidden_post_activation * &value * gate.mapv(gelu_derivative);
+                concatenate(Axis(1), &[grad_value.view(), grad_gate.view()]).unwrap()
+            }
+        };
 
         // Gradient w.r.t. W1 and b1
-        let grad_w1 = input.t().dot(&grad_hidden_pre_activation);
-        let grad_b1 = grad_hidden_pre_activation
+        let mut grad_w1 = input.t().dot(&grad_hidden_pre_activation);
+        let mut grad_b1 = grad_hidden_pre_activation
             .sum_axis(Axis(0))
             .insert_axis(Axis(0)); // Shape: [1, hidden_dim]
 
@@ -81,9 +185,23 @@ impl Layer for FeedForward {
         let grad_input_feedforward = grad_hidden_pre_activation.dot(&self.w1.t());
 
         // Add gradient from residual connection
-        // Forward: output = W2(ReLU(W1*input + b1)) + b2 + input
+        // Forward: output = residual_scale * (W2(ReLU(W1*input + b1)) + b2) + input
         // Backward: grad_input = grad_feedforward + grad_residual
-        let grad_input = grad_input_feedforward + grads;
+        let grad_input = grad_input_feedforward + residual_grads;
+
+        if let Some(scale) = clip_scale(
+            grad_w1
+                .iter()
+                .chain(&grad_b1)
+                .chain(&grad_w2)
+                .chain(&grad_b2),
+            max_norm,
+        ) {
+            grad_w1 *= scale;
+            grad_b1 *= scale;
+            grad_w2 *= scale;
+            grad_b2 *= scale;
+        }
 
         // Update parameters via Adam optimizer
         self.optimizer_w2.step(&mut self.w2, &grad_w2, lr);
@@ -95,8 +213,7 @@ impl Layer for FeedForward {
     }
 
     fn forward(&mut self, input: &Array2<f32>) -> Array2<f32> {
-        let hidden_pre_activation = input.dot(&self.w1) + &self.b1;
-        let hidden_post_activation = hidden_pre_activation.mapv(|x| x.max(0.0)); // ReLU
+        let (hidden_pre_activation, hidden_post_activation) = self.hidden(input);
 
         let output = hidden_post_activation.dot(&self.w2) + &self.b2;
 
@@ -105,10 +222,37 @@ impl Layer for FeedForward {
         self.hidden_pre_activation = Some(hidden_pre_activation);
         self.hidden_post_activation = Some(hidden_post_activation);
 
-        output + input // residual connection (no LayerNorm here)
+        output * self.residual_scale + input // residual connection (no LayerNorm here)
     }
 
     fn parameters(&self) -> usize {
         self.b1.len() + self.b2.len() + self.w1.len() + self.w2.len()
     }
+
+    fn weights(&self) -> Vec<&Array2<f32>> {
+        vec![&self.w1, &self.b1, &self.w2, &self.b2]
+    }
+
+    fn weights_mut(&mut self) -> Vec<&mut Array2<f32>> {
+        vec![&mut self.w1, &mut self.b1, &mut self.w2, &mut self.b2]
+    }
+
+    fn reinitialize(&mut self) {
+        let embedding_dim = self.w1.nrows();
+        let residual_scale = self.residual_scale;
+        *self = FeedForward::with_type(embedding_dim, self.hidden_dim, self.ffn_type);
+        self.residual_scale = residual_scale;
+    }
+
+    fn input_dim(&self) -> Option<usize> {
+        Some(self.w1.nrows())
+    }
+
+    fn output_dim(&self) -> Option<usize> {
+        Some(self.w2.ncols())
+    }
+
+    fn hidden_activations(&self, input: &Array2<f32>) -> Option<Array2<f32>> {
+        Some(self.hidden(input).1)
+    }
 }
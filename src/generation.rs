@@ -0,0 +1,199 @@
+//! Decoding configuration for text generation.
+
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::MAX_SEQ_LEN;
+
+/// What generation does when the prompt plus `max_new_tokens` exceeds [`MAX_SEQ_LEN`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContextOverflow {
+    /// Keep the whole prompt and generate only as many tokens as still fit
+    #[default]
+    ClampNewTokens,
+    /// Drop the oldest prompt tokens so all `max_new_tokens` fit (at most
+    /// `MAX_SEQ_LEN - 1`, keeping at least one prompt token)
+    TruncateOldest,
+    /// Refuse to generate; [`crate::LLM::try_generate`] returns the error
+    Error,
+}
+
+/// Options controlling how [`crate::LLM::generate`] decodes tokens.
+#[derive(Debug, Clone)]
+pub struct GenerationConfig {
+    /// Maximum number of tokens to generate
+    pub max_new_tokens: usize,
+    /// Stop when the chosen token's probability falls below this value
+    pub min_token_prob: Option<f32>,
+    /// Wall-clock budget; generation returns what it has produced once exceeded
+    pub timeout: Option<Duration>,
+    /// Prefix that opens a user turn (e.g. `"User:"`). Generation stops before emitting
+    /// it, so the assistant's turn ends instead of inventing the next user message
+    pub user_prefix: Option<String>,
+    /// Added to the logits of the given token ids before softmax at every step; a bias of
+    /// `f32::NEG_INFINITY` makes a token impossible
+    pub logit_bias: HashMap<usize, f32>,
+    /// How to keep the prompt plus generated tokens within the context window
+    pub context_overflow: ContextOverflow,
+    /// Sample each token instead of picking the most probable one; `None` is greedy
+    pub sampling: Option<SamplingOptions>,
+    /// `(start, end)` temperatures, linearly interpolated from the first to the last of
+    /// `max_new_tokens` steps, overriding `sampling`'s temperature (and sampling with
+    /// `top_p` 1.0 if `sampling` is `None`)
+    pub temperature_schedule: Option<(f32, f32)>,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            max_new_tokens: MAX_SEQ_LEN,
+            min_token_prob: None,
+            timeout: None,
+            user_prefix: None,
+            logit_bias: HashMap::new(),
+            context_overflow: ContextOverflow::default(),
+            sampling: None,
+            temperature_schedule: None,
+        }
+    }
+}
+
+impl GenerationConfig {
+    /// Temperature of [`GenerationConfig::temperature_schedule`] at generation step
+    /// `step` (0-based), reaching the end value at step `max_new_tokens - 1`.
+    pub fn temperature_at(&self, step: usize) -> Option<f32> {
+        let (start, end) = self.temperature_schedule?;
+        let last = self.max_new_tokens.saturating_sub(1);
+        if last == 0 {
+            return Some(end);
+        }
+        let progress = step.min(last) as f32 / last as f32;
+        Some(start + (end - start) * progress)
+    }
+
+    /// Sampling options in effect at generation step `step`; `None` decodes greedily.
+    pub fn sampling_at(&self, step: usize) -> Option<SamplingOptions> {
+        match self.temperature_at(step) {
+            Some(temperature) => Some(SamplingOptions {
+                temperature,
+                ..self.sampling.clone().unwrap_or_default()
+            }),
+            None => self.sampling.clone(),
+        }
+    }
+}
+
+/// Temperatures at or below this decode greedily; dividing logits by them would overflow.
+pub const MIN_TEMPERATURE: f32 = 1e-5;
+
+/// Stochastic decoding options for [`GenerationConfig::sampling`].
+///
+/// Logits are divided by `temperature` and sampled from the [`nucleus_filter`] of the
+/// resulting distribution. A temperature at or below [`MIN_TEMPERATURE`] (including 0
+/// and NaN) switches to exact greedy decoding instead of dividing, so it never produces
+/// Inf or NaN probabilities.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingOptions {
+    /// Softmax temperature; higher is more random
+    pub temperature: f32,
+    /// Nucleus (top-p) threshold; 1.0 samples from the full distribution
+    pub top_p: f32,
+}
+
+impl Default for SamplingOptions {
+    fn default() -> Self {
+        Self {
+            temperature: 1.0,
+            top_p: 1.0,
+        }
+    }
+}
+
+impl SamplingOptions {
+    /// Whether the temperature is too low to sample from, so decoding is greedy.
+    pub fn is_greedy(&self) -> bool {
+        self.temperature.is_nan() || self.temperature <= MIN_TEMPERATURE
+    }
+
+    /// Choose the next token id from a row of logits.
+    pub fn sample(&self, logits: &[f32], rng: &mut impl Rng) -> usize {
+        if self.is_greedy() {
+            return argmax(logits);
+        }
+
+        let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        if !max.is_finite() {
+            return argmax(logits);
+        }
+        let weights: Vec<f32> = logits
+            .iter()
+            .map(|&logit| ((logit - max) / self.temperature).exp())
+            .collect();
+        let total: f32 = weights.iter().sum();
+        let probs: Vec<f32> = weights.iter().map(|w| w / total).collect();
+
+        let nucleus = nucleus_filter(&probs, self.top_p);
+        let mut threshold = rng.random::<f32>();
+        for &(token, prob) in &nucleus {
+            if threshold < prob {
+                return token;
+            }
+            threshold -= prob;
+        }
+        nucleus[nucleus.len() - 1].0
+    }
+}
+
+/// Index of the largest value, preferring the lowest index on ties and skipping NaN.
+///
+/// Every greedy decoding path, including [`crate::LLM::greedy_decode`], goes through
+/// here so they always pick the same token.
+pub(crate) fn argmax<'a>(values: impl IntoIterator<Item = &'a f32>) -> usize {
+    let mut best = (0, f32::NAN);
+    for (index, &value) in values.into_iter().enumerate() {
+        if value > best.1 || (best.1.is_nan() && !value.is_nan()) {
+            best = (index, value);
+        }
+    }
+    best.0
+}
+
+/// Beam-search score of a hypothesis with total log-probability `logprob` over `len`
+/// generated tokens: `logprob / ((5 + len) / 6)^length_penalty`.
+///
+/// A penalty of 0 ranks by raw log-probability, which favors short hypotheses; larger
+/// penalties increasingly favor longer ones.
+pub fn length_normalized_score(logprob: f32, len: usize, length_penalty: f32) -> f32 {
+    logprob / ((5.0 + len as f32) / 6.0).powf(length_penalty)
+}
+
+/// Nucleus (top-p) filtering: the smallest set of most-probable tokens whose cumulative
+/// probability first reaches `top_p`, renormalized to sum to 1.
+///
+/// Returns `(index, prob)` pairs sorted by descending probability, ties broken by lower
+/// index. At least one token is always kept, so `top_p <= 0` yields only the most
+/// probable token.
+pub fn nucleus_filter(probs: &[f32], top_p: f32) -> Vec<(usize, f32)> {
+    let mut ranked: Vec<(usize, f32)> = probs.iter().copied().enumerate().collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut cumulative = 0.0;
+    let mut keep = 0;
+    for &(_, prob) in &ranked {
+        cumulative += prob;
+        keep += 1;
+        if cumulative >= top_p {
+            break;
+        }
+    }
+    ranked.truncate(keep.max(1));
+
+    let total: f32 = ranked.iter().map(|&(_, prob)| prob).sum();
+    if total > 0.0 {
+        for (_, prob) in &mut ranked {
+            *prob /= total;
+        }
+    }
+    ranked
+}
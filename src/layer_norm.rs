@@ -1,7 +1,11 @@
 use ndarray::{Array2, Axis};
 
-use crate::{adam::Adam, llm::Layer};
+use crate::{
+    adam::Adam,
+    llm::{clip_scale, Layer},
+};
 
+#[derive(Clone)]
 pub struct LayerNorm {
     epsilon: f32,       // Small constant for stability
     gamma: Array2<f32>, // Learnable scaling parameter
@@ -54,6 +58,15 @@ impl Layer for LayerNorm {
     }
 
     fn backward(&mut self, grads: &Array2<f32>, lr: f32) -> Array2<f32> {
+        self.backward_clipped(grads, lr, None)
+    }
+
+    fn backward_clipped(
+        &mut self,
+        grads: &Array2<f32>,
+        lr: f32,
+        max_norm: Option<f32>,
+    ) -> Array2<f32> {
         let input = self.cached_input.as_ref().unwrap();
         let mean = self.cached_mean.as_ref().unwrap();
         let std = self.cached_std.as_ref().unwrap();
@@ -62,8 +75,8 @@ impl Layer for LayerNorm {
         let n_features = input.shape()[1] as f32; // Number of features per token
 
         // Gradients w.r.t. gamma and beta
-        let grad_gamma = (&normalized * grads).sum_axis(Axis(0)).insert_axis(Axis(0));
-        let grad_beta = grads.sum_axis(Axis(0)).insert_axis(Axis(0));
+        let mut grad_gamma = (&normalized * grads).sum_axis(Axis(0)).insert_axis(Axis(0));
+        let mut grad_beta = grads.sum_axis(Axis(0)).insert_axis(Axis(0));
 
         // Gradient w.r.t. normalized values
         let grad_normalized = &self.gamma * grads;
@@ -87,6 +100,10 @@ impl Layer for LayerNorm {
         };
 
         // Update learnable parameters
+        if let Some(scale) = clip_scale(grad_gamma.iter().chain(&grad_beta), max_norm) {
+            grad_gamma *= scale;
+            grad_beta *= scale;
+        }
         self.optimizer_gamma.step(&mut self.gamma, &grad_gamma, lr);
         self.optimizer_beta.step(&mut self.beta, &grad_beta, lr);
 
@@ -96,4 +113,24 @@ impl Layer for LayerNorm {
     fn parameters(&self) -> usize {
         self.gamma.len() + self.beta.len()
     }
+
+    fn weights(&self) -> Vec<&Array2<f32>> {
+        vec![&self.gamma, &self.beta]
+    }
+
+    fn weights_mut(&mut self) -> Vec<&mut Array2<f32>> {
+        vec![&mut self.gamma, &mut self.beta]
+    }
+
+    fn reinitialize(&mut self) {
+        *self = LayerNorm::new(self.gamma.ncols());
+    }
+
+    fn input_dim(&self) -> Option<usize> {
+        Some(self.gamma.ncols())
+    }
+
+    fn output_dim(&self) -> Option<usize> {
+        Some(self.gamma.ncols())
+    }
 }
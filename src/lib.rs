@@ -26,17 +26,26 @@
 //! ```
 
 pub mod adam;
+pub mod bpe;
 pub mod checkpoint;
 pub mod config;
 pub mod dataset_loader;
 pub mod embeddings;
 pub mod error;
 pub mod feed_forward;
+pub mod generation;
 pub mod layer_norm;
 pub mod llm;
 pub mod logging;
+pub mod loss;
 pub mod metrics;
+pub mod normalizer;
 pub mod output_projection;
+pub mod progress;
+pub mod quantization;
+pub mod repl;
+pub mod rng;
+pub mod scheduler;
 pub mod self_attention;
 pub mod training_ui;
 pub mod transformer;
@@ -44,20 +53,39 @@ pub mod visualization;
 pub mod vocab;
 
 // Re-export key types and functions for easier access
+pub use bpe::BpeTokenizer;
 pub use config::Config;
-pub use dataset_loader::{Dataset, DatasetType};
+pub use dataset_loader::{
+    AugmentOp, Dataset, DatasetCursor, DatasetType, LabeledDataset, TokenStats,
+};
 pub use embeddings::Embeddings;
 pub use error::{LlmError, Result};
-pub use llm::{Layer, LLM};
+pub use generation::{
+    length_normalized_score, nucleus_filter, ContextOverflow, GenerationConfig, SamplingOptions,
+    MIN_TEMPERATURE,
+};
+pub use llm::{
+    CurriculumStrategy, EpochContext, EpochMetrics, EpochStats, EvaluationReport, ExampleLoss,
+    GeneratedSample, GradientClip, Layer, NeuronStats, StepStats, TokenExplanation,
+    TrainingObjective, TrainingReport, TruncationStrategy, LLM,
+};
 pub use logging::{init_json_logging, init_logging};
-pub use metrics::Metrics;
+pub use loss::{CrossEntropyLoss, FocalLoss, LossFn};
+pub use metrics::{Metrics, MetricsSummary};
+pub use normalizer::TextNormalizer;
+pub use progress::{JsonLinesProgress, NoProgress, PrintProgress, ProgressReporter};
+pub use quantization::{QuantizedModel, QuantizedTensor};
+pub use scheduler::ReduceOnPlateau;
 pub use vocab::Vocab;
 
 // Re-export checkpoint management
-pub use checkpoint::{Checkpoint, CheckpointManager};
+pub use checkpoint::{
+    Checkpoint, CheckpointManager, CheckpointMetadata, InferenceBundle, VocabReconciliation,
+    CHECKPOINT_FORMAT_VERSION, CHECKPOINT_MAGIC,
+};
 
 // Re-export visualization
-pub use visualization::{TrainingVisualizer, VisualizationConfig};
+pub use visualization::{AccentColor, TrainingVisualizer, VisualizationConfig};
 
 /// Model configuration constants
 pub const MAX_SEQ_LEN: usize = 80;
@@ -1,279 +1,2365 @@
-use std::cmp::Ordering;
-
 use ndarray::{Array1, Array2, Axis};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::mpsc::Sender;
+
+use crate::{
+    checkpoint::{Checkpoint, InferenceBundle, VocabReconciliation},
+    config::Config,
+    dataset_loader::{Dataset, DatasetCursor},
+    embeddings::cosine_similarity,
+    error::{LlmError, Result},
+    generation::{argmax, length_normalized_score, ContextOverflow},
+    loss::{CrossEntropyLoss, LossFn},
+    output_projection::OutputProjection,
+    progress::{PrintProgress, ProgressReporter},
+    self_attention::SelfAttention,
+    transformer::TransformerBlock,
+    Embeddings, GenerationConfig, TextNormalizer, Vocab, EMBEDDING_DIM, HIDDEN_DIM, MAX_SEQ_LEN,
+};
+/// Cloning for boxed layers, implemented for every `Layer + Clone` type.
+pub trait LayerClone {
+    fn clone_box(&self) -> Box<dyn Layer>;
+}
+
+impl<T: Layer + Clone + 'static> LayerClone for T {
+    fn clone_box(&self) -> Box<dyn Layer> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Layer> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+pub trait Layer: LayerClone {
+    fn layer_type(&self) -> &str;
+
+    fn forward(&mut self, input: &Array2<f32>) -> Array2<f32>;
+
+    fn backward(&mut self, grads: &Array2<f32>, lr: f32) -> Array2<f32>;
+
+    /// [`Layer::backward`], first scaling this layer's own parameter gradients down to a
+    /// combined L2 norm of at most `max_norm` (when set). The gradient passed on to earlier
+    /// layers is not clipped. Layers without parameters ignore `max_norm`.
+    fn backward_clipped(
+        &mut self,
+        grads: &Array2<f32>,
+        lr: f32,
+        _max_norm: Option<f32>,
+    ) -> Array2<f32> {
+        self.backward(grads, lr)
+    }
+
+    fn parameters(&self) -> usize;
+
+    /// Toggle causal masking for layers that attend over the sequence.
+    ///
+    /// Layers without attention ignore this.
+    fn set_causal(&mut self, _causal: bool) {}
+
+    /// Whether causal masking is on, or `None` for layers without attention.
+    fn causal(&self) -> Option<bool> {
+        None
+    }
+
+    /// Mark key positions holding padding (`true`) so attention never attends to them;
+    /// `None` clears the mask. Layers without attention ignore this.
+    fn set_padding_mask(&mut self, _mask: Option<&[bool]>) {}
+
+    /// Assign each position a document id so attention stays within its document, as
+    /// needed for packed sequences; `None` clears the mask. Layers without attention
+    /// ignore this.
+    fn set_document_mask(&mut self, _document_ids: Option<&[usize]>) {}
+
+    /// Grow vocabulary-sized parameters to `vocab_size` entries, initializing new entries
+    /// freshly. Layers that do not depend on the vocabulary ignore this.
+    fn resize_vocab(&mut self, _vocab_size: usize) {}
+
+    /// Trainable parameter matrices, in a stable order.
+    fn weights(&self) -> Vec<&Array2<f32>> {
+        Vec::new()
+    }
+
+    /// Mutable access to the matrices of [`Layer::weights`], in the same order.
+    fn weights_mut(&mut self) -> Vec<&mut Array2<f32>> {
+        Vec::new()
+    }
+
+    /// Rebuild vocabulary-sized parameters for a new vocabulary: entry `i` takes the
+    /// current entry `old_ids[i]`, or is initialized freshly when `None`. Optimizer state
+    /// for those parameters is reset. Layers that do not depend on the vocabulary ignore
+    /// this.
+    fn remap_vocab(&mut self, _old_ids: &[Option<usize>]) {}
+
+    /// Re-draw all parameters from [`crate::rng::init_rng`] and reset optimizer state.
+    fn reinitialize(&mut self) {}
+
+    /// Attention weights (one row per query) this layer would compute for `input`.
+    /// Layers without attention return `None`.
+    fn attention_map(&self, _input: &Array2<f32>) -> Option<Array2<f32>> {
+        None
+    }
+
+    /// Feed-forward hidden activations (one row per position, after the nonlinearity)
+    /// this layer would compute for `input`. Layers without a feed-forward network
+    /// return `None`.
+    fn hidden_activations(&self, _input: &Array2<f32>) -> Option<Array2<f32>> {
+        None
+    }
+
+    /// Width of each row this layer accepts, if it has a fixed one; checked against the
+    /// previous layer's [`Layer::output_dim`] by [`LLM::try_new`].
+    fn input_dim(&self) -> Option<usize> {
+        None
+    }
+
+    /// Width of each row this layer produces, if it has a fixed one.
+    fn output_dim(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Objective used to build inputs and targets from a training row.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TrainingObjective {
+    /// Next-token prediction with causal attention.
+    #[default]
+    CausalLM,
+    /// BERT-style masked prediction: each token is replaced by `<mask>` with probability
+    /// `mask_prob` and only the masked positions are scored. Attention is bidirectional.
+    /// Masks are drawn from [`crate::rng::init_rng`], so [`crate::rng::seed`] makes them
+    /// reproducible.
+    MaskedLM { mask_prob: f32 },
+}
+
+/// Order in which training samples are visited, for curriculum learning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum CurriculumStrategy {
+    /// Visit samples shortest first (by token count) for the first `epochs` epochs, then
+    /// in a fresh random order every epoch
+    ByLength { epochs: usize },
+    /// Visit every sample exactly once per epoch in a random permutation drawn from
+    /// `seed` and the epoch number, so runs with the same seed see the same orders
+    Shuffle { seed: u64 },
+}
+
+impl std::str::FromStr for CurriculumStrategy {
+    type Err = String;
+
+    /// Parse `by_length:<epochs>` or `shuffle:<seed>`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().split_once(':') {
+            Some(("by_length", epochs)) => epochs
+                .trim()
+                .parse()
+                .map(|epochs| CurriculumStrategy::ByLength { epochs })
+                .map_err(|e| format!("invalid curriculum epochs {:?}: {}", epochs, e)),
+            Some(("shuffle", seed)) => seed
+                .trim()
+                .parse()
+                .map(|seed| CurriculumStrategy::Shuffle { seed })
+                .map_err(|e| format!("invalid curriculum seed {:?}: {}", seed, e)),
+            _ => Err(format!(
+                "unknown curriculum {:?} (expected \"by_length:<epochs>\" or \"shuffle:<seed>\")",
+                s
+            )),
+        }
+    }
+}
+
+/// Which tokens [`LLM::tokenize`] keeps when a text exceeds [`MAX_SEQ_LEN`] tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TruncationStrategy {
+    /// Keep the first tokens
+    Head,
+    /// Keep the last tokens, i.e. the most recent context in a conversation
+    #[default]
+    Tail,
+    /// Keep a window centered on the middle of the text, dropping both ends
+    Middle,
+}
+
+impl TruncationStrategy {
+    /// Cut `tokens` down to at most `max_len` tokens.
+    pub fn apply(self, mut tokens: Vec<usize>, max_len: usize) -> Vec<usize> {
+        if tokens.len() <= max_len {
+            return tokens;
+        }
+        let start = match self {
+            TruncationStrategy::Head => 0,
+            TruncationStrategy::Tail => tokens.len() - max_len,
+            TruncationStrategy::Middle => (tokens.len() - max_len) / 2,
+        };
+        tokens.drain(..start);
+        tokens.truncate(max_len);
+        tokens
+    }
+}
+
+/// How gradients are clipped during backpropagation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientClip {
+    /// Clip the output gradient to this L2 norm once, before it enters the network.
+    Global(f32),
+    /// Clip each layer's own parameter gradients to this L2 norm before its optimizer
+    /// step, so one exploding layer is scaled on its own instead of shrinking every
+    /// layer's update. See [`Layer::backward_clipped`].
+    PerLayer(f32),
+}
+
+/// Factor that brings gradients with these entries down to an L2 norm of `max_norm`, or
+/// `None` when there is no limit or they are already within it.
+pub(crate) fn clip_scale<'a>(
+    grads: impl IntoIterator<Item = &'a f32>,
+    max_norm: Option<f32>,
+) -> Option<f32> {
+    let max_norm = max_norm?;
+    let norm = grads.into_iter().map(|g| g * g).sum::<f32>().sqrt();
+    (norm > max_norm).then(|| max_norm / norm)
+}
+
+impl Default for GradientClip {
+    fn default() -> Self {
+        GradientClip::Global(5.0)
+    }
+}
+
+/// Statistics from a single training step.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StepStats {
+    /// Loss over the scored positions
+    pub loss: f32,
+    /// Scored positions whose greedy prediction matched the target
+    pub correct: usize,
+    /// Number of positions scored
+    pub scored: usize,
+    /// L2 norm of the output gradient before clipping
+    pub grad_norm: f32,
+}
+
+/// Aggregated statistics for one training epoch.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EpochStats {
+    /// Epoch number (1-based)
+    pub epoch: usize,
+    /// Average loss per training row
+    pub loss: f32,
+    /// Fraction of scored positions predicted correctly
+    pub accuracy: f32,
+    /// Average pre-clipping gradient norm per step
+    pub grad_norm: f32,
+    /// Learning rate used for the epoch
+    pub lr: f32,
+}
+
+impl EpochStats {
+    /// Aggregate the statistics of one step per training row.
+    pub fn from_steps(epoch: usize, lr: f32, steps: &[StepStats]) -> Self {
+        let scored_steps = steps.iter().filter(|s| s.scored > 0);
+        let num_scored_steps = scored_steps.clone().count().max(1) as f32;
+        let correct: usize = steps.iter().map(|s| s.correct).sum();
+        let scored: usize = steps.iter().map(|s| s.scored).sum();
+
+        Self {
+            epoch,
+            loss: steps.iter().map(|s| s.loss).sum::<f32>() / steps.len().max(1) as f32,
+            accuracy: correct as f32 / scored.max(1) as f32,
+            grad_norm: scored_steps.map(|s| s.grad_norm).sum::<f32>() / num_scored_steps,
+            lr,
+        }
+    }
+
+    /// Emit the statistics as a structured tracing event, so JSON logs carry each value
+    /// as a typed field.
+    pub fn log(&self) {
+        tracing::info!(
+            epoch = self.epoch as u64,
+            loss = self.loss as f64,
+            accuracy = self.accuracy as f64,
+            grad_norm = self.grad_norm as f64,
+            lr = self.lr as f64,
+            "Epoch complete"
+        );
+    }
+}
+
+/// Metrics sent by [`LLM::train_with_channel`] after each epoch.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EpochMetrics {
+    /// Epoch number (1-based)
+    pub epoch: usize,
+    /// Average loss per training row
+    pub loss: f32,
+    /// Fraction of scored positions predicted correctly
+    pub accuracy: f32,
+    /// Average pre-clipping gradient norm per step
+    pub grad_norm: f32,
+}
+
+impl From<EpochStats> for EpochMetrics {
+    fn from(stats: EpochStats) -> Self {
+        Self {
+            epoch: stats.epoch,
+            loss: stats.loss,
+            accuracy: stats.accuracy,
+            grad_norm: stats.grad_norm,
+        }
+    }
+}
+
+/// State passed to the [`LLM::train_with_callback`] hook at the start of each epoch.
+#[derive(Debug)]
+pub struct EpochContext<'a> {
+    /// Epoch about to start (0-based)
+    pub epoch: usize,
+    /// Average loss of the previous epoch; `None` before the first
+    pub avg_loss: Option<f32>,
+    /// Set to `true` to stop training before this epoch runs
+    pub stop: &'a mut bool,
+    /// Learning rate for this epoch and, unless changed again, the ones after it; a
+    /// scheduler such as [`crate::ReduceOnPlateau`] adjusts it here
+    pub lr: &'a mut f32,
+}
+
+/// Forward-only evaluation of next-token prediction, from [`LLM::evaluate`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EvaluationReport {
+    /// Mean cross-entropy per predicted token
+    pub loss: f32,
+    /// `exp(loss)`
+    pub perplexity: f32,
+    /// Fraction of predicted tokens the greedy prediction got right
+    pub accuracy: f32,
+    /// Number of predicted tokens
+    pub tokens: usize,
+}
+
+impl std::fmt::Display for EvaluationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Tokens: {}", self.tokens)?;
+        writeln!(f, "Loss: {:.4}", self.loss)?;
+        writeln!(f, "Perplexity: {:.2}", self.perplexity)?;
+        write!(f, "Accuracy: {:.2}%", self.accuracy * 100.0)
+    }
+}
+
+/// Loss of a single training example in one epoch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExampleLoss {
+    /// Index of the example in the training data
+    pub index: usize,
+    /// The example text
+    pub text: String,
+    /// Loss of the example's training step
+    pub loss: f32,
+}
+
+/// Summary of a training run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrainingReport {
+    /// Statistics for each completed epoch
+    pub epochs: Vec<EpochStats>,
+    /// Highest-loss examples per epoch, hardest first
+    hardest: Vec<Vec<ExampleLoss>>,
+    /// Lowest-loss examples per epoch, easiest first
+    easiest: Vec<Vec<ExampleLoss>>,
+    /// Completions of [`LLM::sample_prompts`] generated during training
+    pub samples: Vec<GeneratedSample>,
+}
+
+/// A completion generated from one of [`LLM::sample_prompts`] during training.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedSample {
+    /// Epoch after which the sample was generated (1-based)
+    pub epoch: usize,
+    pub prompt: String,
+    pub completion: String,
+}
+
+impl TrainingReport {
+    /// Record an epoch, keeping the `track` highest- and lowest-loss examples.
+    ///
+    /// `losses` holds `(index, loss)` pairs into `data`; rows too short to score should be
+    /// left out so they are not mistaken for easy examples.
+    pub fn record_epoch(
+        &mut self,
+        stats: EpochStats,
+        data: &[&str],
+        losses: &[(usize, f32)],
+        track: usize,
+    ) {
+        let mut ranked = losses.to_vec();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let to_examples = |pairs: &mut dyn Iterator<Item = &(usize, f32)>| {
+            pairs
+                .take(track)
+                .map(|&(index, loss)| ExampleLoss {
+                    index,
+                    text: data[index].to_string(),
+                    loss,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        self.epochs.push(stats);
+        self.hardest.push(to_examples(&mut ranked.iter()));
+        self.easiest.push(to_examples(&mut ranked.iter().rev()));
+    }
+
+    /// Highest-loss examples of `epoch` (1-based), hardest first. Empty if the epoch was
+    /// not recorded or tracking was disabled.
+    pub fn hardest_examples(&self, epoch: usize) -> &[ExampleLoss] {
+        epoch
+            .checked_sub(1)
+            .and_then(|i| self.hardest.get(i))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Lowest-loss examples of `epoch` (1-based), easiest first. Empty if the epoch was
+    /// not recorded or tracking was disabled.
+    pub fn easiest_examples(&self, epoch: usize) -> &[ExampleLoss] {
+        epoch
+            .checked_sub(1)
+            .and_then(|i| self.easiest.get(i))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+/// A language model: a vocabulary and a stack of layers.
+///
+/// `clone()` is a deep copy - every layer's parameters and optimizer state are
+/// duplicated - so a clone can be trained independently of the original.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone)]
+pub struct LLM {
+    pub vocab: Vocab,
+    pub network: Vec<Box<dyn Layer>>,
+    objective: TrainingObjective,
+    pub loss_fn: Box<dyn LossFn>,
+    /// Normalizer applied to text before tokenization; must match the one used on the
+    /// training data
+    pub normalizer: Option<TextNormalizer>,
+    /// Number of highest- and lowest-loss examples recorded in the [`TrainingReport`]
+    /// each epoch; 0 disables tracking
+    pub track_examples: usize,
+    /// Gradient clipping applied during backpropagation
+    pub gradient_clip: GradientClip,
+    /// Treat `</s>` inside a sequence as a document boundary that attention may not
+    /// cross; enable when training on packed sequences
+    pub document_masking: bool,
+    /// Probability of feeding the ground-truth token at each causal training position;
+    /// otherwise the model's own prediction for that position is fed (scheduled
+    /// sampling). 1.0 is plain teacher forcing. Positions are drawn from
+    /// [`crate::rng::init_rng`], so [`crate::rng::seed`] makes them reproducible
+    pub teacher_forcing_ratio: f32,
+    /// Maximum number of texts remembered by [`LLM::tokenize_cached`]; 0 disables the
+    /// cache
+    pub token_cache_capacity: usize,
+    /// Order in which samples are visited each epoch; `None` keeps the data order
+    pub curriculum: Option<CurriculumStrategy>,
+    /// Fraction of out-of-vocabulary words in a prompt above which
+    /// [`LLM::tokenize_with_stats`] logs a warning; 1.0 or more never warns
+    pub oov_warning_threshold: f32,
+    /// Which tokens to keep when a text is longer than [`MAX_SEQ_LEN`]
+    pub truncation: TruncationStrategy,
+    /// Keep the embeddings frozen (see [`LLM::set_layer_trainable`]) for this many
+    /// epochs at the start of each training run, unfreezing them at the next epoch (a
+    /// shorter run leaves them frozen); 0 never freezes them
+    pub freeze_embeddings_epochs: usize,
+    /// Prompts completed and logged every [`LLM::sample_interval`] epochs during
+    /// training, to follow qualitative progress
+    pub sample_prompts: Vec<String>,
+    /// Epochs between completions of [`LLM::sample_prompts`]; 0 never samples
+    pub sample_interval: usize,
+    /// Indices of layers excluded from parameter updates
+    frozen_layers: std::collections::HashSet<usize>,
+    token_cache: TokenCache,
+}
+
+/// Token ids of previously tokenized texts, valid for the vocabulary they were
+/// tokenized with.
+#[derive(Clone, Default)]
+struct TokenCache {
+    vocab_words: Vec<String>,
+    entries: std::collections::HashMap<String, Vec<usize>>,
+}
+
+/// Default for [`LLM::token_cache_capacity`].
+pub const DEFAULT_TOKEN_CACHE_CAPACITY: usize = 100_000;
+
+/// Default for [`LLM::oov_warning_threshold`].
+pub const DEFAULT_OOV_WARNING_THRESHOLD: f32 = 0.5;
+
+impl Default for LLM {
+    fn default() -> Self {
+        let transformer_block = TransformerBlock::new(EMBEDDING_DIM, HIDDEN_DIM);
+        let output_projection = OutputProjection::new(EMBEDDING_DIM, Vocab::default_words().len());
+        Self {
+            vocab: Vocab::default(),
+            network: vec![
+                Box::new(Embeddings::default()),
+                Box::new(transformer_block),
+                Box::new(output_projection),
+            ],
+            objective: TrainingObjective::default(),
+            loss_fn: Box::new(CrossEntropyLoss),
+            normalizer: None,
+            track_examples: 0,
+            gradient_clip: GradientClip::default(),
+            document_masking: false,
+            teacher_forcing_ratio: 1.0,
+            token_cache_capacity: DEFAULT_TOKEN_CACHE_CAPACITY,
+            curriculum: None,
+            oov_warning_threshold: DEFAULT_OOV_WARNING_THRESHOLD,
+            truncation: TruncationStrategy::default(),
+            freeze_embeddings_epochs: 0,
+            sample_prompts: Vec::new(),
+            sample_interval: 0,
+            frozen_layers: std::collections::HashSet::new(),
+            token_cache: TokenCache::default(),
+        }
+    }
+}
+
+/// One generated token as reported by [`LLM::explain`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenExplanation {
+    /// The token that was emitted
+    pub token: String,
+    /// Probability the model assigned to it (after `logit_bias`)
+    pub probability: f32,
+    /// The most probable tokens at this step with their probabilities, most probable
+    /// first; the chosen token is among them unless sampling picked a less likely one
+    pub alternatives: Vec<(String, f32)>,
+}
+
+/// How often each feed-forward hidden unit fired, as reported by
+/// [`LLM::ffn_activation_stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NeuronStats {
+    /// Index of the layer in [`LLM::network`]
+    pub layer: usize,
+    /// Per hidden unit, the fraction of token positions at which its activation was
+    /// nonzero
+    pub activation_fraction: Vec<f32>,
+}
+
+impl NeuronStats {
+    /// Units that never activated (dead).
+    pub fn dead_units(&self) -> Vec<usize> {
+        self.units_where(|fraction| fraction == 0.0)
+    }
+
+    /// Units that activated at every position (saturated), so they never gate anything.
+    pub fn saturated_units(&self) -> Vec<usize> {
+        self.units_where(|fraction| fraction == 1.0)
+    }
+
+    fn units_where(&self, predicate: impl Fn(f32) -> bool) -> Vec<usize> {
+        self.activation_fraction
+            .iter()
+            .enumerate()
+            .filter(|&(_, &fraction)| predicate(fraction))
+            .map(|(unit, _)| unit)
+            .collect()
+    }
+}
+
+/// Number of alternatives listed in each [`TokenExplanation`].
+pub const EXPLANATION_ALTERNATIVES: usize = 3;
+
+/// A partial hypothesis in [`LLM::generate_beam`].
+struct Beam {
+    tokens: Vec<usize>,
+    logprob: f32,
+    finished: bool,
+}
+
+impl LLM {
+    /// [`LLM::new`], first checking that each layer accepts rows as wide as the previous
+    /// layer produces.
+    ///
+    /// # Errors
+    /// Returns `ArchitectureError` naming the first pair of adjacent layers whose
+    /// dimensions disagree. Layers that do not report a dimension are not checked.
+    pub fn try_new(vocab: Vocab, network: Vec<Box<dyn Layer>>) -> Result<Self> {
+        for (idx, pair) in network.windows(2).enumerate() {
+            if let (Some(output), Some(input)) = (pair[0].output_dim(), pair[1].input_dim()) {
+                if output != input {
+                    return Err(LlmError::architecture(format!(
+                        "layer {} ({}) outputs {} dimensions but layer {} ({}) expects {}",
+                        idx,
+                        pair[0].layer_type(),
+                        output,
+                        idx + 1,
+                        pair[1].layer_type(),
+                        input
+                    )));
+                }
+            }
+        }
+        Ok(Self::new(vocab, network))
+    }
+
+    pub fn new(vocab: Vocab, network: Vec<Box<dyn Layer>>) -> Self {
+        Self {
+            vocab,
+            network,
+            objective: TrainingObjective::default(),
+            loss_fn: Box::new(CrossEntropyLoss),
+            normalizer: None,
+            track_examples: 0,
+            gradient_clip: GradientClip::default(),
+            document_masking: false,
+            teacher_forcing_ratio: 1.0,
+            token_cache_capacity: DEFAULT_TOKEN_CACHE_CAPACITY,
+            curriculum: None,
+            oov_warning_threshold: DEFAULT_OOV_WARNING_THRESHOLD,
+            truncation: TruncationStrategy::default(),
+            freeze_embeddings_epochs: 0,
+            sample_prompts: Vec::new(),
+            sample_interval: 0,
+            frozen_layers: std::collections::HashSet::new(),
+            token_cache: TokenCache::default(),
+        }
+    }
+}
+
+impl LLM {
+    pub fn network_description(&self) -> String {
+        self.network
+            .iter()
+            .map(|layer| layer.layer_type())
+            .collect::<Vec<&str>>()
+            .join(", ")
+    }
+
+    /// Graphviz DOT graph of the layer chain, one node per layer labelled with its type,
+    /// weight shapes and parameter count. Render with `dot -Tpng model.dot -o model.png`.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph LLM {\n    rankdir=TB;\n    node [shape=box];\n");
+        for (idx, layer) in self.network.iter().enumerate() {
+            let shapes = layer
+                .weights()
+                .iter()
+                .map(|weight| format!("{}x{}", weight.nrows(), weight.ncols()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let mut label = format!("{}: {}", idx, layer.layer_type());
+            if !shapes.is_empty() {
+                label.push_str(&format!("\\nweights: {}", shapes));
+            }
+            label.push_str(&format!("\\nparams: {}", layer.parameters()));
+            dot.push_str(&format!(
+                "    layer{} [label=\"{}\"];\n",
+                idx,
+                label.replace('"', "\\\"")
+            ));
+        }
+        for idx in 1..self.network.len() {
+            dot.push_str(&format!("    layer{} -> layer{};\n", idx - 1, idx));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn total_parameters(&self) -> usize {
+        // Sum the parameters across all layers in the network
+        self.network
+            .iter()
+            .map(|layer| layer.parameters())
+            .sum::<usize>()
+    }
+
+    pub fn predict(&mut self, text: &str) -> String {
+        self.generate(text, &GenerationConfig::default())
+    }
+
+    /// Generate a continuation of `text` using the given decoding options.
+    pub fn generate(&mut self, text: &str, config: &GenerationConfig) -> String {
+        self.generate_with_logprobs(text, config).0
+    }
+
+    /// Draw `n` independent completions of `text`, e.g. for reranking.
+    ///
+    /// Each completion decodes with its own RNG state from [`crate::rng::init_rng`], so a
+    /// sampling config yields varied outputs while a greedy one repeats the same text.
+    /// `self` is left untouched.
+    pub fn generate_n(&self, text: &str, n: usize, config: &GenerationConfig) -> Vec<String> {
+        let mut model = self.clone();
+        (0..n).map(|_| model.generate(text, config)).collect()
+    }
+
+    /// [`LLM::generate`], returning an error instead of empty output when the request
+    /// does not fit the context window under [`ContextOverflow::Error`].
+    pub fn try_generate(&mut self, text: &str, config: &GenerationConfig) -> Result<String> {
+        Ok(self.try_generate_with_logprobs(text, config)?.0)
+    }
+
+    /// [`LLM::generate`], also returning each generated word with the log-probability
+    /// the model assigned to it (after `logit_bias`) when it was chosen.
+    pub fn generate_with_logprobs(
+        &mut self,
+        text: &str,
+        config: &GenerationConfig,
+    ) -> (String, Vec<(String, f32)>) {
+        self.try_generate_with_logprobs(text, config)
+            .unwrap_or_else(|e| {
+                tracing::warn!("{}", e);
+                (String::new(), Vec::new())
+            })
+    }
+
+    fn try_generate_with_logprobs(
+        &mut self,
+        text: &str,
+        config: &GenerationConfig,
+    ) -> Result<(String, Vec<(String, f32)>)> {
+        let (output_tokens, logprobs) = self.forward(text, config, None)?;
+
+        // Convert token_ids to strings
+        let token_logprobs: Vec<(String, f32)> = output_tokens
+            .iter()
+            .zip(logprobs)
+            .map(|(t, logprob)| (self.vocab.decode[t].clone(), logprob))
+            .collect();
+        let text = token_logprobs
+            .iter()
+            .map(|(word, _)| word.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        Ok((text, token_logprobs))
+    }
+
+    /// Decode a continuation of `prompt` like [`LLM::generate`], explaining each
+    /// generated token with its probability and the top alternatives the model weighed.
+    /// `self` is left untouched.
+    pub fn explain(&self, prompt: &str, cfg: &GenerationConfig) -> Vec<TokenExplanation> {
+        let mut model = self.clone();
+        let mut explanations = Vec::new();
+        if let Err(e) = model.forward(prompt, cfg, Some(&mut explanations)) {
+            tracing::warn!("{}", e);
+        }
+        explanations
+    }
+
+    /// Beam search over continuations of `text`, keeping the `beam_width` best
+    /// hypotheses at each step.
+    ///
+    /// Hypotheses are ranked by [`length_normalized_score`] with `length_penalty` and end
+    /// at `</s>` or the context limit. Returns up to `num_return_sequences` of them as
+    /// `(text, score)`, best first.
+    pub fn generate_beam(
+        &mut self,
+        text: &str,
+        beam_width: usize,
+        length_penalty: f32,
+        num_return_sequences: usize,
+    ) -> Vec<(String, f32)> {
+        let prompt = self.tokenize_with_stats(text).0;
+        if prompt.is_empty() || prompt.len() >= MAX_SEQ_LEN {
+            return Vec::new();
+        }
+        let beam_width = beam_width.max(1);
+        let eos = self.vocab.encode("</s>");
+        let score =
+            |beam: &Beam| length_normalized_score(beam.logprob, beam.tokens.len(), length_penalty);
+
+        let mut beams = vec![Beam {
+            tokens: Vec::new(),
+            logprob: 0.0,
+            finished: false,
+        }];
+        for _ in 0..MAX_SEQ_LEN - prompt.len() {
+            if beams.iter().all(|beam| beam.finished) {
+                break;
+            }
+
+            let mut candidates = Vec::new();
+            for beam in beams {
+                if beam.finished {
+                    candidates.push(beam);
+                    continue;
+                }
+                let sequence = [prompt.as_slice(), &beam.tokens].concat();
+                let logits = self.forward_tokens(&sequence);
+                let last = logits
+                    .row(logits.nrows() - 1)
+                    .to_owned()
+                    .insert_axis(Axis(0));
+                let log_probs = Self::log_softmax(&last);
+
+                let mut ranked: Vec<(usize, f32)> =
+                    log_probs.row(0).iter().copied().enumerate().collect();
+                ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+                for (token, log_prob) in ranked.into_iter().take(beam_width) {
+                    let mut tokens = beam.tokens.clone();
+                    tokens.push(token);
+                    candidates.push(Beam {
+                        tokens,
+                        logprob: beam.logprob + log_prob,
+                        finished: Some(token) == eos,
+                    });
+                }
+            }
+
+            candidates.sort_by(|a, b| score(b).total_cmp(&score(a)));
+            candidates.truncate(beam_width);
+            beams = candidates;
+        }
+
+        beams.sort_by(|a, b| score(b).total_cmp(&score(a)));
+        beams
+            .iter()
+            .take(num_return_sequences)
+            .map(|beam| {
+                let words: Vec<&str> = beam
+                    .tokens
+                    .iter()
+                    .map(|&t| self.vocab.decode[&t].as_str())
+                    .collect();
+                (words.join(" "), score(beam))
+            })
+            .collect()
+    }
+
+    /// Contrastive search: at each step pick, among the `top_k` most probable tokens, the
+    /// one maximizing `(1 - alpha) * probability - alpha * max_similarity`, where
+    /// `max_similarity` is the highest token-embedding cosine similarity to any token
+    /// already in the sequence.
+    ///
+    /// `alpha = 0` is greedy decoding; larger values penalize repetition. Models without
+    /// an `Embeddings` layer get no penalty. Stops after `max_new_tokens`, at `</s>`, or
+    /// at the context limit. `self` is left untouched.
+    pub fn generate_contrastive(
+        &self,
+        input: &str,
+        alpha: f32,
+        top_k: usize,
+        max_new_tokens: usize,
+    ) -> String {
+        let mut tokens = self.tokenize_with_stats(input).0;
+        let mut output_tokens: Vec<usize> = Vec::new();
+        if tokens.is_empty() {
+            return String::new();
+        }
+        let eos = self.vocab.encode("</s>");
+        let embeddings = self.token_embeddings();
+
+        let mut model = self.clone();
+        while output_tokens.len() < max_new_tokens && tokens.len() < MAX_SEQ_LEN {
+            let logits = model.forward_tokens(&tokens);
+            let last = logits
+                .row(logits.nrows() - 1)
+                .to_owned()
+                .insert_axis(Axis(0));
+            let probs = Self::softmax(&last);
+
+            let mut candidates: Vec<(usize, f32)> =
+                probs.row(0).iter().copied().enumerate().collect();
+            candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+            candidates.truncate(top_k.max(1));
+
+            let degeneration = |candidate: usize| {
+                embeddings.map_or(0.0, |embeddings| {
+                    let row = embeddings.row(candidate);
+                    tokens
+                        .iter()
+                        .map(|&prior| cosine_similarity(row, embeddings.row(prior)))
+                        .fold(f32::NEG_INFINITY, f32::max)
+                })
+            };
+            let mut best = candidates[0].0;
+            let mut best_score = f32::NEG_INFINITY;
+            for &(candidate, prob) in &candidates {
+                let score = (1.0 - alpha) * prob - alpha * degeneration(candidate);
+                if score > best_score {
+                    best = candidate;
+                    best_score = score;
+                }
+            }
+
+            output_tokens.push(best);
+            tokens.push(best);
+            if Some(best) == eos {
+                break;
+            }
+        }
+
+        output_tokens
+            .iter()
+            .map(|t| self.vocab.decode[t].as_str())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// The token embedding matrix of the first `Embeddings` layer, if the network has one.
+    fn token_embeddings(&self) -> Option<&Array2<f32>> {
+        self.network
+            .iter()
+            .find(|layer| layer.layer_type() == "Embeddings")
+            .and_then(|layer| layer.weights().first().copied())
+    }
+
+    /// Decode a continuation of `text`, returning the generated token ids and the
+    /// log-probability of each, and recording a [`TokenExplanation`] per token in
+    /// `explanations` if given.
+    fn forward(
+        &mut self,
+        text: &str,
+        config: &GenerationConfig,
+        mut explanations: Option<&mut Vec<TokenExplanation>>,
+    ) -> Result<(Vec<usize>, Vec<f32>)> {
+        // Tokenize the input text
+        let mut tokenized = self.tokenize_with_stats(text).0;
+        let mut output_tokens: Vec<usize> = Vec::new();
+        let mut logprobs: Vec<f32> = Vec::new();
+
+        // Safety check: ensure we have at least one token
+        if tokenized.is_empty() {
+            return Ok((output_tokens, logprobs));
+        }
+
+        // Never let the sequence outgrow the positional embedding table
+        let max_new_tokens = match config.context_overflow {
+            ContextOverflow::ClampNewTokens => config
+                .max_new_tokens
+                .min(MAX_SEQ_LEN.saturating_sub(tokenized.len())),
+            ContextOverflow::TruncateOldest => {
+                let max_new_tokens = config.max_new_tokens.min(MAX_SEQ_LEN - 1);
+                let keep = MAX_SEQ_LEN - max_new_tokens;
+                if tokenized.len() > keep {
+                    tracing::debug!("Dropping {} oldest prompt tokens", tokenized.len() - keep);
+                    tokenized.drain(..tokenized.len() - keep);
+                }
+                max_new_tokens
+            }
+            ContextOverflow::Error => {
+                if tokenized.len() + config.max_new_tokens > MAX_SEQ_LEN {
+                    return Err(LlmError::ValidationError(format!(
+                        "Prompt of {} tokens plus {} new tokens exceeds the context window of {} tokens",
+                        tokenized.len(),
+                        config.max_new_tokens,
+                        MAX_SEQ_LEN
+                    )));
+                }
+                config.max_new_tokens
+            }
+        };
+
+        let mut rng = (config.sampling.is_some() || config.temperature_schedule.is_some())
+            .then(crate::rng::init_rng);
+        let started = std::time::Instant::now();
+        let stop_tokens = config
+            .user_prefix
+            .as_deref()
+            .map(|prefix| self.tokenize(prefix))
+            .unwrap_or_default();
+
+        for step in 0..max_new_tokens {
+            if config
+                .timeout
+                .is_some_and(|timeout| started.elapsed() >= timeout)
+            {
+                tracing::debug!("Generation timed out after {} tokens", output_tokens.len());
+                break;
+            }
+            // let tokenized_clone = tokenized.clone();
+
+            // Check if we're approaching the maximum sequence length
+            if output_tokens.len() >= MAX_SEQ_LEN - 1 {
+                break;
+            }
+
+            let logits = self.forward_tokens(&tokenized);
+
+            // Safety check: ensure we have at least one token
+            if logits.shape()[0] == 0 {
+                break;
+            }
+
+            let mut last_logit = logits
+                .row(logits.shape()[0] - 1)
+                .to_owned()
+                .insert_axis(Axis(0));
+            for (&token, &bias) in &config.logit_bias {
+                if let Some(logit) = last_logit.get_mut([0, token]) {
+                    *logit += bias;
+                }
+            }
+
+            // Softmax - convert activations of each token to a probability distribution over the
+            // vocabulary
+            let probs = Self::softmax(&last_logit); // 1 x vocab_size
+
+            let next_token = match (config.sampling_at(step), &mut rng) {
+                (Some(sampling), Some(rng)) => {
+                    sampling.sample(last_logit.row(0).as_slice().unwrap(), rng)
+                }
+                // Greedy Decode - Choose the highest probability token
+                _ => Self::greedy_decode(&probs)[0],
+            };
+
+            // Stop rather than emit a token the model is not confident about
+            if let Some(min_prob) = config.min_token_prob {
+                if probs[[0, next_token]] < min_prob {
+                    break;
+                }
+            }
+
+            output_tokens.push(next_token);
+            logprobs.push(Self::log_softmax(&last_logit)[[0, next_token]]);
+            tokenized.push(next_token);
+            if let Some(explanations) = explanations.as_deref_mut() {
+                explanations.push(self.explain_step(&probs, next_token));
+            }
+
+            // The model started a user turn: drop the prefix and end the assistant's turn
+            if !stop_tokens.is_empty() && output_tokens.ends_with(&stop_tokens) {
+                output_tokens.truncate(output_tokens.len() - stop_tokens.len());
+                logprobs.truncate(output_tokens.len());
+                if let Some(explanations) = explanations.as_deref_mut() {
+                    explanations.truncate(output_tokens.len());
+                }
+                break;
+            }
+
+            if next_token == self.vocab.encode("</s>").unwrap() {
+                break;
+            }
+        }
+
+        Ok((output_tokens, logprobs))
+    }
+
+    /// Describe choosing `token` from the `1 x vocab_size` distribution `probs`.
+    fn explain_step(&self, probs: &Array2<f32>, token: usize) -> TokenExplanation {
+        let mut ranked: Vec<(usize, f32)> = probs.row(0).iter().copied().enumerate().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.truncate(EXPLANATION_ALTERNATIVES);
+
+        TokenExplanation {
+            token: self.vocab.decode[&token].clone(),
+            probability: probs[[0, token]],
+            alternatives: ranked
+                .into_iter()
+                .map(|(id, prob)| (self.vocab.decode[&id].clone(), prob))
+                .collect(),
+        }
+    }
+
+    /// Run the network over a token sequence and return the logits for every position.
+    fn forward_tokens(&mut self, token_ids: &[usize]) -> Array2<f32> {
+        self.update_padding_mask(token_ids);
+        self.update_document_mask(token_ids);
+        let mut input: Array2<f32> = Array2::zeros((1, token_ids.len()));
+        input
+            .row_mut(0)
+            .assign(&token_ids.iter().map(|&x| x as f32).collect::<Array1<f32>>());
+
+        for layer in &mut self.network {
+            input = layer.forward(&input);
+        }
+
+        input
+    }
+
+    /// Mask the `<pad>` positions of `token_ids` in every attention layer, or clear the
+    /// mask when the sequence has no padding.
+    fn update_padding_mask(&mut self, token_ids: &[usize]) {
+        let mask = self
+            .vocab
+            .encode("<pad>")
+            .map(|pad_id| token_ids.iter().map(|&id| id == pad_id).collect::<Vec<_>>())
+            .filter(|mask| mask.contains(&true));
+        for layer in &mut self.network {
+            layer.set_padding_mask(mask.as_deref());
+        }
+    }
+
+    /// With [`LLM::document_masking`] enabled, confine attention to the `</s>`-separated
+    /// documents of `token_ids`; otherwise clear the mask.
+    fn update_document_mask(&mut self, token_ids: &[usize]) {
+        let document_ids = self
+            .vocab
+            .encode("</s>")
+            .filter(|_| self.document_masking)
+            .filter(|eos_id| {
+                token_ids
+                    .iter()
+                    .take(token_ids.len().saturating_sub(1))
+                    .any(|id| id == eos_id)
+            })
+            .map(|eos_id| {
+                let mut document = 0;
+                token_ids
+                    .iter()
+                    .map(|&id| {
+                        let current = document;
+                        // `</s>` closes its own document
+                        if id == eos_id {
+                            document += 1;
+                        }
+                        current
+                    })
+                    .collect::<Vec<_>>()
+            });
+        for layer in &mut self.network {
+            layer.set_document_mask(document_ids.as_deref());
+        }
+    }
+
+    /// Total log-probability the model assigns to `text`, summed over every next-token
+    /// prediction. Higher (less negative) means more likely; texts shorter than two
+    /// tokens score 0. `self` is left untouched.
+    pub fn sequence_logprob(&self, text: &str) -> f32 {
+        -self.per_position_loss(text).iter().sum::<f32>()
+    }
+
+    /// Cross-entropy of each next-token prediction in `input`: entry `i` is the loss of
+    /// predicting token `i + 1` from the tokens before it. Shows where in a sequence the
+    /// model struggles; texts shorter than two tokens yield an empty vector. `self` is
+    /// left untouched.
+    pub fn per_position_loss(&self, input: &str) -> Vec<f32> {
+        let tokens = self.tokenize(input);
+        if tokens.len() < 2 {
+            return Vec::new();
+        }
+
+        let mut model = self.clone();
+        let log_probs = Self::log_softmax(&model.forward_tokens(&tokens[..tokens.len() - 1]));
+        tokens[1..]
+            .iter()
+            .enumerate()
+            .map(|(pos, &target)| -log_probs[[pos, target]])
+            .collect()
+    }
+
+    /// Time one training step layer by layer.
+    ///
+    /// Runs a forward and backward pass over `input` (using the causal objective) and
+    /// returns, for each layer in order, its type with its forward and backward durations.
+    /// Timing only happens here, so regular training pays no overhead.
+    pub fn profile_step(
+        &mut self,
+        input: &str,
+        lr: f32,
+    ) -> Vec<(String, std::time::Duration, std::time::Duration)> {
+        use std::time::{Duration, Instant};
+
+        let tokens = self.tokenize(input);
+        if tokens.len() < 2 {
+            return Vec::new();
+        }
+        let input_ids = &tokens[..tokens.len() - 1];
+        let target_ids = &tokens[1..];
+
+        self.update_padding_mask(input_ids);
+        self.update_document_mask(input_ids);
+        let mut forward_times = Vec::with_capacity(self.network.len());
+        let mut activations: Array2<f32> = Array2::zeros((1, input_ids.len()));
+        activations
+            .row_mut(0)
+            .assign(&input_ids.iter().map(|&x| x as f32).collect::<Array1<f32>>());
+        for layer in &mut self.network {
+            let started = Instant::now();
+            activations = layer.forward(&activations);
+            forward_times.push(started.elapsed());
+        }
+
+        let probs = Self::softmax(&activations);
+        let grads_output = self.loss_fn.gradient(&probs, target_ids);
+
+        let mut backward_times = vec![Duration::ZERO; self.network.len()];
+        self.backpropagate(
+            grads_output,
+            lr,
+            Some(&mut |idx, elapsed| backward_times[idx] = elapsed),
+        );
+
+        self.network
+            .iter()
+            .zip(forward_times.into_iter().zip(backward_times))
+            .map(|(layer, (forward, backward))| (layer.layer_type().to_string(), forward, backward))
+            .collect()
+    }
+
+    /// Score next-token prediction on held-out `texts` without updating the model.
+    ///
+    /// Loss and accuracy are averaged over every predicted token, so longer texts weigh
+    /// more. Texts shorter than two tokens are skipped; if nothing is scored the report
+    /// has zero loss and accuracy and a perplexity of 1.
+    pub fn evaluate(&mut self, texts: &[String]) -> EvaluationReport {
+        let mut total_loss = 0.0;
+        let mut correct = 0;
+        let mut tokens = 0;
+
+        for text in texts {
+            let ids = self.tokenize(text);
+            if ids.len() < 2 {
+                continue;
+            }
+
+            let target_ids = &ids[1..];
+            let log_probs = Self::log_softmax(&self.forward_tokens(&ids[..ids.len() - 1]));
+            for (pos, &target) in target_ids.iter().enumerate() {
+                total_loss -= log_probs[[pos, target]];
+            }
+            correct += Self::greedy_decode(&log_probs)
+                .iter()
+                .zip(target_ids)
+                .filter(|(predicted, target)| predicted == target)
+                .count();
+            tokens += target_ids.len();
+        }
+
+        let loss = total_loss / tokens.max(1) as f32;
+        EvaluationReport {
+            loss,
+            perplexity: loss.exp(),
+            accuracy: correct as f32 / tokens.max(1) as f32,
+            tokens,
+        }
+    }
+
+    /// Build a reliability diagram over next-token predictions.
+    ///
+    /// Every predicted position is assigned to one of `bins` equal-width confidence bins
+    /// over [0, 1] based on the probability of its top token. Each returned entry is
+    /// `(mean predicted probability, empirical accuracy, count)`, in bin order; empty bins
+    /// report zeros. `self` is left untouched.
+    pub fn calibration_report(&self, texts: &[String], bins: usize) -> Vec<(f32, f32, usize)> {
+        let bins = bins.max(1);
+        let mut model = self.clone();
+        let mut confidence_sums = vec![0.0f32; bins];
+        let mut correct_counts = vec![0usize; bins];
+        let mut counts = vec![0usize; bins];
+
+        for text in texts {
+            let tokens = self.tokenize(text);
+            if tokens.len() < 2 {
+                continue;
+            }
+
+            let input_ids = &tokens[..tokens.len() - 1];
+            let target_ids = &tokens[1..];
+            let probs = Self::softmax(&model.forward_tokens(input_ids));
+            let predictions = Self::greedy_decode(&probs);
+
+            for (row_idx, (&predicted, &target)) in
+                predictions.iter().zip(target_ids.iter()).enumerate()
+            {
+                let confidence = probs[[row_idx, predicted]];
+                let bin = ((confidence * bins as f32) as usize).min(bins - 1);
+                confidence_sums[bin] += confidence;
+                counts[bin] += 1;
+                if predicted == target {
+                    correct_counts[bin] += 1;
+                }
+            }
+        }
+
+        (0..bins)
+            .map(|bin| {
+                if counts[bin] == 0 {
+                    (0.0, 0.0, 0)
+                } else {
+                    let count = counts[bin] as f32;
+                    (
+                        confidence_sums[bin] / count,
+                        correct_counts[bin] as f32 / count,
+                        counts[bin],
+                    )
+                }
+            })
+            .collect()
+    }
+
+    pub fn train(&mut self, data: Vec<&str>, epochs: usize, lr: f32) -> TrainingReport {
+        self.train_with_progress(data, epochs, lr, &mut PrintProgress)
+    }
+
+    /// [`LLM::train`], reporting each epoch to `progress`.
+    pub fn train_with_progress(
+        &mut self,
+        data: Vec<&str>,
+        epochs: usize,
+        lr: f32,
+        progress: &mut dyn ProgressReporter,
+    ) -> TrainingReport {
+        self.train_with_visualizer(data, epochs, lr, progress, None)
+    }
+
+    pub fn train_with_visualizer(
+        &mut self,
+        data: Vec<&str>,
+        epochs: usize,
+        lr: f32,
+        progress: &mut dyn ProgressReporter,
+        mut visualizer: Option<&mut crate::visualization::TrainingVisualizer>,
+    ) -> TrainingReport {
+        let mut report = TrainingReport::default();
+        let tokenized_data = data
+            .iter()
+            .map(|input| self.tokenize(input))
+            .collect::<Vec<Vec<usize>>>();
+
+        for epoch in 0..epochs {
+            progress.on_epoch_start(epoch + 1);
+            let stats = self.run_epoch(
+                &mut DatasetCursor::new(epoch, 0),
+                &data,
+                &tokenized_data,
+                lr,
+                &mut report,
+                |_| false,
+            );
+
+            let avg_loss = stats.loss;
+            progress.on_epoch_end(epoch + 1, avg_loss);
+            if let Some(vis) = &mut visualizer {
+                vis.record_loss(avg_loss);
+                vis.record_accuracy(stats.accuracy);
+                vis.record_gradient(stats.grad_norm);
+                vis.set_epoch(epoch + 1);
+            }
+        }
+        progress.finish();
+
+        report
+    }
+
+    /// Objective [`LLM::train_step`] trains with.
+    pub fn objective(&self) -> TrainingObjective {
+        self.objective
+    }
+
+    /// Train with `objective` from now on.
+    ///
+    /// # Errors
+    /// Returns `VocabularyError` for [`TrainingObjective::MaskedLM`] if the vocabulary
+    /// has no `<mask>` token, and `ConfigError` if its `mask_prob` is outside `(0, 1]`.
+    pub fn set_objective(&mut self, objective: TrainingObjective) -> Result<()> {
+        if let TrainingObjective::MaskedLM { mask_prob } = objective {
+            if self.vocab.encode("<mask>").is_none() {
+                return Err(LlmError::vocabulary(
+                    "Masked LM objective requires a <mask> token in the vocabulary",
+                ));
+            }
+            if !(mask_prob > 0.0 && mask_prob <= 1.0) {
+                return Err(LlmError::config(format!(
+                    "mask_prob must be in (0, 1], got {}",
+                    mask_prob
+                )));
+            }
+        }
+        self.objective = objective;
+        Ok(())
+    }
+
+    /// Train in a single phase on both splits of `dataset`, drawing a fresh
+    /// [`Dataset::mixed_epoch`] at `mix_ratio` for every epoch.
+    pub fn train_blended(
+        &mut self,
+        dataset: &Dataset,
+        mix_ratio: f32,
+        epochs: usize,
+        lr: f32,
+        progress: &mut dyn ProgressReporter,
+    ) -> TrainingReport {
+        let mut report = TrainingReport::default();
+        let mut rng = crate::rng::init_rng();
+
+        for epoch in 0..epochs {
+            progress.on_epoch_start(epoch + 1);
+            let data = dataset.mixed_epoch(mix_ratio, &mut rng);
+            let tokenized_data: Vec<Vec<usize>> = data
+                .iter()
+                .map(|input| self.tokenize_cached(input))
+                .collect();
+
+            let stats = self.run_epoch(
+                &mut DatasetCursor::new(epoch, 0),
+                &data,
+                &tokenized_data,
+                lr,
+                &mut report,
+                |_| false,
+            );
+            progress.on_epoch_end(epoch + 1, stats.loss);
+        }
+        progress.finish();
+
+        report
+    }
+
+    /// [`LLM::train`], calling `on_epoch` at the start of every epoch for custom logging,
+    /// learning-rate scheduling through [`EpochContext::lr`], curriculum changes, or
+    /// early stopping through [`EpochContext::stop`].
+    pub fn train_with_callback(
+        &mut self,
+        data: Vec<&str>,
+        epochs: usize,
+        mut lr: f32,
+        mut on_epoch: impl FnMut(EpochContext),
+    ) -> TrainingReport {
+        let mut report = TrainingReport::default();
+        let tokenized_data = data
+            .iter()
+            .map(|input| self.tokenize(input))
+            .collect::<Vec<Vec<usize>>>();
+
+        for epoch in 0..epochs {
+            let mut stop = false;
+            on_epoch(EpochContext {
+                epoch,
+                avg_loss: report.epochs.last().map(|stats| stats.loss),
+                stop: &mut stop,
+                lr: &mut lr,
+            });
+            if stop {
+                tracing::info!(epoch = epoch as u64, "Training stopped by callback");
+                break;
+            }
+
+            self.run_epoch(
+                &mut DatasetCursor::new(epoch, 0),
+                &data,
+                &tokenized_data,
+                lr,
+                &mut report,
+                |_| false,
+            );
+        }
+
+        report
+    }
+
+    /// [`LLM::train`], sending [`EpochMetrics`] on `tx` after every epoch so another
+    /// thread (e.g. a GUI) can follow progress. Training carries on if the receiver is
+    /// dropped.
+    pub fn train_with_channel(
+        &mut self,
+        data: Vec<&str>,
+        epochs: usize,
+        lr: f32,
+        tx: Sender<EpochMetrics>,
+    ) -> TrainingReport {
+        let mut report = TrainingReport::default();
+        let tokenized_data = data
+            .iter()
+            .map(|input| self.tokenize(input))
+            .collect::<Vec<Vec<usize>>>();
+        let mut connected = true;
+
+        for epoch in 0..epochs {
+            let stats = self.run_epoch(
+                &mut DatasetCursor::new(epoch, 0),
+                &data,
+                &tokenized_data,
+                lr,
+                &mut report,
+                |_| false,
+            );
+            if connected && tx.send(stats.into()).is_err() {
+                tracing::debug!("Metrics receiver dropped; training continues");
+                connected = false;
+            }
+        }
+
+        report
+    }
+
+    /// Train from `cursor` until `epochs` complete or `should_stop` returns true.
+    ///
+    /// The cursor counts positions in each epoch's [`LLM::epoch_order`] and is advanced
+    /// after every sample, and `should_stop` is checked after each advance, so storing
+    /// the cursor in a [`crate::Checkpoint`] and passing it back in resumes at the next
+    /// unprocessed sample of the same visiting order. That order is only reproduced
+    /// exactly if the curriculum is deterministic, i.e. none,
+    /// [`CurriculumStrategy::Shuffle`], or the sorted epochs of
+    /// [`CurriculumStrategy::ByLength`]. An epoch resumed part-way reports statistics
+    /// over the samples trained in this call only. A cursor past the end of `data`
+    /// (e.g. saved against a larger dataset) moves to the start of the next epoch with a
+    /// warning.
+    pub fn train_resumable(
+        &mut self,
+        data: Vec<&str>,
+        epochs: usize,
+        lr: f32,
+        cursor: &mut DatasetCursor,
+        mut should_stop: impl FnMut(&DatasetCursor) -> bool,
+    ) -> TrainingReport {
+        let mut report = TrainingReport::default();
+        let tokenized_data = data
+            .iter()
+            .map(|input| self.tokenize(input))
+            .collect::<Vec<Vec<usize>>>();
+        if tokenized_data.is_empty() {
+            return report;
+        }
+        if cursor.sample_index >= tokenized_data.len() {
+            tracing::warn!(
+                epoch = cursor.epoch as u64,
+                sample_index = cursor.sample_index as u64,
+                num_samples = tokenized_data.len() as u64,
+                "Resume cursor is past the end of the dataset; starting the next epoch"
+            );
+            *cursor = DatasetCursor::new(cursor.epoch + 1, 0);
+        }
 
-use crate::{
-    output_projection::OutputProjection, transformer::TransformerBlock, Embeddings, Vocab,
-    EMBEDDING_DIM, HIDDEN_DIM, MAX_SEQ_LEN,
-};
-pub trait Layer {
-    fn layer_type(&self) -> &str;
+        while cursor.epoch < epochs {
+            let mut stopped = false;
+            self.run_epoch(cursor, &data, &tokenized_data, lr, &mut report, |cursor| {
+                stopped = should_stop(cursor);
+                stopped
+            });
+            if stopped {
+                tracing::info!(
+                    epoch = cursor.epoch as u64,
+                    sample_index = cursor.sample_index as u64,
+                    "Training stopped"
+                );
+                break;
+            }
+        }
 
-    fn forward(&mut self, input: &Array2<f32>) -> Array2<f32>;
+        report
+    }
 
-    fn backward(&mut self, grads: &Array2<f32>, lr: f32) -> Array2<f32>;
+    /// Train epoch `cursor.epoch` over `rows`, the tokenized `data`, starting at
+    /// position `cursor.sample_index` of its [`LLM::epoch_order`]. Every training loop
+    /// runs its epochs through here.
+    ///
+    /// The cursor is advanced after every step and `should_stop` checked after each
+    /// advance. Returns statistics over the steps trained here; the epoch is only
+    /// recorded in `report` if it ran to the end, and stopping early leaves the cursor
+    /// inside it.
+    pub(crate) fn run_epoch(
+        &mut self,
+        cursor: &mut DatasetCursor,
+        data: &[&str],
+        rows: &[Vec<usize>],
+        lr: f32,
+        report: &mut TrainingReport,
+        mut should_stop: impl FnMut(&DatasetCursor) -> bool,
+    ) -> EpochStats {
+        let epoch = cursor.epoch;
+        self.apply_embedding_warmup(epoch);
+        let order = self.epoch_order(epoch, rows);
+        let mut trained = Vec::new();
+        let mut steps = Vec::new();
+
+        if rows.is_empty() {
+            *cursor = DatasetCursor::new(epoch + 1, 0);
+        }
+        while cursor.epoch == epoch {
+            let index = order[cursor.sample_index];
+            trained.push(index);
+            steps.push(self.train_step(&rows[index], lr));
+            cursor.advance(rows.len());
+            if should_stop(cursor) {
+                break;
+            }
+        }
 
-    fn parameters(&self) -> usize;
-}
+        let stats = EpochStats::from_steps(epoch + 1, lr, &steps);
+        if cursor.epoch > epoch {
+            stats.log();
+            let losses = Self::ordered_losses(&steps, &trained);
+            report.record_epoch(stats, data, &losses, self.track_examples);
+            report.samples.extend(self.sample_completions(epoch + 1));
+        }
+        stats
+    }
 
-#[allow(clippy::upper_case_acronyms)]
-pub struct LLM {
-    pub vocab: Vocab,
-    pub network: Vec<Box<dyn Layer>>,
-}
+    /// Smoke-test the model by running a forward pass over a few vocabulary tokens and
+    /// checking that every logit is finite, to catch broken initialization before
+    /// training or decoding. `self` is left untouched.
+    ///
+    /// # Errors
+    /// Returns `TrainingError` if the vocabulary is empty or any logit is NaN or
+    /// infinite.
+    pub fn self_check(&self) -> Result<()> {
+        let tokens: Vec<usize> = (0..self.vocab.size().min(4)).collect();
+        if tokens.is_empty() {
+            return Err(LlmError::TrainingError(
+                "Self-check failed: the vocabulary is empty".to_string(),
+            ));
+        }
 
-impl Default for LLM {
-    fn default() -> Self {
-        let transformer_block = TransformerBlock::new(EMBEDDING_DIM, HIDDEN_DIM);
-        let output_projection = OutputProjection::new(EMBEDDING_DIM, Vocab::default_words().len());
-        Self {
-            vocab: Vocab::default(),
-            network: vec![
-                Box::new(Embeddings::default()),
-                Box::new(transformer_block),
-                Box::new(output_projection),
-            ],
+        let logits = self.clone().forward_tokens(&tokens);
+        let non_finite = logits.iter().filter(|x| !x.is_finite()).count();
+        if non_finite > 0 {
+            return Err(LlmError::TrainingError(format!(
+                "Self-check failed: {} of {} logits are not finite",
+                non_finite,
+                logits.len()
+            )));
         }
+        Ok(())
     }
-}
 
-impl LLM {
-    pub fn new(vocab: Vocab, network: Vec<Box<dyn Layer>>) -> Self {
-        Self { vocab, network }
-    }
-}
+    /// Activation statistics of every feed-forward layer over all token positions of
+    /// `inputs`, in network order, for spotting dead and saturated hidden units.
+    ///
+    /// Inputs without known tokens are skipped. `self` is left untouched.
+    pub fn ffn_activation_stats(&self, inputs: &[&str]) -> Vec<NeuronStats> {
+        let mut model = self.clone();
+        // Per feed-forward layer: (layer index, nonzero count per unit, positions seen)
+        let mut counts: Vec<(usize, Vec<usize>, usize)> = Vec::new();
+
+        for input in inputs {
+            let tokens = model.tokenize(input);
+            if tokens.is_empty() {
+                continue;
+            }
+            model.update_padding_mask(&tokens);
+            model.update_document_mask(&tokens);
+            let mut hidden = Self::token_row(&tokens);
+
+            let mut ffn = 0;
+            for (index, layer) in model.network.iter_mut().enumerate() {
+                if let Some(activations) = layer.hidden_activations(&hidden) {
+                    if counts.len() == ffn {
+                        counts.push((index, vec![0; activations.ncols()], 0));
+                    }
+                    let (_, nonzero, positions) = &mut counts[ffn];
+                    for row in activations.rows() {
+                        for (count, &value) in nonzero.iter_mut().zip(row) {
+                            if value != 0.0 {
+                                *count += 1;
+                            }
+                        }
+                    }
+                    *positions += activations.nrows();
+                    ffn += 1;
+                }
+                hidden = layer.forward(&hidden);
+            }
+        }
 
-impl LLM {
-    pub fn network_description(&self) -> String {
-        self.network
-            .iter()
-            .map(|layer| layer.layer_type())
-            .collect::<Vec<&str>>()
-            .join(", ")
+        counts
+            .into_iter()
+            .map(|(layer, nonzero, positions)| NeuronStats {
+                layer,
+                activation_fraction: nonzero
+                    .into_iter()
+                    .map(|count| count as f32 / positions.max(1) as f32)
+                    .collect(),
+            })
+            .collect()
     }
 
-    pub fn total_parameters(&self) -> usize {
-        // Sum the parameters across all layers in the network
-        self.network
-            .iter()
-            .map(|layer| layer.parameters())
-            .sum::<usize>()
+    /// Attention weights of every attention layer for `input`, in network order.
+    ///
+    /// Returns nothing when the input has no known tokens. `self` is left untouched.
+    pub fn attention_maps(&self, input: &str) -> Vec<Array2<f32>> {
+        let tokens = self.tokenize(input);
+        if tokens.is_empty() {
+            return Vec::new();
+        }
+
+        let mut model = self.clone();
+        model.update_padding_mask(&tokens);
+        model.update_document_mask(&tokens);
+        let mut hidden = Self::token_row(&tokens);
+
+        let mut maps = Vec::new();
+        for layer in &mut model.network {
+            maps.extend(layer.attention_map(&hidden));
+            hidden = layer.forward(&hidden);
+        }
+        maps
     }
 
-    pub fn predict(&mut self, text: &str) -> String {
-        let output_tokens = self.forward(text);
+    /// Output of layer `layer_index` (0 is the embeddings) in a forward pass over
+    /// `input`, one row per token. `self` is left untouched.
+    ///
+    /// # Errors
+    /// Returns `ValidationError` if `layer_index` is past the last layer or the input has
+    /// no known tokens.
+    pub fn forward_capture(&self, input: &str, layer_index: usize) -> Result<Array2<f32>> {
+        if layer_index >= self.network.len() {
+            return Err(LlmError::ValidationError(format!(
+                "layer index {} is out of range for a network of {} layers",
+                layer_index,
+                self.network.len()
+            )));
+        }
+        let tokens = self.tokenize(input);
+        if tokens.is_empty() {
+            return Err(LlmError::ValidationError(
+                "input has no tokens in the vocabulary".to_string(),
+            ));
+        }
 
-        // Handle empty output
-        if output_tokens.is_empty() {
-            return String::new();
+        let mut model = self.clone();
+        model.update_padding_mask(&tokens);
+        model.update_document_mask(&tokens);
+        let mut hidden = Self::token_row(&tokens);
+        for layer in &mut model.network[..=layer_index] {
+            hidden = layer.forward(&hidden);
         }
+        Ok(hidden)
+    }
 
-        // Convert token_ids to strings
-        let token_strs = output_tokens
+    /// Token ids as the single-row input the embeddings layer expects.
+    fn token_row(tokens: &[usize]) -> Array2<f32> {
+        Array2::from_shape_vec(
+            (1, tokens.len()),
+            tokens.iter().map(|&x| x as f32).collect(),
+        )
+        .expect("one row of token ids")
+    }
+
+    /// Mean entropy (in nats) of the attention distributions of each attention layer for
+    /// `input`; values near zero mean every query attends to a single position.
+    pub fn attention_entropy(&self, input: &str) -> Vec<f32> {
+        self.attention_maps(input)
             .iter()
-            .map(|t| self.vocab.decode[t].clone())
-            .collect::<Vec<String>>();
+            .map(SelfAttention::mean_entropy)
+            .collect()
+    }
 
-        token_strs.join(" ")
+    /// Freeze (`trainable = false`) or unfreeze the parameters of layer `index`.
+    ///
+    /// A frozen layer still backpropagates gradients to the layers before it but trains
+    /// with a learning rate of 0, so its parameters stay exactly as they are.
+    ///
+    /// # Errors
+    /// Returns `ValidationError` if `index` is past the last layer.
+    pub fn set_layer_trainable(&mut self, index: usize, trainable: bool) -> Result<()> {
+        if index >= self.network.len() {
+            return Err(LlmError::ValidationError(format!(
+                "layer index {} is out of range for a network of {} layers",
+                index,
+                self.network.len()
+            )));
+        }
+        if trainable {
+            self.frozen_layers.remove(&index);
+        } else {
+            self.frozen_layers.insert(index);
+        }
+        Ok(())
     }
 
-    fn forward(&mut self, text: &str) -> Vec<usize> {
-        // Tokenize the input text
-        let mut tokenized = self.tokenize(text);
-        let mut output_tokens: Vec<usize> = Vec::new();
+    /// Whether layer `index` is updated during training.
+    pub fn is_layer_trainable(&self, index: usize) -> bool {
+        !self.frozen_layers.contains(&index)
+    }
 
-        // Safety check: ensure we have at least one token
-        if tokenized.is_empty() {
-            return output_tokens;
+    /// Freeze the embeddings during the first [`LLM::freeze_embeddings_epochs`] epochs
+    /// and unfreeze them at the epoch after; called at the start of every epoch.
+    fn apply_embedding_warmup(&mut self, epoch: usize) {
+        if self.freeze_embeddings_epochs == 0 || epoch > self.freeze_embeddings_epochs {
+            return;
         }
+        let trainable = epoch >= self.freeze_embeddings_epochs;
+        for index in 0..self.network.len() {
+            if self.network[index].layer_type() == "Embeddings" {
+                self.set_layer_trainable(index, trainable)
+                    .expect("index is within the network");
+            }
+        }
+    }
 
-        let input_len = tokenized.len();
+    /// Greedily complete each of [`LLM::sample_prompts`] and log the results if epoch
+    /// `epoch` (1-based) is a multiple of [`LLM::sample_interval`]; called at the end of
+    /// every epoch.
+    fn sample_completions(&mut self, epoch: usize) -> Vec<GeneratedSample> {
+        if self.sample_interval == 0 || !epoch.is_multiple_of(self.sample_interval) {
+            return Vec::new();
+        }
+        let prompts = self.sample_prompts.clone();
+        prompts
+            .into_iter()
+            .map(|prompt| {
+                let completion = self.generate(&prompt, &GenerationConfig::default());
+                tracing::info!(epoch = epoch as u64, %prompt, %completion, "Training sample");
+                GeneratedSample {
+                    epoch,
+                    prompt,
+                    completion,
+                }
+            })
+            .collect()
+    }
 
-        // Prevent overflow if input_len >= MAX_SEQ_LEN
-        if input_len >= MAX_SEQ_LEN {
-            return output_tokens;
+    /// Indices of `rows` in the order epoch `epoch` (0-based) visits them, following
+    /// [`LLM::curriculum`].
+    pub fn epoch_order(&self, epoch: usize, rows: &[Vec<usize>]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..rows.len()).collect();
+        match self.curriculum {
+            Some(CurriculumStrategy::ByLength { epochs }) if epoch < epochs => {
+                order.sort_by_key(|&index| rows[index].len());
+            }
+            Some(CurriculumStrategy::ByLength { .. }) => {
+                order.shuffle(&mut crate::rng::init_rng());
+            }
+            Some(CurriculumStrategy::Shuffle { seed }) => {
+                let epoch_seed = crate::rng::splitmix64(seed) ^ epoch as u64;
+                order.shuffle(&mut StdRng::seed_from_u64(epoch_seed));
+            }
+            None => {}
         }
+        order
+    }
 
-        for _ in 0..(MAX_SEQ_LEN - input_len) {
-            // let tokenized_clone = tokenized.clone();
+    /// `(sample index, loss)` of each step that scored at least one position, where
+    /// step `i` trained sample `order[i]`.
+    fn ordered_losses(steps: &[StepStats], order: &[usize]) -> Vec<(usize, f32)> {
+        steps
+            .iter()
+            .zip(order)
+            .filter(|(step, _)| step.scored > 0)
+            .map(|(step, &index)| (index, step.loss))
+            .collect()
+    }
 
-            // Check if we're approaching the maximum sequence length
-            if output_tokens.len() >= MAX_SEQ_LEN - 1 {
-                break;
-            }
+    /// Learning-rate range test: train a copy of the model for `steps` steps while
+    /// raising the learning rate exponentially from `min_lr` to `max_lr`, cycling
+    /// through `data`.
+    ///
+    /// Returns the `(lr, loss)` curve; a good learning rate usually sits just before the
+    /// loss starts climbing. `self` is left untouched. Bounds that cannot be swept
+    /// upward on a log scale (`min_lr <= 0`, `max_lr < min_lr`, or non-finite values)
+    /// yield an empty curve with a warning.
+    pub fn lr_range_test(
+        &self,
+        data: &[&str],
+        min_lr: f32,
+        max_lr: f32,
+        steps: usize,
+    ) -> Vec<(f32, f32)> {
+        if !(min_lr > 0.0 && max_lr >= min_lr && max_lr.is_finite()) {
+            tracing::warn!(
+                "lr_range_test needs 0 < min_lr <= max_lr (got {} and {})",
+                min_lr,
+                max_lr
+            );
+            return Vec::new();
+        }
+
+        let mut model = self.clone();
+        let rows: Vec<Vec<usize>> = data
+            .iter()
+            .map(|text| model.tokenize(text))
+            .filter(|tokens| tokens.len() >= 2)
+            .collect();
+        if rows.is_empty() {
+            return Vec::new();
+        }
 
-            let token_input = Array2::from_shape_vec(
-                (1, tokenized.len()),
-                tokenized.iter().map(|&x| x as f32).collect(),
-            )
-            .unwrap();
-            let mut input = token_input;
+        let growth = (max_lr / min_lr).powf(1.0 / steps.saturating_sub(1).max(1) as f32);
+        (0..steps)
+            .map(|step| {
+                let lr = min_lr * growth.powi(step as i32);
+                let stats = model.train_step(&rows[step % rows.len()], lr);
+                (lr, stats.loss)
+            })
+            .collect()
+    }
 
-            for layer in &mut self.network {
-                input = layer.forward(&input);
+    /// Run a forward and backward pass over a single tokenized row using the configured
+    /// objective. Rows too short to score return empty statistics.
+    ///
+    /// # Panics
+    /// With [`TrainingObjective::MaskedLM`], if the vocabulary was replaced by one
+    /// without `<mask>` after [`LLM::set_objective`].
+    pub fn train_step(&mut self, training_row: &[usize], lr: f32) -> StepStats {
+        match self.objective {
+            TrainingObjective::CausalLM => self.causal_lm_step(training_row, lr),
+            TrainingObjective::MaskedLM { mask_prob } => {
+                self.masked_lm_step(training_row, mask_prob, lr)
             }
+        }
+    }
 
-            let logits = input;
+    fn causal_lm_step(&mut self, training_row: &[usize], lr: f32) -> StepStats {
+        if training_row.len() < 2 {
+            return StepStats::default();
+        }
 
-            // Safety check: ensure we have at least one token
-            if logits.shape()[0] == 0 {
-                break;
-            }
+        // 1. Slice input and targets
+        let mut input_ids = training_row[..training_row.len() - 1].to_vec(); // Exclude the last token
+        let target_ids = &training_row[1..]; // This is a vector. Each element is the index in the vocab.
 
-            let last_logit = logits
-                .row(logits.shape()[0] - 1)
-                .to_owned()
-                .insert_axis(Axis(0));
+        if self.teacher_forcing_ratio < 1.0 {
+            input_ids = self.scheduled_sampling_inputs(&input_ids, &mut crate::rng::init_rng());
+        }
 
-            // Softmax - convert activations of each token to a probability distribution over the
-            // vocabulary
-            let probs = Self::softmax(&last_logit); // 1 x vocab_size
+        // Forward pass
+        let logits = self.forward_tokens(&input_ids);
+        let probs = Self::softmax(&logits);
 
-            // Greedy Decode - Choose the highest probability token for each position
-            let tokens = Self::greedy_decode(&probs);
+        let loss = self.loss_fn.loss_from_logits(&logits, target_ids);
+        let correct = Self::greedy_decode(&probs)
+            .iter()
+            .zip(target_ids)
+            .filter(|(predicted, target)| predicted == target)
+            .count();
 
-            let next_token = tokens[tokens.len() - 1];
+        // Backward pass
+        let grads_output = self.loss_fn.gradient(&probs, target_ids); // this is d_L/d_output_projection
+        let grad_norm = Self::gradient_norm(&grads_output);
 
-            output_tokens.push(next_token);
-            tokenized.push(next_token);
+        self.backpropagate(grads_output, lr, None);
 
-            if next_token == self.vocab.encode("</s>").unwrap() {
-                break;
-            }
+        StepStats {
+            loss,
+            correct,
+            scored: target_ids.len(),
+            grad_norm,
         }
-
-        output_tokens
     }
 
-    pub fn train(&mut self, data: Vec<&str>, epochs: usize, lr: f32) {
-        self.train_with_progress(data, epochs, lr, None);
+    /// Replace each input after the first, with probability `1 - teacher_forcing_ratio`,
+    /// by the model's greedy prediction for that position from a ground-truth pass.
+    fn scheduled_sampling_inputs(&mut self, input_ids: &[usize], rng: &mut impl Rng) -> Vec<usize> {
+        let predictions = Self::greedy_decode(&self.forward_tokens(input_ids));
+        let ratio = f64::from(self.teacher_forcing_ratio.clamp(0.0, 1.0));
+        input_ids
+            .iter()
+            .enumerate()
+            .map(|(pos, &token)| {
+                if pos > 0 && !rng.random_bool(ratio) {
+                    predictions[pos - 1]
+                } else {
+                    token
+                }
+            })
+            .collect()
     }
 
-    pub fn train_with_progress(
-        &mut self,
-        data: Vec<&str>,
-        epochs: usize,
-        lr: f32,
-        progress: Option<&indicatif::ProgressBar>,
-    ) {
-        self.train_with_visualizer(data, epochs, lr, progress, None);
+    fn masked_lm_step(&mut self, training_row: &[usize], mask_prob: f32, lr: f32) -> StepStats {
+        let mask_id = self.vocab.encode("<mask>").expect(
+            "Masked LM objective requires a <mask> token in the vocabulary (checked by LLM::set_objective)",
+        );
+        if training_row.is_empty() {
+            return StepStats::default();
+        }
+
+        let (input_ids, targets) = Self::mask_tokens(
+            training_row,
+            mask_prob,
+            mask_id,
+            &mut crate::rng::init_rng(),
+        );
+
+        // Attention must see the whole sequence for both passes
+        let causal: Vec<Option<bool>> = self.network.iter().map(|layer| layer.causal()).collect();
+        self.set_causal(false);
+
+        let logits = self.forward_tokens(&input_ids);
+        let probs = Self::softmax(&logits);
+
+        // Score only the masked positions
+        let (masked_rows, masked_targets): (Vec<usize>, Vec<usize>) = targets
+            .iter()
+            .enumerate()
+            .filter_map(|(row_idx, target)| target.map(|t| (row_idx, t)))
+            .unzip();
+        let masked_probs = probs.select(Axis(0), &masked_rows);
+
+        let loss = self
+            .loss_fn
+            .loss_from_logits(&logits.select(Axis(0), &masked_rows), &masked_targets);
+        let correct = Self::greedy_decode(&masked_probs)
+            .iter()
+            .zip(&masked_targets)
+            .filter(|(predicted, target)| predicted == target)
+            .count();
+
+        let masked_grads = self.loss_fn.gradient(&masked_probs, &masked_targets);
+        let mut grads_output = Array2::zeros(probs.dim());
+        for (grad_row, &row_idx) in masked_grads.rows().into_iter().zip(&masked_rows) {
+            grads_output.row_mut(row_idx).assign(&grad_row);
+        }
+        let grad_norm = Self::gradient_norm(&grads_output);
+        self.backpropagate(grads_output, lr, None);
+
+        for (layer, causal) in self.network.iter_mut().zip(causal) {
+            if let Some(causal) = causal {
+                layer.set_causal(causal);
+            }
+        }
+
+        StepStats {
+            loss,
+            correct,
+            scored: masked_targets.len(),
+            grad_norm,
+        }
     }
 
-    pub fn train_with_visualizer(
-        &mut self,
-        data: Vec<&str>,
-        epochs: usize,
-        lr: f32,
-        progress: Option<&indicatif::ProgressBar>,
-        mut visualizer: Option<&mut crate::visualization::TrainingVisualizer>,
-    ) {
-        let tokenized_data = data
+    /// Replace tokens with `mask_id` with probability `mask_prob`.
+    ///
+    /// Returns the corrupted input and, per position, the original token if it was masked.
+    /// At least one position is always masked so every row contributes to the loss.
+    pub fn mask_tokens(
+        tokens: &[usize],
+        mask_prob: f32,
+        mask_id: usize,
+        rng: &mut impl Rng,
+    ) -> (Vec<usize>, Vec<Option<usize>>) {
+        let mut input_ids = tokens.to_vec();
+        let mut targets: Vec<Option<usize>> = tokens
             .iter()
-            .map(|input| self.tokenize(input))
-            .collect::<Vec<Vec<usize>>>();
+            .map(|&token| {
+                rng.random_bool(mask_prob.clamp(0.0, 1.0) as f64)
+                    .then_some(token)
+            })
+            .collect();
 
-        for epoch in 0..epochs {
-            let mut total_loss = 0.0;
-            for training_row in &tokenized_data {
-                if training_row.len() < 2 {
-                    continue;
-                }
+        if !tokens.is_empty() && targets.iter().all(Option::is_none) {
+            let idx = rng.random_range(0..tokens.len());
+            targets[idx] = Some(tokens[idx]);
+        }
 
-                // 1. Slice input and targets
-                let input_ids = &training_row[..training_row.len() - 1]; // Exclude the last token
-                let target_ids = &training_row[1..]; // This is a vector. Each element is the index in the vocab.
+        for (input, target) in input_ids.iter_mut().zip(&targets) {
+            if target.is_some() {
+                *input = mask_id;
+            }
+        }
 
-                // Forward pass
-                let mut input: Array2<f32> = Array2::zeros((1, input_ids.len()));
-                input
-                    .row_mut(0)
-                    .assign(&input_ids.iter().map(|&x| x as f32).collect::<Array1<f32>>());
+        (input_ids, targets)
+    }
 
-                for layer in &mut self.network {
-                    input = layer.forward(&input);
-                }
+    /// Add a word to the vocabulary and grow the embedding and output-projection matrices
+    /// with freshly-initialized rows for it. Returns the word's token ID.
+    pub fn expand_vocab(&mut self, word: &str) -> usize {
+        let id = self.vocab.add_word(word);
+        let vocab_size = self.vocab.size();
+        for layer in &mut self.network {
+            layer.resize_vocab(vocab_size);
+        }
+        id
+    }
+
+    /// Build the standard architecture described by `config`: embeddings,
+    /// `num_blocks` transformer blocks and an output projection sized to `vocab`.
+    ///
+    /// Layers are built in network order, so calling [`crate::rng::seed`] first makes the
+    /// initialization reproducible. The text normalizer, gradient clipping, example
+    /// tracking and document masking settings are taken from `config` as well.
+    ///
+    /// # Errors
+    /// Returns `ConfigError` if `embedding_dim` or `max_seq_len` differ from
+    /// [`EMBEDDING_DIM`] and [`MAX_SEQ_LEN`], which the embeddings and decoding are
+    /// compiled for.
+    pub fn from_config(config: &Config, vocab: Vocab) -> Result<LLM> {
+        let model = &config.model;
+        if model.embedding_dim != EMBEDDING_DIM || model.max_seq_len != MAX_SEQ_LEN {
+            return Err(LlmError::ConfigError(format!(
+                "embedding_dim {} and max_seq_len {} must be {} and {} in this build",
+                model.embedding_dim, model.max_seq_len, EMBEDDING_DIM, MAX_SEQ_LEN
+            )));
+        }
+        if model.num_heads != 1 {
+            return Err(LlmError::ConfigError(format!(
+                "num_heads {} is not supported: attention is single-head in this build",
+                model.num_heads
+            )));
+        }
 
-                let logits = input;
-                let probs = Self::softmax(&logits);
+        let mut network: Vec<Box<dyn Layer>> = vec![Box::new(Embeddings::new(vocab.clone()))];
+        for _ in 0..model.num_blocks {
+            network.push(Box::new(
+                TransformerBlock::with_ffn(model.embedding_dim, model.hidden_dim, model.ffn_type)
+                    .with_residual_scale(model.residual_scale),
+            ));
+        }
+        network.push(Box::new(OutputProjection::new(
+            model.embedding_dim,
+            vocab.size(),
+        )));
+
+        let mut llm = LLM::try_new(vocab, network)?;
+        llm.normalizer = config.data.normalizer();
+        llm.track_examples = config.training.track_examples;
+        llm.gradient_clip = config.training.gradient_clip_mode();
+        llm.document_masking = config.training.pack_sequences;
+        llm.teacher_forcing_ratio = config.training.teacher_forcing_ratio;
+        llm.curriculum = config.training.curriculum;
+        llm.freeze_embeddings_epochs = config.training.freeze_embeddings_epochs;
+        llm.sample_prompts = config.training.sample_prompts.clone();
+        llm.sample_interval = config.training.sample_interval;
+        Ok(llm)
+    }
 
-                total_loss += Self::cross_entropy_loss_step(&probs, target_ids);
+    /// Save the weights and vocabulary together with `config`, so
+    /// [`LLM::from_pretrained`] can rebuild the model without any other input.
+    /// `config` must describe this model's architecture.
+    pub fn save_pretrained(&self, path: &Path, config: &Config) -> Result<()> {
+        let mut checkpoint = self.to_checkpoint(0, 0.0);
+        checkpoint.metadata.set_config(config)?;
+        checkpoint.save(path)
+    }
 
-                // Backward pass
-                let mut grads_output = Self::compute_gradients_step(&probs, target_ids); // this is d_L/d_output_projection
+    /// Load a ready-to-use model saved with [`LLM::save_pretrained`].
+    ///
+    /// # Errors
+    /// Returns `ConfigError` if the checkpoint does not embed a model config, and
+    /// `ShapeMismatch` if its weights do not fit the config's architecture.
+    pub fn from_pretrained(checkpoint_path: &Path) -> Result<LLM> {
+        let checkpoint = Checkpoint::load(checkpoint_path)?;
+        let config = checkpoint.metadata.parsed_config().map_err(|_| {
+            LlmError::ConfigError(format!(
+                "{} has no embedded model config (save it with LLM::save_pretrained)",
+                checkpoint_path.display()
+            ))
+        })?;
+
+        let vocab = Vocab::new(checkpoint.vocab.iter().map(String::as_str).collect());
+        let mut llm = LLM::from_config(&config, vocab)?;
+        llm.load_checkpoint(&checkpoint)?;
+        tracing::info!("Loaded pretrained model from {:?}", checkpoint_path);
+        Ok(llm)
+    }
 
-                // Apply gradient clipping BEFORE backpropagation
-                Self::clip_gradients(&mut grads_output, 5.0);
+    /// Write only the weights and vocabulary to `path`, for deployment. The file is
+    /// smaller than a checkpoint and loads with [`LLM::load_inference_bundle`].
+    pub fn export_inference_bundle(&self, path: &Path) -> Result<()> {
+        InferenceBundle::from_checkpoint(self.to_checkpoint(0, 0.0)).save(path)
+    }
 
-                for layer in self.network.iter_mut().rev() {
-                    grads_output = layer.backward(&grads_output, lr);
-                }
+    /// Load weights written by [`LLM::export_inference_bundle`] into this model,
+    /// reconciling vocabularies as [`LLM::load_checkpoint`] does.
+    ///
+    /// # Errors
+    /// Returns `ShapeMismatch` if the bundle does not fit this architecture.
+    pub fn load_inference_bundle(&mut self, path: &Path) -> Result<VocabReconciliation> {
+        let bundle = InferenceBundle::load(path)?;
+        self.load_checkpoint(&bundle.to_checkpoint())
+    }
 
-                let tokens = Self::greedy_decode(&probs);
-                let next_token = tokens[tokens.len() - 1];
+    /// Snapshot the vocabulary and every layer's weights into a checkpoint.
+    pub fn to_checkpoint(&self, epoch: usize, loss: f32) -> Checkpoint {
+        let mut checkpoint = Checkpoint::new(epoch, loss, &self.network_description());
+        for weight in self.network.iter().flat_map(|layer| layer.weights()) {
+            checkpoint.add_parameter(weight);
+        }
+        checkpoint.vocab = self.vocab.words.clone();
+        checkpoint
+    }
 
-                if next_token == self.vocab.encode("</s>").unwrap() {
-                    continue;
-                }
+    /// Load weights saved by [`LLM::to_checkpoint`] into this model.
+    ///
+    /// When the checkpoint was saved with a different vocabulary, vocabulary-sized
+    /// parameters are matched by word: shared tokens keep their trained parameters, new
+    /// tokens are initialized freshly and tokens missing from the current vocabulary are
+    /// dropped with a warning. A checkpoint without a saved vocabulary is assumed to
+    /// match the current one. The model is left unchanged on error.
+    ///
+    /// # Errors
+    /// Returns `ShapeMismatch` if the checkpoint does not fit this architecture.
+    pub fn load_checkpoint(&mut self, checkpoint: &Checkpoint) -> Result<VocabReconciliation> {
+        let reconciliation = if checkpoint.vocab.is_empty() {
+            VocabReconciliation {
+                old_ids: (0..self.vocab.size()).map(Some).collect(),
+                added: Vec::new(),
+                dropped: Vec::new(),
             }
+        } else {
+            checkpoint.reconcile_vocab(&self.vocab)
+        };
 
-            let avg_loss = total_loss / tokenized_data.len() as f32;
-            if let Some(pb) = progress {
-                pb.set_message(format!("Epoch {}: Loss = {:.4}", epoch + 1, avg_loss));
+        let expected_shapes: Vec<(usize, usize)> = self
+            .network
+            .iter()
+            .flat_map(|layer| layer.weights())
+            .map(|weight| weight.dim())
+            .collect();
+        if checkpoint.parameters.len() != expected_shapes.len() {
+            return Err(LlmError::shape_mismatch(
+                format!("{} parameter matrices", expected_shapes.len()),
+                checkpoint.parameters.len(),
+            ));
+        }
+
+        // Load into a copy so a mismatch leaves the model untouched
+        let mut network = self.network.clone();
+        for (index, weight) in network
+            .iter_mut()
+            .flat_map(|layer| layer.weights_mut())
+            .enumerate()
+        {
+            *weight = if checkpoint.shapes.is_empty() {
+                // Migrated version 1 checkpoints carry no shapes; assume the model's own
+                Array2::from_shape_vec(weight.dim(), checkpoint.parameters[index].clone()).map_err(
+                    |_| LlmError::shape_mismatch(weight.len(), checkpoint.parameters[index].len()),
+                )?
             } else {
-                println!("Epoch {}: Loss = {:.4}", epoch + 1, avg_loss);
+                checkpoint.parameter(index)?
+            };
+        }
+        if !reconciliation.is_identity() {
+            for layer in &mut network {
+                layer.remap_vocab(&reconciliation.old_ids);
             }
-            if let Some(vis) = &mut visualizer {
-                vis.record_loss(avg_loss);
-                vis.set_epoch(epoch + 1);
+        }
+
+        for (weight, expected) in network
+            .iter()
+            .flat_map(|layer| layer.weights())
+            .zip(&expected_shapes)
+        {
+            if weight.dim() != *expected {
+                return Err(LlmError::shape_mismatch(
+                    format!("{:?}", expected),
+                    format!("{:?}", weight.dim()),
+                ));
             }
         }
+
+        if !reconciliation.dropped.is_empty() {
+            tracing::warn!(
+                "Dropped {} checkpoint tokens missing from the vocabulary: {:?}",
+                reconciliation.dropped.len(),
+                reconciliation.dropped
+            );
+        }
+        if !reconciliation.added.is_empty() {
+            tracing::info!(
+                "Initialized {} tokens not in the checkpoint",
+                reconciliation.added.len()
+            );
+        }
+        self.network = network;
+        Ok(reconciliation)
     }
 
-    pub fn tokenize(&self, text: &str) -> Vec<usize> {
-        // Split by whitespace first
-        let mut tokens = Vec::new();
-
-        for word in text.split_whitespace() {
-            // Special case for end token
-            if word == "</s>" {
-                if let Some(token_id) = self.vocab.encode(word) {
-                    tokens.push(token_id);
-                }
-                continue;
-            }
+    /// Reinitialize every layer's parameters deterministically from `seed` and reset all
+    /// optimizer state.
+    ///
+    /// The result matches a freshly constructed model whose layers were built in network
+    /// order after calling [`crate::rng::seed`] with the same seed.
+    pub fn reinitialize(&mut self, seed: u64) {
+        crate::rng::seed(seed);
+        for layer in &mut self.network {
+            layer.reinitialize();
+        }
+    }
 
-            let mut current_word = String::new();
+    /// Enable or disable causal masking in every layer of the network.
+    pub fn set_causal(&mut self, causal: bool) {
+        for layer in &mut self.network {
+            layer.set_causal(causal);
+        }
+    }
 
-            for c in word.chars() {
-                if c.is_ascii_punctuation() {
-                    // If we have a word before the punctuation, add it
-                    if !current_word.is_empty() {
-                        if let Some(token_id) = self.vocab.encode(&current_word) {
-                            tokens.push(token_id);
-                        }
-                        current_word.clear();
-                    }
+    /// Token ids of `text`, cut to [`MAX_SEQ_LEN`] tokens according to
+    /// [`LLM::truncation`].
+    ///
+    /// `</s>` is never appended: training samples carry their own end marker, and
+    /// prompts must not end with one or generation would see a finished document. Use
+    /// [`LLM::tokenize_with_options`] to append it.
+    pub fn tokenize(&self, text: &str) -> Vec<usize> {
+        self.tokenize_with_options(text, false)
+    }
 
-                    // Add the punctuation as its own token
-                    if let Some(token_id) = self.vocab.encode(&c.to_string()) {
-                        tokens.push(token_id);
-                    }
-                } else {
-                    current_word.push(c);
+    /// [`LLM::tokenize`], appending `</s>` when `add_eos` is set and `text` does not
+    /// already end with it. The end marker survives truncation. Without `</s>` in the
+    /// vocabulary `add_eos` has no effect.
+    pub fn tokenize_with_options(&self, text: &str, add_eos: bool) -> Vec<usize> {
+        // Words missing from the vocabulary are dropped
+        let mut tokens: Vec<usize> = self
+            .split_normalized(text)
+            .iter()
+            .filter_map(|piece| self.vocab.encode(piece))
+            .collect();
+        match self.vocab.encode("</s>") {
+            Some(eos) if add_eos => {
+                // Set the end marker aside so truncation cannot drop it
+                if tokens.last() == Some(&eos) {
+                    tokens.pop();
                 }
+                let mut tokens = self.truncation.apply(tokens, MAX_SEQ_LEN - 1);
+                tokens.push(eos);
+                tokens
             }
+            _ => self.truncation.apply(tokens, MAX_SEQ_LEN),
+        }
+    }
 
-            // Add any remaining word
-            if !current_word.is_empty() {
-                if let Some(token_id) = self.vocab.encode(&current_word) {
-                    tokens.push(token_id);
-                }
-            }
+    /// [`LLM::tokenize`], also returning the fraction of word pieces that were missing
+    /// from the vocabulary (0.0 for empty input).
+    ///
+    /// Logs a warning when the fraction exceeds [`LLM::oov_warning_threshold`], since the
+    /// model cannot see the dropped words.
+    pub fn tokenize_with_stats(&self, text: &str) -> (Vec<usize>, f32) {
+        let pieces = self.split_normalized(text);
+        let tokens: Vec<usize> = pieces
+            .iter()
+            .filter_map(|piece| self.vocab.encode(piece))
+            .collect();
+        let oov_rate = if pieces.is_empty() {
+            0.0
+        } else {
+            (pieces.len() - tokens.len()) as f32 / pieces.len() as f32
+        };
+        if oov_rate > self.oov_warning_threshold {
+            tracing::warn!(
+                oov_rate,
+                "{} of {} words in the input are not in the vocabulary",
+                pieces.len() - tokens.len(),
+                pieces.len()
+            );
+        }
+        (self.truncation.apply(tokens, MAX_SEQ_LEN), oov_rate)
+    }
+
+    /// Word pieces of `text` after applying the normalizer, if any.
+    fn split_normalized(&self, text: &str) -> Vec<String> {
+        match &self.normalizer {
+            Some(normalizer) => Vocab::split_text(&normalizer.normalize(text)),
+            None => Vocab::split_text(text),
+        }
+    }
+
+    /// [`LLM::tokenize`] with memoization, for data tokenized repeatedly (e.g. every
+    /// epoch).
+    ///
+    /// The cache is dropped whenever the vocabulary's words change, whether through
+    /// [`LLM::expand_vocab`], [`LLM::load_checkpoint`] or by replacing `vocab`. Replacing
+    /// `normalizer` or `truncation` requires [`LLM::clear_token_cache`].
+    pub fn tokenize_cached(&mut self, text: &str) -> Vec<usize> {
+        if self.token_cache.vocab_words != self.vocab.words {
+            self.clear_token_cache();
+        }
+        if let Some(tokens) = self.token_cache.entries.get(text) {
+            return tokens.clone();
         }
 
+        let tokens = self.tokenize(text);
+        if self.token_cache.entries.len() < self.token_cache_capacity {
+            self.token_cache
+                .entries
+                .insert(text.to_string(), tokens.clone());
+        }
         tokens
     }
 
+    /// Number of texts currently held by the tokenization cache.
+    pub fn token_cache_len(&self) -> usize {
+        self.token_cache.entries.len()
+    }
+
+    /// Forget every cached tokenization.
+    pub fn clear_token_cache(&mut self) {
+        self.token_cache.entries.clear();
+        self.token_cache.vocab_words.clone_from(&self.vocab.words);
+    }
+
     pub fn softmax(logits: &Array2<f32>) -> Array2<f32> {
         // logits is seq_len x vocab_size
         let mut result = logits.clone();
@@ -294,16 +2380,35 @@ impl LLM {
         result
     }
 
+    /// Row-wise log-softmax, computed as `x - logsumexp(x)` so large logits neither
+    /// overflow nor collapse to `ln(0)`.
+    pub fn log_softmax(logits: &Array2<f32>) -> Array2<f32> {
+        let mut result = logits.clone();
+        for mut row in result.rows_mut() {
+            let max_val = row.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let log_sum_exp = max_val + row.iter().map(|&x| (x - max_val).exp()).sum::<f32>().ln();
+            row.mapv_inplace(|x| x - log_sum_exp);
+        }
+        result
+    }
+
+    /// Mean cross-entropy computed directly from logits with [`LLM::log_softmax`].
+    pub fn cross_entropy_from_logits(logits: &Array2<f32>, targets: &[usize]) -> f32 {
+        let log_probs = Self::log_softmax(logits);
+        let total: f32 = targets
+            .iter()
+            .enumerate()
+            .map(|(row_idx, &target)| -log_probs[[row_idx, target]])
+            .sum();
+        total / targets.len() as f32
+    }
+
+    /// Index of the highest value in each row.
+    ///
+    /// Ties go to the lowest token id and NaNs are never chosen over a number, so
+    /// decoding is reproducible regardless of platform.
     pub fn greedy_decode(probs: &Array2<f32>) -> Vec<usize> {
-        probs
-            .map_axis(Axis(1), |row| {
-                row.iter()
-                    .enumerate()
-                    .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
-                    .map(|(index, _)| index)
-                    .unwrap()
-            })
-            .to_vec()
+        probs.map_axis(Axis(1), argmax).to_vec()
     }
 
     pub fn cross_entropy_loss_step(probs: &Array2<f32>, target: &[usize]) -> f32 {
@@ -336,9 +2441,53 @@ impl LLM {
         grads
     }
 
+    /// Run the backward pass from the output gradient, clipping according to
+    /// [`LLM::gradient_clip`]. When given, `on_layer` receives each layer's index and
+    /// backward time; without it nothing is timed.
+    fn backpropagate(
+        &mut self,
+        mut grads: Array2<f32>,
+        lr: f32,
+        mut on_layer: Option<&mut dyn FnMut(usize, std::time::Duration)>,
+    ) {
+        let max_norm = match self.gradient_clip {
+            GradientClip::Global(max_norm) => {
+                Self::clip_gradients(&mut grads, max_norm);
+                None
+            }
+            GradientClip::PerLayer(max_norm) => Some(max_norm),
+        };
+
+        for (idx, layer) in self.network.iter_mut().enumerate().rev() {
+            tracing::trace!(
+                layer = idx,
+                grad_norm = Self::gradient_norm(&grads),
+                "Backpropagating"
+            );
+            // Frozen layers still pass gradients on, but do not move their parameters
+            let layer_lr = if self.frozen_layers.contains(&idx) {
+                0.0
+            } else {
+                lr
+            };
+            match on_layer.as_mut() {
+                Some(on_layer) => {
+                    let started = std::time::Instant::now();
+                    grads = layer.backward_clipped(&grads, layer_lr, max_norm);
+                    on_layer(idx, started.elapsed());
+                }
+                None => grads = layer.backward_clipped(&grads, layer_lr, max_norm),
+            }
+        }
+    }
+
+    /// L2 norm of a gradient matrix, leaving it untouched.
+    pub fn gradient_norm(grads: &Array2<f32>) -> f32 {
+        grads.iter().map(|&x| x * x).sum::<f32>().sqrt()
+    }
+
     pub fn clip_gradients(grads: &mut Array2<f32>, max_norm: f32) {
-        // Calculate L2 norm of gradients
-        let norm = grads.iter().map(|&x| x * x).sum::<f32>().sqrt();
+        let norm = Self::gradient_norm(grads);
 
         // If norm exceeds max_norm, scale gradients down
         if norm > max_norm {
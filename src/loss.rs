@@ -0,0 +1,202 @@
+//! Loss functions used by the training loop.
+//!
+//! Losses operate on softmax probabilities (`seq_len x vocab_size`) and return gradients
+//! with respect to the logits, which is what the output layer's backward pass expects.
+
+use ndarray::Array2;
+
+use crate::LLM;
+
+/// Cloning for boxed losses, implemented for every `LossFn + Clone` type.
+pub trait LossFnClone {
+    fn clone_box(&self) -> Box<dyn LossFn>;
+}
+
+impl<T: LossFn + Clone + 'static> LossFnClone for T {
+    fn clone_box(&self) -> Box<dyn LossFn> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn LossFn> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// A training objective's loss and its gradient with respect to the logits.
+pub trait LossFn: LossFnClone {
+    /// Loss averaged over the rows of `probs`.
+    fn loss(&self, probs: &Array2<f32>, targets: &[usize]) -> f32;
+
+    /// Gradient of [`LossFn::loss`] with respect to the logits.
+    fn gradient(&self, probs: &Array2<f32>, targets: &[usize]) -> Array2<f32>;
+
+    /// [`LossFn::loss`] computed from raw logits. Override to avoid taking the log of
+    /// probabilities that underflowed to zero.
+    fn loss_from_logits(&self, logits: &Array2<f32>, targets: &[usize]) -> f32 {
+        self.loss(&LLM::softmax(logits), targets)
+    }
+}
+
+/// Standard softmax cross-entropy.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CrossEntropyLoss;
+
+impl LossFn for CrossEntropyLoss {
+    fn loss(&self, probs: &Array2<f32>, targets: &[usize]) -> f32 {
+        LLM::cross_entropy_loss_step(probs, targets)
+    }
+
+    fn gradient(&self, probs: &Array2<f32>, targets: &[usize]) -> Array2<f32> {
+        LLM::compute_gradients_step(probs, targets)
+    }
+
+    fn loss_from_logits(&self, logits: &Array2<f32>, targets: &[usize]) -> f32 {
+        LLM::cross_entropy_from_logits(logits, targets)
+    }
+}
+
+/// Focal loss, `-(1 - p_t)^gamma * ln(p_t)`, which down-weights easy predictions.
+/// With `gamma = 0` it reduces to cross-entropy.
+#[derive(Debug, Clone, Copy)]
+pub struct FocalLoss {
+    pub gamma: f32,
+}
+
+impl LossFn for FocalLoss {
+    fn loss(&self, probs: &Array2<f32>, targets: &[usize]) -> f32 {
+        let mut loss = 0.0;
+        for (row_idx, &target) in targets.iter().enumerate() {
+            let p_t = probs[[row_idx, target]].max(1e-15);
+            loss -= (1.0 - p_t).powf(self.gamma) * p_t.ln();
+        }
+
+        loss / targets.len() as f32
+    }
+
+    fn gradient(&self, probs: &Array2<f32>, targets: &[usize]) -> Array2<f32> {
+        if probs.shape()[0] != targets.len() {
+            panic!("Probs and target must have the same number of rows");
+        }
+
+        let mut grads = Array2::zeros(probs.dim());
+        let batch_size = targets.len() as f32;
+
+        for (row_idx, &target) in targets.iter().enumerate() {
+            let p_t = probs[[row_idx, target]].max(1e-15);
+            let one_minus = (1.0 - p_t).max(0.0);
+
+            // dL/dz_j = [gamma * (1-p_t)^(gamma-1) * p_t * ln(p_t) - (1-p_t)^gamma] * (δ_tj - p_j)
+            // For gamma < 1 the power blows up as p_t -> 1 while ln(p_t) -> 0; their
+            // product tends to 0, so the base is kept off zero to avoid inf * 0
+            let modulating = if self.gamma == 0.0 {
+                -1.0
+            } else {
+                self.gamma * one_minus.max(1e-7).powf(self.gamma - 1.0) * p_t * p_t.ln()
+                    - one_minus.powf(self.gamma)
+            };
+
+            for (j, grad) in grads.row_mut(row_idx).iter_mut().enumerate() {
+                let delta = if j == target { 1.0 } else { 0.0 };
+                *grad = modulating * (delta - probs[[row_idx, j]]) / batch_size;
+            }
+        }
+
+        grads
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_probs() -> Array2<f32> {
+        let logits = Array2::from_shape_fn((3, 5), |(i, j)| ((i * 5 + j) as f32 * 0.37).sin());
+        LLM::softmax(&logits)
+    }
+
+    #[test]
+    fn test_cross_entropy_matches_llm_step() {
+        let probs = sample_probs();
+        let targets = [1, 4, 0];
+
+        assert_eq!(
+            CrossEntropyLoss.loss(&probs, &targets),
+            LLM::cross_entropy_loss_step(&probs, &targets)
+        );
+        assert_eq!(
+            CrossEntropyLoss.gradient(&probs, &targets),
+            LLM::compute_gradients_step(&probs, &targets)
+        );
+    }
+
+    #[test]
+    fn test_cross_entropy_from_large_logits_is_exact() {
+        let logits = Array2::from_shape_vec((2, 3), vec![1e3, -1e3, 0.0, 5e3, 5e3, -5e3]).unwrap();
+        let targets = [1, 0];
+
+        // Through probabilities the target underflows to 0 and the loss saturates
+        let naive = CrossEntropyLoss.loss(&LLM::softmax(&logits), &targets);
+        let stable = CrossEntropyLoss.loss_from_logits(&logits, &targets);
+        assert!(stable.is_finite());
+        // Row 1: -(-1000 - 1000) = 2000, row 2: ln 2
+        let expected = (2000.0 + 2f32.ln()) / 2.0;
+        assert!((stable - expected).abs() < 1e-3, "{}", stable);
+        assert!((naive - expected).abs() > 100.0);
+
+        let log_probs = LLM::log_softmax(&logits);
+        assert!(log_probs.iter().all(|x| x.is_finite()));
+        assert!((log_probs[[0, 0]]).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_loss_from_logits_matches_probability_path() {
+        let logits = Array2::from_shape_fn((3, 5), |(i, j)| ((i * 5 + j) as f32 * 0.37).sin());
+        let targets = [1, 4, 0];
+        let probs = LLM::softmax(&logits);
+
+        let from_logits = CrossEntropyLoss.loss_from_logits(&logits, &targets);
+        assert!((from_logits - CrossEntropyLoss.loss(&probs, &targets)).abs() < 1e-5);
+        let focal = FocalLoss { gamma: 2.0 };
+        assert_eq!(
+            focal.loss_from_logits(&logits, &targets),
+            focal.loss(&probs, &targets)
+        );
+    }
+
+    #[test]
+    fn test_focal_loss_with_zero_gamma_is_cross_entropy() {
+        let probs = sample_probs();
+        let targets = [2, 3, 1];
+        let focal = FocalLoss { gamma: 0.0 };
+
+        let ce_loss = CrossEntropyLoss.loss(&probs, &targets);
+        assert!((focal.loss(&probs, &targets) - ce_loss).abs() < 1e-6);
+
+        let ce_grads = CrossEntropyLoss.gradient(&probs, &targets);
+        let focal_grads = focal.gradient(&probs, &targets);
+        for (a, b) in ce_grads.iter().zip(focal_grads.iter()) {
+            assert!((a - b).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_focal_loss_down_weights_confident_predictions() {
+        let probs = sample_probs();
+        let targets = [0, 0, 0];
+        let ce = CrossEntropyLoss.loss(&probs, &targets);
+        let focal = FocalLoss { gamma: 2.0 }.loss(&probs, &targets);
+        assert!(focal < ce);
+    }
+
+    #[test]
+    fn test_focal_gradient_with_small_gamma_is_finite_when_certain() {
+        let probs = Array2::from_shape_vec((2, 3), vec![1.0, 0.0, 0.0, 0.2, 0.5, 0.3]).unwrap();
+        let targets = [0, 1];
+        let grads = FocalLoss { gamma: 0.5 }.gradient(&probs, &targets);
+        assert!(grads.iter().all(|g| g.is_finite()), "{:?}", grads);
+        // A perfectly confident correct prediction contributes no gradient
+        assert!(grads.row(0).iter().all(|&g| g == 0.0));
+    }
+}
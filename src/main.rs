@@ -3,24 +3,25 @@
 //! This binary trains and runs an interactive LLM with support for configuration,
 //! checkpointing, and graceful shutdown.
 
-use clap::Parser;
-use indicatif::ProgressBar;
-use std::io::Write;
-use std::path::PathBuf;
+use clap::{Parser, Subcommand};
+use rustyline::{error::ReadlineError, DefaultEditor};
+use std::path::{Path, PathBuf};
 use tracing::info;
 
 use llm::{
-    init_logging, output_projection::OutputProjection, transformer::TransformerBlock, Config,
-    Dataset, DatasetType, Embeddings, Result as LlmResult, Vocab, EMBEDDING_DIM, HIDDEN_DIM, LLM,
-    MAX_SEQ_LEN,
+    init_logging, Config, Dataset, DatasetType, GenerationConfig, Result as LlmResult,
+    TrainingReport, Vocab, LLM, MAX_SEQ_LEN,
 };
 
+/// Prefix that opens a user turn in the chat data
+const USER_PREFIX: &str = "User:";
+
 /// Command-line arguments for the LLM
 #[derive(Parser, Debug)]
 #[command(name = "RustGPT")]
 #[command(about = "A transformer-based LLM in pure Rust", long_about = None)]
 struct Args {
-    /// Path to configuration file
+    /// Path to configuration file (.toml, .json, .yaml)
     #[arg(short = 'c', long, value_name = "FILE")]
     config: Option<PathBuf>,
 
@@ -28,10 +29,14 @@ struct Args {
     #[arg(short = 't', long)]
     train: bool,
 
-    /// Load model from checkpoint
+    /// Load a model saved with --save-model and skip straight to interactive mode
     #[arg(short = 'k', long, value_name = "FILE")]
     checkpoint: Option<PathBuf>,
 
+    /// Save the trained model to FILE for loading with --checkpoint
+    #[arg(long, value_name = "FILE")]
+    save_model: Option<PathBuf>,
+
     /// Logging level (debug, info, warn, error)
     #[arg(short = 'l', long, default_value = "info")]
     log_level: String,
@@ -51,6 +56,57 @@ struct Args {
     /// Output directory for checkpoints
     #[arg(short, long, value_name = "DIR")]
     output: Option<PathBuf>,
+
+    /// Train on at most N samples of each dataset; 0 uses all (overrides config)
+    #[arg(long, value_name = "N")]
+    max_samples: Option<usize>,
+
+    /// Number of attention heads; only 1 is supported in this build (overrides config)
+    #[arg(long, value_name = "N")]
+    num_heads: Option<usize>,
+
+    /// Report perplexity and accuracy on a held-out FILE (same format as the training
+    /// data) once training completes
+    #[arg(long, value_name = "FILE")]
+    test_data: Option<PathBuf>,
+
+    /// Check that the freshly initialized model produces finite logits before training
+    #[arg(long)]
+    self_check: bool,
+
+    /// Random seed for weight initialization (overrides config)
+    #[arg(long, value_name = "SEED")]
+    seed: Option<u64>,
+
+    /// Write the default configuration to FILE (.toml, .json, .yaml) and exit
+    #[arg(long, value_name = "FILE")]
+    write_default_config: Option<PathBuf>,
+
+    /// Allow --write-default-config to overwrite an existing file
+    #[arg(long, requires = "write_default_config")]
+    force: bool,
+
+    /// Print the effective configuration (after file, CLI and seed overrides) as TOML
+    #[arg(long)]
+    print_config: bool,
+
+    /// Validate the configuration and exit without loading data or training
+    #[arg(long)]
+    dry_run: bool,
+
+    /// File that keeps interactive prompt history between sessions
+    #[arg(long, value_name = "FILE", default_value = ".rustgpt_history")]
+    history_file: PathBuf,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Subcommands that run instead of training
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print corpus statistics for the configured dataset and exit
+    Stats,
 }
 
 fn main() -> LlmResult<()> {
@@ -62,10 +118,16 @@ fn main() -> LlmResult<()> {
 
     info!("RustGPT v{} starting", llm::VERSION);
 
+    if let Some(path) = &args.write_default_config {
+        Config::write_default(path, args.force)?;
+        println!("Wrote default configuration to {}", path.display());
+        return Ok(());
+    }
+
     // Load or create configuration
     let mut config = if let Some(config_path) = args.config {
         info!("Loading configuration from {:?}", config_path);
-        Config::from_toml(&config_path)?
+        Config::load(&config_path)?
     } else {
         Config::default()
     };
@@ -80,32 +142,63 @@ fn main() -> LlmResult<()> {
     if let Some(path) = args.output {
         config.output.checkpoint_dir = path.to_string_lossy().to_string();
     }
+    if let Some(max_samples) = args.max_samples {
+        config.data.max_samples = Some(max_samples);
+    }
+    if let Some(num_heads) = args.num_heads {
+        config.model.num_heads = num_heads;
+    }
+    let seed = config.model.resolve_seed(args.seed);
+    config.model.seed = Some(seed);
+    info!("Using random seed {}", seed);
 
     // Validate configuration
     config.validate()?;
     info!("Configuration loaded and validated");
+    if args.print_config {
+        println!("{}", config.to_toml_string()?);
+    }
+    if args.dry_run {
+        return Ok(());
+    }
     info!(
         "Model config: embedding_dim={}, hidden_dim={}, max_seq_len={}",
         config.model.embedding_dim, config.model.hidden_dim, config.model.max_seq_len
     );
 
+    if let Some(path) = &args.checkpoint {
+        let mut llm = LLM::from_pretrained(path)?;
+        println!("Loaded model from {}", path.display());
+        return run_interactive(&mut llm, &args.history_file);
+    }
+
     // Load dataset
     info!(
         "Loading dataset from {:?} and {:?}",
         config.data.pretraining_data, config.data.chat_training_data
     );
 
-    let dataset = Dataset::new(
+    let normalizer = config.data.normalizer();
+    let data_type = if config.data.format == "csv" {
+        if config.data.csv_has_headers {
+            DatasetType::CSVWithHeaders
+        } else {
+            DatasetType::CSV
+        }
+    } else {
+        DatasetType::JSON
+    };
+    let mut dataset = Dataset::new_with_normalizer(
         &config.data.pretraining_data,
         &config.data.chat_training_data,
-        if config.data.format == "csv" {
-            DatasetType::CSV
-        } else {
-            DatasetType::JSON
-        },
+        data_type,
+        normalizer.as_ref(),
     )?;
 
     dataset.validate()?;
+    if let Some(max_samples) = config.data.max_samples {
+        dataset.truncate(max_samples);
+    }
     info!("Dataset loaded: {} total samples", dataset.total_samples());
 
     // Build vocabulary from dataset
@@ -120,32 +213,39 @@ fn main() -> LlmResult<()> {
     let vocab = Vocab::new(vocab_words_refs);
     info!("Vocabulary built with {} tokens", vocab.size());
 
+    println!("\n=== DATASET STATISTICS ===");
+    println!("{}", dataset.stats_report(&vocab));
+    for (index, reason) in dataset.integrity_report(&vocab) {
+        tracing::warn!("Sample {}: {}", index, reason);
+    }
+    if matches!(args.command, Some(Command::Stats)) {
+        return Ok(());
+    }
+    if config.data.split_sentences {
+        let added = dataset.split_sentences(MAX_SEQ_LEN);
+        info!("Sentence splitting added {} pre-training samples", added);
+    }
+    if config.training.pack_sequences {
+        dataset.pack(MAX_SEQ_LEN);
+    }
+
     // Create model layers
     info!("Initializing model layers...");
-    let transformer_block_1 = TransformerBlock::new(EMBEDDING_DIM, HIDDEN_DIM);
-    let transformer_block_2 = TransformerBlock::new(EMBEDDING_DIM, HIDDEN_DIM);
-    let transformer_block_3 = TransformerBlock::new(EMBEDDING_DIM, HIDDEN_DIM);
-    let output_projection = OutputProjection::new(EMBEDDING_DIM, vocab.words.len());
-    let embeddings = Embeddings::new(vocab.clone());
-
-    let mut llm = LLM::new(
-        vocab.clone(),
-        vec![
-            Box::new(embeddings),
-            Box::new(transformer_block_1),
-            Box::new(transformer_block_2),
-            Box::new(transformer_block_3),
-            Box::new(output_projection),
-        ],
-    );
+    llm::rng::seed(seed);
+    // Built in network order so a seed reproduces `LLM::reinitialize`
+    let mut llm = LLM::from_config(&config, vocab.clone())?;
 
     println!("\n=== MODEL INFORMATION ===");
     println!("Network architecture: {}", llm.network_description());
     println!(
         "Model configuration -> max_seq_len: {}, embedding_dim: {}, hidden_dim: {}",
-        MAX_SEQ_LEN, EMBEDDING_DIM, HIDDEN_DIM
+        config.model.max_seq_len, config.model.embedding_dim, config.model.hidden_dim
     );
     println!("Total parameters: {}", llm.total_parameters());
+    if args.self_check {
+        llm.self_check()?;
+        info!("Self-check passed");
+    }
 
     let test_input = "User: How do mountains form?";
     println!("\n=== BEFORE TRAINING ===");
@@ -155,83 +255,110 @@ fn main() -> LlmResult<()> {
     // Training phase
     info!("Starting training phase...");
 
-    // Pre-training
-    println!("\n=== PRE-TRAINING MODEL ===");
-    info!(
-        "Pre-training on {} examples for {} epochs with learning rate {}",
-        dataset.pretraining_data.len(),
-        config.training.pretraining_epochs,
-        config.training.pretraining_lr
-    );
+    if let Some(mix_ratio) = config.training.mix_ratio {
+        // Blended training: one phase drawing from both splits, so instruction tuning
+        // does not overwrite what pre-training learned
+        println!("\n=== BLENDED TRAINING ===");
+        let epochs = config.training.pretraining_epochs;
+        let lr = config.training.pretraining_lr;
+        info!(
+            "Training on {} examples blended at mix ratio {} for {} epochs with learning rate {}",
+            dataset.total_samples(),
+            mix_ratio,
+            epochs,
+            lr
+        );
 
-    let pretraining_examples: Vec<&str> = dataset
-        .pretraining_data
-        .iter()
-        .map(|s| s.as_str())
-        .collect();
-
-    // Use visualization dashboard if -v flag is set, otherwise use progress bar
-    if args.visualize {
-        llm::training_ui::train_with_dashboard(
-            &mut llm,
-            pretraining_examples.clone(),
-            config.training.pretraining_epochs,
-            config.training.pretraining_lr,
-            "Pre-training",
-        )?;
+        if args.visualize {
+            // The dashboard trains every epoch on a single draw of the blend
+            llm::training_ui::train_with_dashboard(
+                &mut llm,
+                dataset.mixed_epoch(mix_ratio, &mut llm::rng::init_rng()),
+                epochs,
+                lr,
+                "Blended Training",
+            )?;
+        } else {
+            let mut pb = config.output.progress_bar(epochs as u64)?;
+            let report = llm.train_blended(&dataset, mix_ratio, epochs, lr, &mut pb);
+            pb.finish_with_message("✓ Blended training complete");
+            log_tracked_examples(&report);
+        }
     } else {
-        let pb = ProgressBar::new(config.training.pretraining_epochs as u64);
-        pb.set_style(
-            indicatif::ProgressStyle::default_bar()
-                .template("{msg}\n[{bar:40.cyan/blue}] {pos}/{len}")
-                .unwrap(),
-        );
-        llm.train_with_progress(
-            pretraining_examples.clone(),
+        // Pre-training
+        println!("\n=== PRE-TRAINING MODEL ===");
+        info!(
+            "Pre-training on {} examples for {} epochs with learning rate {}",
+            dataset.pretraining_data.len(),
             config.training.pretraining_epochs,
-            config.training.pretraining_lr,
-            Some(&pb),
+            config.training.pretraining_lr
         );
-        pb.finish_with_message("✓ Pre-training complete");
-    }
-
-    // Instruction tuning
-    println!("\n=== INSTRUCTION TUNING ===");
-    let chat_training_examples: Vec<&str> = dataset
-        .chat_training_data
-        .iter()
-        .map(|s| s.as_str())
-        .collect();
 
-    info!(
-        "Instruction tuning on {} examples for {} epochs with learning rate {}",
-        dataset.chat_training_data.len(),
-        config.training.finetuning_epochs,
-        config.training.finetuning_lr
-    );
+        let pretraining_examples: Vec<&str> = dataset
+            .pretraining_data
+            .iter()
+            .map(|s| s.as_str())
+            .collect();
+
+        // Use visualization dashboard if -v flag is set, otherwise use progress bar
+        if args.visualize {
+            llm::training_ui::train_with_dashboard(
+                &mut llm,
+                pretraining_examples.clone(),
+                config.training.pretraining_epochs,
+                config.training.pretraining_lr,
+                "Pre-training",
+            )?;
+        } else {
+            let mut pb = config
+                .output
+                .progress_bar(config.training.pretraining_epochs as u64)?;
+            let report = llm.train_with_progress(
+                pretraining_examples.clone(),
+                config.training.pretraining_epochs,
+                config.training.pretraining_lr,
+                &mut pb,
+            );
+            pb.finish_with_message("✓ Pre-training complete");
+            log_tracked_examples(&report);
+        }
 
-    if args.visualize {
-        llm::training_ui::train_with_dashboard(
-            &mut llm,
-            chat_training_examples.clone(),
-            config.training.finetuning_epochs,
-            config.training.finetuning_lr,
-            "Instruction Tuning",
-        )?;
-    } else {
-        let pb = ProgressBar::new(config.training.finetuning_epochs as u64);
-        pb.set_style(
-            indicatif::ProgressStyle::default_bar()
-                .template("{msg}\n[{bar:40.cyan/blue}] {pos}/{len}")
-                .unwrap(),
-        );
-        llm.train_with_progress(
-            chat_training_examples.clone(),
+        // Instruction tuning
+        println!("\n=== INSTRUCTION TUNING ===");
+        let chat_training_examples: Vec<&str> = dataset
+            .chat_training_data
+            .iter()
+            .map(|s| s.as_str())
+            .collect();
+
+        info!(
+            "Instruction tuning on {} examples for {} epochs with learning rate {}",
+            dataset.chat_training_data.len(),
             config.training.finetuning_epochs,
-            config.training.finetuning_lr,
-            Some(&pb),
+            config.training.finetuning_lr
         );
-        pb.finish_with_message("✓ Instruction tuning complete");
+
+        if args.visualize {
+            llm::training_ui::train_with_dashboard(
+                &mut llm,
+                chat_training_examples.clone(),
+                config.training.finetuning_epochs,
+                config.training.finetuning_lr,
+                "Instruction Tuning",
+            )?;
+        } else {
+            let mut pb = config
+                .output
+                .progress_bar(config.training.finetuning_epochs as u64)?;
+            let report = llm.train_with_progress(
+                chat_training_examples.clone(),
+                config.training.finetuning_epochs,
+                config.training.finetuning_lr,
+                &mut pb,
+            );
+            pb.finish_with_message("✓ Instruction tuning complete");
+            log_tracked_examples(&report);
+        }
     }
 
     println!("\n=== AFTER TRAINING ===");
@@ -242,29 +369,59 @@ fn main() -> LlmResult<()> {
 
     info!("Training completed successfully");
 
+    if let Some(path) = &args.test_data {
+        let test_data = Dataset::from_many(std::slice::from_ref(path), data_type)?;
+        info!("Evaluating on {} test examples", test_data.len());
+        println!("\n=== TEST SET EVALUATION ===");
+        println!("{}", llm.evaluate(&test_data));
+    }
+
+    if let Some(path) = &args.save_model {
+        llm.save_pretrained(path, &config)?;
+        println!("Saved model to {}", path.display());
+    }
+
+    run_interactive(&mut llm, &args.history_file)
+}
+
+/// Chat with the model until the user exits, keeping prompt history in `history_file`.
+fn run_interactive(llm: &mut LLM, history_file: &Path) -> LlmResult<()> {
     // Interactive mode
     println!("\n--- Interactive Mode ---");
     println!("Type a prompt and press Enter to generate text.");
-    println!("Type 'exit' to quit.");
+    println!("Type 'exit' or 'quit' to quit. Use the arrow keys to recall earlier prompts.");
+    println!("Type '/regen' to answer the previous prompt again or '/last' to reprint it.");
+    println!("Earlier turns are kept as context; type '/reset' to start a new conversation.");
     info!("Entering interactive mode");
 
-    let mut input = String::new();
+    let mut editor = DefaultEditor::new()
+        .map_err(|e| llm::LlmError::Other(format!("Failed to start line editor: {}", e)))?;
+    if let Err(e) = llm::repl::load_history(editor.history_mut(), history_file) {
+        tracing::warn!("{}", e);
+    }
+
+    // End the assistant's turn rather than letting it write the next user message
+    let generation_config = GenerationConfig {
+        user_prefix: Some(USER_PREFIX.to_string()),
+        ..Default::default()
+    };
+    let mut state = llm::repl::ReplState::default();
     loop {
-        input.clear();
-        print!("\nEnter prompt: ");
-        std::io::stdout().flush().unwrap();
-
-        if std::io::stdin()
-            .read_line(&mut input)
-            .map_err(|e| llm::LlmError::Other(format!("Failed to read input: {}", e)))?
-            == 0
-        {
-            info!("EOF reached, exiting");
-            break;
-        }
+        let input = match editor.readline("\nEnter prompt: ") {
+            Ok(line) => line,
+            Err(ReadlineError::Eof | ReadlineError::Interrupted) => {
+                info!("EOF reached, exiting");
+                break;
+            }
+            Err(e) => return Err(llm::LlmError::Other(format!("Failed to read input: {}", e))),
+        };
 
         let trimmed_input = input.trim();
-        if trimmed_input.eq_ignore_ascii_case("exit") {
+        if !trimmed_input.is_empty() {
+            let _ = editor.add_history_entry(trimmed_input);
+        }
+        if trimmed_input.eq_ignore_ascii_case("exit") || trimmed_input.eq_ignore_ascii_case("quit")
+        {
             info!("User requested exit");
             println!("Exiting interactive mode.");
             break;
@@ -273,13 +430,86 @@ fn main() -> LlmResult<()> {
         if trimmed_input.is_empty() {
             continue;
         }
+        if trimmed_input == "/reset" {
+            state.reset();
+            println!("Conversation cleared.");
+            continue;
+        }
+        if trimmed_input == "/last" {
+            match state.last_response() {
+                Ok(response) => println!("Model output: {}", response),
+                Err(e) => println!("{}", e),
+            }
+            continue;
+        }
+        let trimmed_input = if trimmed_input == "/regen" {
+            match state.regen_prompt() {
+                Ok(prompt) => {
+                    let prompt = prompt.to_string();
+                    state.drop_last_turn();
+                    prompt
+                }
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            }
+        } else {
+            trimmed_input.to_string()
+        };
+        let trimmed_input = trimmed_input.as_str();
+
+        // Grow the vocabulary with unseen prompt words so they are not dropped
+        let mut prompt_words = std::collections::HashSet::new();
+        let normalized_input = llm
+            .normalizer
+            .as_ref()
+            .map_or_else(|| trimmed_input.to_string(), |n| n.normalize(trimmed_input));
+        Vocab::process_text_for_vocab(&[normalized_input], &mut prompt_words);
+        let mut new_words: Vec<String> = prompt_words
+            .into_iter()
+            .filter(|word| !llm.vocab.contains(word))
+            .collect();
+        new_words.sort();
+        for word in &new_words {
+            llm.expand_vocab(word);
+        }
+        if !new_words.is_empty() {
+            info!("Added {} new tokens to the vocabulary", new_words.len());
+        }
 
-        let formatted_input = format!("User: {}", trimmed_input);
+        // Feed back earlier turns, leaving half the context window for the reply
+        let formatted_input =
+            state.context(trimmed_input, USER_PREFIX, llm::MAX_SEQ_LEN / 2, |text| {
+                llm.tokenize(text).len()
+            });
         info!("Generating prediction for: {}", formatted_input);
-        let prediction = llm.predict(&formatted_input);
+        let prediction = llm.generate(&formatted_input, &generation_config);
         println!("Model output: {}", prediction);
+        state.record(trimmed_input, &prediction);
+    }
+
+    if let Err(e) = llm::repl::save_history(editor.history_mut(), history_file) {
+        tracing::warn!("{}", e);
     }
 
     info!("RustGPT shutdown complete");
     Ok(())
 }
+
+/// Log the hardest and easiest examples of the final epoch, if tracking is enabled.
+fn log_tracked_examples(report: &TrainingReport) {
+    let epoch = report.epochs.len();
+    for example in report.hardest_examples(epoch) {
+        info!(
+            "Hardest example #{} (loss {:.4}): {}",
+            example.index, example.loss, example.text
+        );
+    }
+    for example in report.easiest_examples(epoch) {
+        info!(
+            "Easiest example #{} (loss {:.4}): {}",
+            example.index, example.loss, example.text
+        );
+    }
+}
@@ -2,6 +2,7 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
+use std::fmt;
 
 /// Training metrics tracker.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +17,63 @@ pub struct Metrics {
     learning_rates: VecDeque<f32>,
     /// Maximum window size
     window_size: usize,
+    /// Micro-steps whose gradients are accumulated into one optimizer update
+    #[serde(default = "default_one")]
+    accumulation_steps: usize,
+    /// Samples per micro-step
+    #[serde(default = "default_one")]
+    batch_size: usize,
+    /// Optimizer updates recorded so far, including ones that left the window
+    #[serde(default)]
+    update_steps: usize,
+    /// Micro-steps recorded so far
+    #[serde(default)]
+    micro_steps: usize,
+    /// Micro-steps of the update in progress
+    #[serde(default)]
+    pending: MicroSteps,
+}
+
+fn default_one() -> usize {
+    1
+}
+
+/// Running sums over the micro-steps of one optimizer update.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct MicroSteps {
+    count: usize,
+    loss: f32,
+    accuracy: f32,
+    grad_norm: f32,
+    lr: f32,
+}
+
+/// Snapshot of the headline numbers in a [`Metrics`] window.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSummary {
+    /// Average loss over the window
+    pub avg_loss: f32,
+    /// Most recent loss, if any was recorded
+    pub latest_loss: Option<f32>,
+    /// Perplexity implied by the average loss
+    pub perplexity: f32,
+    /// Average accuracy over the window
+    pub avg_accuracy: f32,
+    /// Average gradient norm over the window
+    pub avg_gradient_norm: f32,
+}
+
+impl fmt::Display for MetricsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Avg Loss: {:.4}", self.avg_loss)?;
+        match self.latest_loss {
+            Some(loss) => writeln!(f, "Latest Loss: {:.4}", loss)?,
+            None => writeln!(f, "Latest Loss: n/a")?,
+        }
+        writeln!(f, "Perplexity: {:.2}", self.perplexity)?;
+        writeln!(f, "Avg Accuracy: {:.2}%", self.avg_accuracy * 100.0)?;
+        write!(f, "Avg Gradient Norm: {:.4}", self.avg_gradient_norm)
+    }
 }
 
 impl Default for Metrics {
@@ -33,9 +91,39 @@ impl Metrics {
             gradient_norms: VecDeque::with_capacity(window_size),
             learning_rates: VecDeque::with_capacity(window_size),
             window_size,
+            accumulation_steps: 1,
+            batch_size: 1,
+            update_steps: 0,
+            micro_steps: 0,
+            pending: MicroSteps::default(),
         }
     }
 
+    /// Count one step per optimizer update that accumulates gradients over
+    /// `accumulation_steps` micro-steps of `batch_size` samples each; see
+    /// [`Metrics::record_micro_step`]. Zeros are treated as 1.
+    pub fn with_accumulation(mut self, accumulation_steps: usize, batch_size: usize) -> Self {
+        self.accumulation_steps = accumulation_steps.max(1);
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Samples contributing to each optimizer update: the batch size times the number of
+    /// accumulated micro-steps.
+    pub fn effective_batch_size(&self) -> usize {
+        self.batch_size * self.accumulation_steps
+    }
+
+    /// Optimizer updates recorded so far, including ones no longer in the window.
+    pub fn update_steps(&self) -> usize {
+        self.update_steps
+    }
+
+    /// Micro-steps recorded with [`Metrics::record_micro_step`] so far.
+    pub fn micro_steps(&self) -> usize {
+        self.micro_steps
+    }
+
     /// Record a loss value.
     pub fn record_loss(&mut self, loss: f32) {
         self.losses.push_back(loss);
@@ -68,6 +156,51 @@ impl Metrics {
         }
     }
 
+    /// Record all four series for one optimizer update, keeping them aligned row for
+    /// row.
+    pub fn record_step(&mut self, loss: f32, accuracy: f32, grad_norm: f32, lr: f32) {
+        self.record_loss(loss);
+        self.record_accuracy(accuracy);
+        self.record_gradient_norm(grad_norm);
+        self.record_learning_rate(lr);
+        self.update_steps += 1;
+    }
+
+    /// Record one micro-step under gradient accumulation.
+    ///
+    /// Every `accumulation_steps` micro-steps are recorded as a single
+    /// [`Metrics::record_step`] of their averages (the last learning rate), so the step
+    /// column counts optimizer updates. Call [`Metrics::flush_micro_steps`] at the end of
+    /// an epoch to record a trailing partial update.
+    pub fn record_micro_step(&mut self, loss: f32, accuracy: f32, grad_norm: f32, lr: f32) {
+        self.micro_steps += 1;
+        let pending = &mut self.pending;
+        pending.count += 1;
+        pending.loss += loss;
+        pending.accuracy += accuracy;
+        pending.grad_norm += grad_norm;
+        pending.lr = lr;
+        if pending.count >= self.accumulation_steps {
+            self.flush_micro_steps();
+        }
+    }
+
+    /// Record the micro-steps of an unfinished update as one step; does nothing if there
+    /// are none.
+    pub fn flush_micro_steps(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        if pending.count == 0 {
+            return;
+        }
+        let count = pending.count as f32;
+        self.record_step(
+            pending.loss / count,
+            pending.accuracy / count,
+            pending.grad_norm / count,
+            pending.lr,
+        );
+    }
+
     /// Get average loss over the window.
     pub fn avg_loss(&self) -> f32 {
         if self.losses.is_empty() {
@@ -105,6 +238,21 @@ impl Metrics {
         self.accuracies.back().copied()
     }
 
+    /// Recorded losses in the window, oldest first.
+    pub fn losses(&self) -> impl Iterator<Item = f32> + '_ {
+        self.losses.iter().copied()
+    }
+
+    /// Recorded accuracies in the window, oldest first.
+    pub fn accuracies(&self) -> impl Iterator<Item = f32> + '_ {
+        self.accuracies.iter().copied()
+    }
+
+    /// Recorded gradient norms in the window, oldest first.
+    pub fn gradient_norms(&self) -> impl Iterator<Item = f32> + '_ {
+        self.gradient_norms.iter().copied()
+    }
+
     /// Get loss trend (true = increasing, false = decreasing).
     pub fn loss_trend(&self) -> Option<bool> {
         if self.losses.len() < 2 {
@@ -116,12 +264,27 @@ impl Metrics {
         Some(recent_avg > old_avg)
     }
 
+    /// Summarize the current window.
+    pub fn summary(&self) -> MetricsSummary {
+        let avg_loss = self.avg_loss();
+        MetricsSummary {
+            avg_loss,
+            latest_loss: self.latest_loss(),
+            perplexity: avg_loss.exp(),
+            avg_accuracy: self.avg_accuracy(),
+            avg_gradient_norm: self.avg_gradient_norm(),
+        }
+    }
+
     /// Export metrics as JSON.
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string_pretty(&self)
     }
 
     /// Export metrics to CSV format.
+    ///
+    /// The step column numbers optimizer updates from the start of training, so it keeps
+    /// counting once older rows have left the window.
     pub fn to_csv(&self) -> String {
         let mut csv = String::from("step,loss,accuracy,gradient_norm,learning_rate\n");
 
@@ -129,12 +292,14 @@ impl Metrics {
             .losses
             .len()
             .max(self.accuracies.len())
-            .max(self.gradient_norms.len());
+            .max(self.gradient_norms.len())
+            .max(self.learning_rates.len());
+        let first_step = self.update_steps.saturating_sub(max_len);
 
         for i in 0..max_len {
             csv.push_str(&format!(
                 "{},{},{},{},{}\n",
-                i,
+                first_step + i,
                 self.losses
                     .get(i)
                     .map(|v| v.to_string())
@@ -156,12 +321,64 @@ impl Metrics {
         csv
     }
 
+    /// Append another tracker's histories after this one's, dropping the oldest values
+    /// once the window is full. Its updates and micro-steps are counted after this one's,
+    /// so [`Metrics::to_csv`] keeps numbering steps from the start of training.
+    pub fn extend(&mut self, other: &Metrics) {
+        for &loss in &other.losses {
+            self.record_loss(loss);
+        }
+        for &accuracy in &other.accuracies {
+            self.record_accuracy(accuracy);
+        }
+        for &norm in &other.gradient_norms {
+            self.record_gradient_norm(norm);
+        }
+        for &lr in &other.learning_rates {
+            self.record_learning_rate(lr);
+        }
+        self.update_steps += other.update_steps;
+        self.micro_steps += other.micro_steps;
+    }
+
+    /// Average several trackers element-wise, aligning series by step.
+    ///
+    /// Each step averages over the trackers that recorded it, so shorter runs do not drag
+    /// later steps toward zero. The result uses the largest window of the inputs.
+    pub fn merge_averaged(metrics: &[Metrics]) -> Metrics {
+        let window_size = metrics.iter().map(|m| m.window_size).max().unwrap_or(100);
+        let average = |series: fn(&Metrics) -> &VecDeque<f32>| -> VecDeque<f32> {
+            let len = metrics.iter().map(|m| series(m).len()).max().unwrap_or(0);
+            (0..len)
+                .map(|i| {
+                    let values: Vec<f32> = metrics
+                        .iter()
+                        .filter_map(|m| series(m).get(i))
+                        .copied()
+                        .collect();
+                    values.iter().sum::<f32>() / values.len() as f32
+                })
+                .collect()
+        };
+
+        Metrics {
+            losses: average(|m| &m.losses),
+            accuracies: average(|m| &m.accuracies),
+            gradient_norms: average(|m| &m.gradient_norms),
+            learning_rates: average(|m| &m.learning_rates),
+            ..Metrics::new(window_size)
+        }
+    }
+
     /// Clear all metrics.
     pub fn clear(&mut self) {
         self.losses.clear();
         self.accuracies.clear();
         self.gradient_norms.clear();
         self.learning_rates.clear();
+        self.update_steps = 0;
+        self.micro_steps = 0;
+        self.pending = MicroSteps::default();
     }
 }
 
@@ -181,6 +398,124 @@ mod tests {
         assert!((metrics.avg_loss() - avg).abs() < 0.01);
     }
 
+    #[test]
+    fn test_record_step_keeps_series_aligned() {
+        let mut metrics = Metrics::new(10);
+        for step in 0..5 {
+            let step = step as f32;
+            metrics.record_step(2.0 - step * 0.1, step * 0.1, 0.5, 0.01);
+        }
+
+        let lens = [
+            metrics.losses.len(),
+            metrics.accuracies.len(),
+            metrics.gradient_norms.len(),
+            metrics.learning_rates.len(),
+        ];
+        assert_eq!(lens, [5; 4]);
+
+        let csv = metrics.to_csv();
+        let rows: Vec<&str> = csv.lines().skip(1).collect();
+        assert_eq!(rows.len(), 5);
+        for row in rows {
+            let fields: Vec<&str> = row.split(',').collect();
+            assert_eq!(fields.len(), 5);
+            assert!(fields.iter().all(|field| !field.is_empty()), "{}", row);
+        }
+    }
+
+    #[test]
+    fn test_accumulation_counts_optimizer_updates() {
+        let mut metrics = Metrics::new(100).with_accumulation(4, 8);
+        assert_eq!(metrics.effective_batch_size(), 32);
+
+        // One epoch of 32 micro-steps
+        for step in 0..32 {
+            metrics.record_micro_step(1.0 + step as f32, 0.5, 1.0, 0.01);
+        }
+        assert_eq!(metrics.micro_steps(), 32);
+        assert_eq!(metrics.update_steps(), metrics.micro_steps() / 4);
+        assert_eq!(metrics.losses.len(), 8);
+        // The first update averages micro-steps 0..4
+        assert_eq!(metrics.losses[0], 2.5);
+
+        let csv = metrics.to_csv();
+        let last_row = csv.lines().last().unwrap();
+        assert!(last_row.starts_with("7,"), "{}", last_row);
+
+        // A trailing partial update is recorded on flush
+        metrics.record_micro_step(1.0, 0.5, 1.0, 0.01);
+        assert_eq!(metrics.update_steps(), 8);
+        metrics.flush_micro_steps();
+        assert_eq!(metrics.update_steps(), 9);
+        metrics.flush_micro_steps();
+        assert_eq!(metrics.update_steps(), 9);
+    }
+
+    #[test]
+    fn test_merge_averaged() {
+        let mut a = Metrics::new(10);
+        a.record_loss(1.0);
+        a.record_accuracy(0.4);
+        let mut b = Metrics::new(10);
+        b.record_loss(3.0);
+        b.record_accuracy(0.8);
+        b.record_loss(5.0);
+
+        let merged = Metrics::merge_averaged(&[a, b]);
+        assert_eq!(merged.losses, VecDeque::from(vec![2.0, 5.0]));
+        assert!((merged.latest_accuracy().unwrap() - 0.6).abs() < 1e-6);
+        assert!(Metrics::merge_averaged(&[]).latest_loss().is_none());
+    }
+
+    #[test]
+    fn test_extend_respects_window() {
+        let mut a = Metrics::new(3);
+        a.record_step(1.0, 0.5, 1.0, 0.1);
+        a.record_step(2.0, 0.5, 1.0, 0.1);
+        let mut b = Metrics::new(10);
+        for loss in [3.0, 4.0] {
+            b.record_step(loss, 0.5, 1.0, 0.1);
+        }
+
+        a.extend(&b);
+        assert_eq!(a.losses, VecDeque::from(vec![2.0, 3.0, 4.0]));
+
+        // Steps keep counting across the extend: the oldest kept row is update 1
+        assert_eq!(a.update_steps(), 4);
+        let csv = a.to_csv();
+        let steps: Vec<&str> = csv
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').next().unwrap())
+            .collect();
+        assert_eq!(steps, vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn test_summary_matches_averages() {
+        let mut metrics = Metrics::new(10);
+        for (loss, accuracy, norm) in [(2.0, 0.5, 1.0), (1.0, 0.7, 3.0)] {
+            metrics.record_loss(loss);
+            metrics.record_accuracy(accuracy);
+            metrics.record_gradient_norm(norm);
+        }
+
+        let summary = metrics.summary();
+        assert_eq!(summary.avg_loss, metrics.avg_loss());
+        assert_eq!(summary.latest_loss, Some(1.0));
+        assert!((summary.perplexity - 1.5f32.exp()).abs() < 1e-5);
+        assert!((summary.avg_accuracy - 0.6).abs() < 1e-6);
+        assert_eq!(summary.avg_gradient_norm, 2.0);
+
+        let text = summary.to_string();
+        assert!(text.contains("Perplexity: 4.48"));
+        assert!(Metrics::new(10)
+            .summary()
+            .to_string()
+            .contains("Latest Loss: n/a"));
+    }
+
     #[test]
     fn test_csv_export() {
         let mut metrics = Metrics::new(10);
@@ -0,0 +1,68 @@
+//! Text normalization applied before tokenization.
+//!
+//! The same normalizer must be used when loading training data and when tokenizing at
+//! inference time, otherwise equivalent inputs map to different tokens.
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+/// Normalizes text to a canonical form: Unicode NFC, collapsed whitespace and,
+/// optionally, lowercase.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextNormalizer {
+    /// Lowercase the text after normalization
+    pub lowercase: bool,
+}
+
+impl TextNormalizer {
+    /// Create a normalizer.
+    pub fn new(lowercase: bool) -> Self {
+        Self { lowercase }
+    }
+
+    /// Normalize a string.
+    pub fn normalize(&self, s: &str) -> String {
+        let composed: String = s.nfc().collect();
+        let collapsed = composed.split_whitespace().collect::<Vec<_>>().join(" ");
+        if self.lowercase {
+            collapsed.to_lowercase()
+        } else {
+            collapsed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collapses_whitespace() {
+        let normalizer = TextNormalizer::default();
+        assert_eq!(
+            normalizer.normalize("  hello   world\t\nagain "),
+            "hello world again"
+        );
+    }
+
+    #[test]
+    fn test_composes_unicode() {
+        let normalizer = TextNormalizer::default();
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        assert_ne!(composed, decomposed);
+        assert_eq!(normalizer.normalize(decomposed), composed);
+    }
+
+    #[test]
+    fn test_optional_lowercase() {
+        assert_eq!(
+            TextNormalizer::new(true).normalize("Hello World"),
+            "hello world"
+        );
+        assert_eq!(
+            TextNormalizer::new(false).normalize("Hello World"),
+            "Hello World"
+        );
+    }
+}
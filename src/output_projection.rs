@@ -1,8 +1,12 @@
 use ndarray::{Array2, Axis};
 use rand_distr::{Distribution, Normal};
 
-use crate::{adam::Adam, llm::Layer};
+use crate::{
+    adam::Adam,
+    llm::{clip_scale, Layer},
+};
 
+#[derive(Clone)]
 pub struct OutputProjection {
     pub w_out: Array2<f32>, // Weight matrix
     pub b_out: Array2<f32>, // Bias vector
@@ -13,7 +17,7 @@ pub struct OutputProjection {
 impl OutputProjection {
     /// Initialize output layer with random weights and zero bias
     pub fn new(embedding_dim: usize, vocab_size: usize) -> Self {
-        let mut rng = rand::rng();
+        let mut rng = crate::rng::init_rng();
         // Xavier/He initialization: std = sqrt(2 / fan_in)
         let std = (2.0 / embedding_dim as f32).sqrt();
         let normal = Normal::new(0.0, std).unwrap();
@@ -40,10 +44,23 @@ impl Layer for OutputProjection {
     }
 
     fn backward(&mut self, grads: &Array2<f32>, lr: f32) -> Array2<f32> {
+        self.backward_clipped(grads, lr, None)
+    }
+
+    fn backward_clipped(
+        &mut self,
+        grads: &Array2<f32>,
+        lr: f32,
+        max_norm: Option<f32>,
+    ) -> Array2<f32> {
         // grads shape is [sequence_length, vocab_size]
         let input = self.cached_input.as_ref().unwrap();
-        let grad_w_out = input.t().dot(grads);
-        let grad_b_out = grads.mean_axis(Axis(0)).unwrap();
+        let mut grad_w_out = input.t().dot(grads);
+        let mut grad_b_out = grads.mean_axis(Axis(0)).unwrap();
+        if let Some(scale) = clip_scale(grad_w_out.iter().chain(&grad_b_out), max_norm) {
+            grad_w_out *= scale;
+            grad_b_out *= scale;
+        }
 
         let grad_input = grads.dot(&self.w_out.t());
 
@@ -56,4 +73,68 @@ impl Layer for OutputProjection {
     fn parameters(&self) -> usize {
         self.w_out.len() + self.b_out.len()
     }
+
+    fn weights(&self) -> Vec<&Array2<f32>> {
+        vec![&self.w_out, &self.b_out]
+    }
+
+    fn weights_mut(&mut self) -> Vec<&mut Array2<f32>> {
+        vec![&mut self.w_out, &mut self.b_out]
+    }
+
+    fn reinitialize(&mut self) {
+        let (embedding_dim, vocab_size) = self.w_out.dim();
+        *self = OutputProjection::new(embedding_dim, vocab_size);
+    }
+
+    fn resize_vocab(&mut self, vocab_size: usize) {
+        let current = self.w_out.ncols();
+        if vocab_size <= current {
+            return;
+        }
+
+        let embedding_dim = self.w_out.nrows();
+        let added = vocab_size - current;
+        let mut rng = crate::rng::init_rng();
+        let std = (2.0 / embedding_dim as f32).sqrt();
+        let normal = Normal::new(0.0, std).unwrap();
+        let new_columns =
+            Array2::from_shape_fn((embedding_dim, added), |_| normal.sample(&mut rng));
+
+        self.w_out = ndarray::concatenate![Axis(1), self.w_out, new_columns];
+        self.b_out = ndarray::concatenate![Axis(1), self.b_out, Array2::zeros((1, added))];
+
+        // Keep the optimizer moments aligned with the grown matrix
+        let zeros = Array2::<f32>::zeros((embedding_dim, added));
+        self.optimizer.m = ndarray::concatenate![Axis(1), self.optimizer.m, zeros];
+        self.optimizer.v = ndarray::concatenate![Axis(1), self.optimizer.v, zeros];
+    }
+
+    fn remap_vocab(&mut self, old_ids: &[Option<usize>]) {
+        let embedding_dim = self.w_out.nrows();
+        let mut remapped = OutputProjection::new(embedding_dim, old_ids.len());
+        for (id, old_id) in old_ids.iter().enumerate() {
+            if let Some(old_id) = old_id.filter(|&old| old < self.w_out.ncols()) {
+                remapped
+                    .w_out
+                    .column_mut(id)
+                    .assign(&self.w_out.column(old_id));
+                remapped
+                    .b_out
+                    .column_mut(id)
+                    .assign(&self.b_out.column(old_id));
+            }
+        }
+        self.w_out = remapped.w_out;
+        self.b_out = remapped.b_out;
+        self.optimizer = remapped.optimizer;
+    }
+
+    fn input_dim(&self) -> Option<usize> {
+        Some(self.w_out.nrows())
+    }
+
+    fn output_dim(&self) -> Option<usize> {
+        Some(self.w_out.ncols())
+    }
 }
@@ -0,0 +1,110 @@
+//! Training progress reporting.
+//!
+//! The training loops report through the [`ProgressReporter`] trait, so the same loop can
+//! drive a terminal progress bar, plain log lines, machine-readable JSON, or nothing.
+
+use std::io::Write;
+
+/// Receives training progress events. Epochs are numbered from 1.
+pub trait ProgressReporter {
+    /// An epoch is about to start.
+    fn on_epoch_start(&mut self, _epoch: usize) {}
+
+    /// An epoch finished with average loss `loss`.
+    fn on_epoch_end(&mut self, epoch: usize, loss: f32);
+
+    /// The training run is over.
+    fn finish(&mut self) {}
+}
+
+/// Ignores every event.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressReporter for NoProgress {
+    fn on_epoch_end(&mut self, _epoch: usize, _loss: f32) {}
+}
+
+/// Prints `Epoch N: Loss = x` to stdout after every epoch; what [`crate::LLM::train`]
+/// uses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrintProgress;
+
+impl ProgressReporter for PrintProgress {
+    fn on_epoch_end(&mut self, epoch: usize, loss: f32) {
+        println!("Epoch {}: Loss = {:.4}", epoch, loss);
+    }
+}
+
+/// Advances the bar by one per epoch and shows the latest loss as its message. The bar
+/// is left for the caller to finish with its own message.
+impl ProgressReporter for indicatif::ProgressBar {
+    fn on_epoch_end(&mut self, epoch: usize, loss: f32) {
+        self.set_message(format!("Epoch {}: Loss = {:.4}", epoch, loss));
+        self.inc(1);
+    }
+}
+
+/// Writes one JSON object per event, e.g. `{"event":"epoch_end","epoch":1,"loss":2.5}`,
+/// for consumption by other tools.
+#[derive(Debug)]
+pub struct JsonLinesProgress<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonLinesProgress<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// The underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn emit(&mut self, event: serde_json::Value) {
+        if let Err(e) = writeln!(self.writer, "{}", event) {
+            tracing::warn!("Failed to write progress event: {}", e);
+        }
+    }
+}
+
+impl<W: Write> ProgressReporter for JsonLinesProgress<W> {
+    fn on_epoch_start(&mut self, epoch: usize) {
+        self.emit(serde_json::json!({ "event": "epoch_start", "epoch": epoch }));
+    }
+
+    fn on_epoch_end(&mut self, epoch: usize, loss: f32) {
+        self.emit(serde_json::json!({ "event": "epoch_end", "epoch": epoch, "loss": loss }));
+    }
+
+    fn finish(&mut self) {
+        self.emit(serde_json::json!({ "event": "finish" }));
+        if let Err(e) = self.writer.flush() {
+            tracing::warn!("Failed to flush progress events: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_lines_progress_writes_one_event_per_line() {
+        let mut progress = JsonLinesProgress::new(Vec::new());
+        progress.on_epoch_start(1);
+        progress.on_epoch_end(1, 2.5);
+        progress.finish();
+
+        let output = String::from_utf8(progress.into_inner()).unwrap();
+        let events: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0]["event"], "epoch_start");
+        assert_eq!(events[1]["loss"], 2.5);
+        assert_eq!(events[2]["event"], "finish");
+    }
+}
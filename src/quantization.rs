@@ -0,0 +1,212 @@
+//! Post-training int8 quantization for compact model files.
+//!
+//! Each weight matrix is stored as `i8` values with one symmetric scale per tensor, so a
+//! value `q` stands for `q * scale`. Shapes and the vocabulary are kept, and a quantized
+//! model loads back into an [`LLM`] of the same architecture.
+
+use crate::checkpoint::Checkpoint;
+use crate::error::{LlmError, Result};
+use crate::llm::LLM;
+use bincode::{Decode, Encode};
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A weight matrix quantized to int8 with a per-tensor scale.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct QuantizedTensor {
+    /// Shape of the original matrix
+    pub shape: (usize, usize),
+    /// Value of one quantization step; the largest magnitude maps to 127
+    pub scale: f32,
+    /// Quantized values in row-major order
+    pub values: Vec<i8>,
+}
+
+impl QuantizedTensor {
+    /// Quantize `matrix` symmetrically around zero.
+    pub fn quantize(matrix: &Array2<f32>) -> Self {
+        let max_abs = matrix.iter().fold(0.0f32, |max, &x| max.max(x.abs()));
+        let scale = if max_abs > 0.0 { max_abs / 127.0 } else { 1.0 };
+        Self {
+            shape: matrix.dim(),
+            scale,
+            values: matrix
+                .iter()
+                .map(|&x| (x / scale).round().clamp(-127.0, 127.0) as i8)
+                .collect(),
+        }
+    }
+
+    /// Reconstruct the matrix; each entry is within `scale / 2` of the original.
+    ///
+    /// # Panics
+    /// If `values` does not fill `shape`, which [`QuantizedModel::load`] rules out for
+    /// loaded models.
+    pub fn dequantize(&self) -> Array2<f32> {
+        let values = self.values.iter().map(|&q| q as f32 * self.scale).collect();
+        Array2::from_shape_vec(self.shape, values).expect("quantized values match their shape")
+    }
+}
+
+/// An int8 copy of a model's weights and vocabulary, without optimizer state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct QuantizedModel {
+    /// Vocabulary the weights were trained with, in id order
+    pub vocab: Vec<String>,
+    /// Every layer's weights, in [`crate::Layer::weights`] order
+    pub tensors: Vec<QuantizedTensor>,
+}
+
+impl QuantizedModel {
+    /// Dequantized weights, in the order they were quantized.
+    pub fn dequantize(&self) -> Vec<Array2<f32>> {
+        self.tensors
+            .iter()
+            .map(QuantizedTensor::dequantize)
+            .collect()
+    }
+
+    /// A checkpoint holding the dequantized weights, loadable with
+    /// [`LLM::load_checkpoint`].
+    pub fn to_checkpoint(&self) -> Checkpoint {
+        let mut checkpoint = Checkpoint::new(0, 0.0, "int8");
+        for weight in self.dequantize() {
+            checkpoint.add_parameter(&weight);
+        }
+        checkpoint.vocab = self.vocab.clone();
+        checkpoint
+    }
+
+    /// Load the dequantized weights into `llm` for inference.
+    ///
+    /// # Errors
+    /// Returns `ShapeMismatch` if `llm` has a different architecture.
+    pub fn load_into(&self, llm: &mut LLM) -> Result<()> {
+        llm.load_checkpoint(&self.to_checkpoint()).map(|_| ())
+    }
+
+    /// Save the quantized model to file.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let serialized =
+            bincode::encode_to_vec(self, bincode::config::standard()).map_err(|e| {
+                LlmError::serialization(format!("Failed to serialize quantized model: {}", e))
+            })?;
+        std::fs::write(path, serialized).map_err(LlmError::IoError)?;
+        tracing::info!("Quantized model saved to {:?}", path);
+        Ok(())
+    }
+
+    /// Load a quantized model from file.
+    ///
+    /// # Errors
+    /// Returns `SerializationError` if the file is corrupt, including a tensor whose
+    /// values do not fill its shape.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).map_err(LlmError::IoError)?;
+        let (model, _): (Self, _) = bincode::decode_from_slice(&data, bincode::config::standard())
+            .map_err(|e| {
+                LlmError::serialization(format!("Failed to deserialize quantized model: {}", e))
+            })?;
+        for (index, tensor) in model.tensors.iter().enumerate() {
+            let (rows, cols) = tensor.shape;
+            if rows.checked_mul(cols) != Some(tensor.values.len()) {
+                return Err(LlmError::serialization(format!(
+                    "Quantized tensor {} in {:?} has {} values for shape {}x{}",
+                    index,
+                    path,
+                    tensor.values.len(),
+                    rows,
+                    cols
+                )));
+            }
+        }
+        Ok(model)
+    }
+}
+
+impl LLM {
+    /// Quantize every layer's weights to int8 with per-tensor scales.
+    pub fn quantize_int8(&self) -> QuantizedModel {
+        QuantizedModel {
+            vocab: self.vocab.words.clone(),
+            tensors: self
+                .network
+                .iter()
+                .flat_map(|layer| layer.weights())
+                .map(QuantizedTensor::quantize)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dequantize_within_half_step() {
+        let matrix = Array2::from_shape_fn((4, 6), |(i, j)| ((i * 6 + j) as f32 * 0.7).sin() * 3.0);
+        let quantized = QuantizedTensor::quantize(&matrix);
+        assert_eq!(quantized.shape, (4, 6));
+        assert_eq!(quantized.values.iter().map(|q| q.abs()).max(), Some(127));
+
+        let restored = quantized.dequantize();
+        for (original, restored) in matrix.iter().zip(restored.iter()) {
+            assert!((original - restored).abs() <= quantized.scale / 2.0 + 1e-6);
+        }
+
+        let zeros = QuantizedTensor::quantize(&Array2::zeros((2, 2)));
+        assert_eq!(zeros.dequantize(), Array2::<f32>::zeros((2, 2)));
+    }
+
+    #[test]
+    fn test_quantized_model_is_a_quarter_the_size() {
+        let mut llm = LLM::default();
+        let quantized = llm.quantize_int8();
+
+        let config = bincode::config::standard();
+        let full = bincode::encode_to_vec(llm.to_checkpoint(0, 0.0), config).unwrap();
+        let compact = bincode::encode_to_vec(&quantized, config).unwrap();
+        let ratio = compact.len() as f32 / full.len() as f32;
+        assert!((0.2..0.3).contains(&ratio), "ratio {}", ratio);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.int8");
+        quantized.save(&path).unwrap();
+        let loaded = QuantizedModel::load(&path).unwrap();
+        assert_eq!(loaded, quantized);
+
+        // Shapes survive and every weight is within its tensor's error bound
+        let original: Vec<Array2<f32>> = llm
+            .network
+            .iter()
+            .flat_map(|layer| layer.weights())
+            .cloned()
+            .collect();
+        loaded.load_into(&mut llm).unwrap();
+        let weights = llm.network.iter().flat_map(|layer| layer.weights());
+        for ((before, after), tensor) in original.iter().zip(weights).zip(&quantized.tensors) {
+            assert_eq!(before.dim(), after.dim());
+            for (a, b) in before.iter().zip(after.iter()) {
+                assert!((a - b).abs() <= tensor.scale / 2.0 + 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_load_rejects_tensor_not_matching_its_shape() {
+        let mut quantized = LLM::default().quantize_int8();
+        quantized.tensors[1].values.pop();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.int8");
+        quantized.save(&path).unwrap();
+        match QuantizedModel::load(&path) {
+            Err(LlmError::SerializationError(message)) => {
+                assert!(message.contains("tensor 1"), "{}", message);
+            }
+            other => panic!("expected SerializationError, got {:?}", other.map(|_| ())),
+        }
+    }
+}
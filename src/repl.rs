@@ -0,0 +1,181 @@
+//! Line editing helpers for the interactive prompt.
+//!
+//! Command history is kept in a file between sessions so earlier prompts can be recalled
+//! with the arrow keys. [`ReplState`] remembers the conversation so far, which is fed
+//! back as context for each new prompt, and the last exchange for the `/regen` and
+//! `/last` commands.
+
+use crate::error::{LlmError, Result};
+use rustyline::history::History;
+use std::path::Path;
+
+/// Load saved prompts from `path` into `history`.
+///
+/// A missing file is not an error: it simply means there is no history yet.
+pub fn load_history<H: History>(history: &mut H, path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    history
+        .load(path)
+        .map_err(|e| LlmError::Other(format!("Failed to load history from {:?}: {}", path, e)))
+}
+
+/// Save the prompts in `history` to `path`, replacing its previous contents.
+pub fn save_history<H: History>(history: &mut H, path: &Path) -> Result<()> {
+    history
+        .save(path)
+        .map_err(|e| LlmError::Other(format!("Failed to save history to {:?}: {}", path, e)))
+}
+
+/// The conversation and most recent exchange of an interactive session.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplState {
+    last_prompt: Option<String>,
+    last_response: Option<String>,
+    /// Completed `(prompt, response)` exchanges, oldest first
+    turns: Vec<(String, String)>,
+}
+
+impl ReplState {
+    /// Remember a completed exchange.
+    pub fn record(&mut self, prompt: &str, response: &str) {
+        self.last_prompt = Some(prompt.to_string());
+        self.last_response = Some(response.to_string());
+        self.turns.push((prompt.to_string(), response.to_string()));
+    }
+
+    /// Forget the conversation so far (`/reset`). `/regen` and `/last` still refer to
+    /// the last exchange.
+    pub fn reset(&mut self) {
+        self.turns.clear();
+    }
+
+    /// Drop the most recent exchange from the conversation, e.g. before `/regen` answers
+    /// it again.
+    pub fn drop_last_turn(&mut self) {
+        self.turns.pop();
+    }
+
+    /// Generation input for `prompt`: the earlier exchanges followed by the new user
+    /// turn, each turn starting with `user_prefix`.
+    ///
+    /// Whole exchanges are dropped oldest first until `count_tokens` of the result is at
+    /// most `max_tokens`; the new turn is always kept.
+    pub fn context(
+        &self,
+        prompt: &str,
+        user_prefix: &str,
+        max_tokens: usize,
+        count_tokens: impl Fn(&str) -> usize,
+    ) -> String {
+        let current = format!("{} {}", user_prefix, prompt);
+        let past: Vec<String> = self
+            .turns
+            .iter()
+            .map(|(prompt, response)| format!("{} {} {}", user_prefix, prompt, response))
+            .collect();
+
+        for start in 0..past.len() {
+            let mut parts = past[start..].to_vec();
+            parts.push(current.clone());
+            let context = parts.join(" ");
+            if count_tokens(&context) <= max_tokens {
+                return context;
+            }
+        }
+        current
+    }
+
+    /// The prompt `/regen` should run again.
+    ///
+    /// # Errors
+    /// Returns an error if nothing has been generated yet.
+    pub fn regen_prompt(&self) -> Result<&str> {
+        self.last_prompt
+            .as_deref()
+            .ok_or_else(|| LlmError::Other("No previous prompt to regenerate".to_string()))
+    }
+
+    /// The output `/last` should reprint.
+    ///
+    /// # Errors
+    /// Returns an error if nothing has been generated yet.
+    pub fn last_response(&self) -> Result<&str> {
+        self.last_response
+            .as_deref()
+            .ok_or_else(|| LlmError::Other("No previous output yet".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::history::FileHistory;
+
+    #[test]
+    fn test_repl_state_tracks_last_exchange() {
+        let mut state = ReplState::default();
+        assert!(state.regen_prompt().is_err());
+        assert!(state.last_response().is_err());
+
+        state.record("How do mountains form?", "tectonic plates");
+        state.record("What causes rain?", "water vapor condenses");
+        assert_eq!(state.regen_prompt().unwrap(), "What causes rain?");
+        assert_eq!(state.last_response().unwrap(), "water vapor condenses");
+    }
+
+    #[test]
+    fn test_context_concatenates_turns_and_drops_oldest() {
+        let words = |text: &str| text.split_whitespace().count();
+        let mut state = ReplState::default();
+        assert_eq!(state.context("hi", "User:", 10, words), "User: hi");
+
+        state.record("What causes rain?", "Assistant: water vapor </s>");
+        state.record("And snow?", "Assistant: cold </s>");
+        assert_eq!(
+            state.context("Thanks", "User:", 100, words),
+            "User: What causes rain? Assistant: water vapor </s> \
+             User: And snow? Assistant: cold </s> User: Thanks"
+        );
+
+        // Over the limit the oldest exchange goes first, then the next
+        assert_eq!(
+            state.context("Thanks", "User:", 9, words),
+            "User: And snow? Assistant: cold </s> User: Thanks"
+        );
+        assert_eq!(state.context("Thanks", "User:", 3, words), "User: Thanks");
+
+        state.drop_last_turn();
+        assert_eq!(
+            state.context("Thanks", "User:", 100, words),
+            "User: What causes rain? Assistant: water vapor </s> User: Thanks"
+        );
+        state.reset();
+        assert_eq!(state.context("Thanks", "User:", 100, words), "User: Thanks");
+        assert_eq!(state.regen_prompt().unwrap(), "And snow?");
+    }
+
+    #[test]
+    fn test_history_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+
+        let mut history = FileHistory::new();
+        load_history(&mut history, &path).unwrap();
+        assert_eq!(history.len(), 0);
+
+        for line in ["How do mountains form?", "What causes rain?", "exit"] {
+            history.add(line).unwrap();
+        }
+        save_history(&mut history, &path).unwrap();
+
+        let mut restored = FileHistory::new();
+        load_history(&mut restored, &path).unwrap();
+        let lines: Vec<&str> = restored.iter().map(String::as_str).collect();
+        assert_eq!(
+            lines,
+            vec!["How do mountains form?", "What causes rain?", "exit"]
+        );
+    }
+}
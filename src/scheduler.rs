@@ -0,0 +1,103 @@
+//! Learning-rate schedules driven by observed losses.
+//!
+//! Schedulers plug into [`crate::LLM::train_with_callback`] by rewriting
+//! [`crate::EpochContext::lr`] at the start of each epoch.
+
+use crate::llm::EpochContext;
+
+/// Reduce the learning rate when the loss stops improving (like PyTorch's
+/// `ReduceLROnPlateau`).
+///
+/// Every observation that does not beat the best loss so far counts toward `patience`;
+/// once `patience` such observations accumulate, the learning rate is multiplied by
+/// `factor` (never going below `min_lr`) and the count starts over.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReduceOnPlateau {
+    /// Multiplier applied to the learning rate on a plateau, e.g. 0.5 to halve it
+    pub factor: f32,
+    /// Number of non-improving observations tolerated before reducing
+    pub patience: usize,
+    /// Lower bound for the learning rate
+    pub min_lr: f32,
+    best: f32,
+    stale: usize,
+}
+
+impl ReduceOnPlateau {
+    pub fn new(factor: f32, patience: usize, min_lr: f32) -> Self {
+        Self {
+            factor,
+            patience,
+            min_lr,
+            best: f32::INFINITY,
+            stale: 0,
+        }
+    }
+
+    /// Record `loss` and return the learning rate to use from now on, given the current
+    /// one.
+    pub fn observe(&mut self, loss: f32, lr: f32) -> f32 {
+        if loss < self.best {
+            self.best = loss;
+            self.stale = 0;
+            return lr;
+        }
+
+        self.stale += 1;
+        if self.stale < self.patience.max(1) {
+            return lr;
+        }
+        self.stale = 0;
+        let reduced = (lr * self.factor).max(self.min_lr);
+        if reduced < lr {
+            tracing::info!(
+                lr = reduced as f64,
+                best_loss = self.best as f64,
+                "Loss plateaued; reducing learning rate"
+            );
+        }
+        reduced
+    }
+
+    /// Hook for [`crate::LLM::train_with_callback`]: observe the previous epoch's loss
+    /// and update the epoch's learning rate.
+    pub fn on_epoch(&mut self, ctx: &mut EpochContext) {
+        if let Some(loss) = ctx.avg_loss {
+            *ctx.lr = self.observe(loss, *ctx.lr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reduces_after_patience_and_respects_min_lr() {
+        let mut plateau = ReduceOnPlateau::new(0.5, 2, 0.0015);
+        let mut lr = 0.01;
+
+        lr = plateau.observe(1.0, lr);
+        lr = plateau.observe(0.8, lr);
+        assert_eq!(lr, 0.01);
+
+        // Two observations without improvement halve the rate
+        lr = plateau.observe(0.9, lr);
+        assert_eq!(lr, 0.01);
+        lr = plateau.observe(0.8, lr);
+        assert_eq!(lr, 0.005);
+
+        // An improvement resets the count
+        lr = plateau.observe(0.7, lr);
+        lr = plateau.observe(0.75, lr);
+        assert_eq!(lr, 0.005);
+        lr = plateau.observe(0.75, lr);
+        assert_eq!(lr, 0.0025);
+
+        // Further plateaus stop at min_lr
+        for _ in 0..6 {
+            lr = plateau.observe(0.75, lr);
+        }
+        assert_eq!(lr, 0.0015);
+    }
+}
@@ -3,10 +3,23 @@ use std::f32;
 use ndarray::Array2;
 use rand_distr::{Distribution, Normal};
 
-use crate::{adam::Adam, llm::Layer, EMBEDDING_DIM};
+use crate::{
+    adam::Adam,
+    llm::{clip_scale, Layer},
+    EMBEDDING_DIM,
+};
 
+#[derive(Clone)]
 pub struct SelfAttention {
     pub embedding_dim: usize,
+    /// Mask out future positions (disable for bidirectional attention)
+    pub causal: bool,
+    /// Factor applied to the attention output before the residual is added
+    pub residual_scale: f32,
+    /// Key positions holding padding, which no query may attend to
+    key_padding_mask: Option<Vec<bool>>,
+    /// Document id of each position; queries only attend to keys of the same document
+    document_ids: Option<Vec<usize>>,
     w_q: Array2<f32>, // Weight matrices for Q, K, V
     w_k: Array2<f32>,
     w_v: Array2<f32>,
@@ -27,13 +40,17 @@ impl Default for SelfAttention {
 impl SelfAttention {
     /// Initializes a Transformer with random Q, K, V weights
     pub fn new(embedding_dim: usize) -> Self {
-        let mut rng = rand::rng();
+        let mut rng = crate::rng::init_rng();
         // Xavier/He initialization: std = sqrt(2 / fan_in)
         let std = (2.0 / embedding_dim as f32).sqrt();
         let normal = Normal::new(0.0, std).unwrap();
 
         SelfAttention {
             embedding_dim,
+            causal: true,
+            residual_scale: 1.0,
+            key_padding_mask: None,
+            document_ids: None,
             w_q: Array2::from_shape_fn((embedding_dim, embedding_dim), |_| normal.sample(&mut rng)),
             w_k: Array2::from_shape_fn((embedding_dim, embedding_dim), |_| normal.sample(&mut rng)),
             w_v: Array2::from_shape_fn((embedding_dim, embedding_dim), |_| normal.sample(&mut rng)),
@@ -57,24 +74,80 @@ impl SelfAttention {
         let k_t = k.t();
         let mut scores = q.dot(&k_t) / dk;
 
-        // Apply causal masking - prevent attention to future tokens
-        let seq_len = scores.shape()[0];
-        for i in 0..seq_len {
-            for j in (i + 1)..seq_len {
-                scores[[i, j]] = f32::NEG_INFINITY;
-            }
-        }
+        self.apply_mask(&mut scores);
 
         let weights = self.softmax(&scores);
         weights.dot(v)
     }
 
+    /// Masked attention weights (`seq_len x seq_len`, one row per query) for `input`.
+    pub fn attention_weights(&self, input: &Array2<f32>) -> Array2<f32> {
+        let (q, k, _) = self.compute_qkv(input);
+        let mut scores = q.dot(&k.t()) / (self.embedding_dim as f32).sqrt();
+        self.apply_mask(&mut scores);
+        self.softmax(&scores)
+    }
+
+    /// Mean over queries of the entropy (in nats) of each row of attention `weights`.
+    ///
+    /// A row attending uniformly to `n` positions has entropy `ln(n)`; a one-hot row has
+    /// entropy 0.
+    pub fn mean_entropy(weights: &Array2<f32>) -> f32 {
+        if weights.nrows() == 0 {
+            return 0.0;
+        }
+        let total: f32 = weights
+            .rows()
+            .into_iter()
+            .map(|row| {
+                row.iter()
+                    .filter(|&&p| p > 0.0)
+                    .map(|&p| -p * p.ln())
+                    .sum::<f32>()
+            })
+            .sum();
+        total / weights.nrows() as f32
+    }
+
+    /// Apply causal masking - prevent attention to future tokens -, key padding
+    /// masking - prevent attention to pad positions - and document masking - prevent
+    /// attention across packed documents
+    fn apply_mask(&self, scores: &mut Array2<f32>) {
+        let seq_len = scores.shape()[0];
+        if self.causal {
+            for i in 0..seq_len {
+                for j in (i + 1)..seq_len {
+                    scores[[i, j]] = f32::NEG_INFINITY;
+                }
+            }
+        }
+        if let Some(mask) = &self.key_padding_mask {
+            for (j, _) in mask.iter().enumerate().filter(|(_, &is_pad)| is_pad) {
+                if j < scores.shape()[1] {
+                    scores.column_mut(j).fill(f32::NEG_INFINITY);
+                }
+            }
+        }
+        if let Some(documents) = &self.document_ids {
+            for ((i, j), score) in scores.indexed_iter_mut() {
+                if documents.get(i) != documents.get(j) {
+                    *score = f32::NEG_INFINITY;
+                }
+            }
+        }
+    }
+
     fn softmax(&self, scores: &Array2<f32>) -> Array2<f32> {
         let mut result = scores.clone();
 
         // Apply softmax row-wise
         for mut row in result.rows_mut() {
-            let max_val = row.iter().max_by(|a, b| a.partial_cmp(b).unwrap()).unwrap();
+            let max_val = *row.iter().max_by(|a, b| a.partial_cmp(b).unwrap()).unwrap();
+            // A query with every key masked attends to nothing
+            if max_val == f32::NEG_INFINITY {
+                row.fill(0.0);
+                continue;
+            }
             // Calculate exp for each element
             let exp_values: Vec<f32> = row.iter().map(|&x| (x - max_val).exp()).collect();
             let sum_exp: f32 = exp_values.iter().sum();
@@ -128,32 +201,36 @@ impl Layer for SelfAttention {
         self.cached_input = Some(input.clone());
         let qkv = self.compute_qkv(input);
         let attention = self.attention(&qkv.0, &qkv.1, &qkv.2);
-        attention + input // residual connection (no LayerNorm here)
+        attention * self.residual_scale + input // residual connection (no LayerNorm here)
     }
 
     fn backward(&mut self, grads: &Array2<f32>, lr: f32) -> Array2<f32> {
+        self.backward_clipped(grads, lr, None)
+    }
+
+    fn backward_clipped(
+        &mut self,
+        grads: &Array2<f32>,
+        lr: f32,
+        max_norm: Option<f32>,
+    ) -> Array2<f32> {
         let input = self.cached_input.as_ref().unwrap();
         let q = input.dot(&self.w_q);
         let k = input.dot(&self.w_k);
         let v = input.dot(&self.w_v);
         let dk = self.w_q.shape()[1] as f32;
         let scale = dk.sqrt();
+        let grads_attention = grads * self.residual_scale;
 
         let mut scores = q.dot(&k.t()) / scale;
 
-        // Apply causal masking - prevent attention to future tokens
-        let seq_len = scores.shape()[0];
-        for i in 0..seq_len {
-            for j in (i + 1)..seq_len {
-                scores[[i, j]] = f32::NEG_INFINITY;
-            }
-        }
+        self.apply_mask(&mut scores);
 
         let attn_weights = self.softmax(&scores); // also cached
 
         // Step 1: grads = ∂L/∂attn_output
-        let grad_attn_weights = grads.dot(&v.t());
-        let grad_v = attn_weights.t().dot(grads);
+        let grad_attn_weights = grads_attention.dot(&v.t());
+        let grad_v = attn_weights.t().dot(&grads_attention);
 
         // Step 2: softmax backward
         let grad_scores = SelfAttention::softmax_backward(&attn_weights, &grad_attn_weights); // [seq_len, seq_len]
@@ -163,9 +240,9 @@ impl Layer for SelfAttention {
         let grad_k = grad_scores.t().dot(&q);
 
         // Step 4: ∂L/∂W_q/W_k/W_v
-        let grad_w_q = input.t().dot(&grad_q);
-        let grad_w_k = input.t().dot(&grad_k);
-        let grad_w_v = input.t().dot(&grad_v);
+        let mut grad_w_q = input.t().dot(&grad_q);
+        let mut grad_w_k = input.t().dot(&grad_k);
+        let mut grad_w_v = input.t().dot(&grad_v);
 
         // Step 5: ∂L/∂input (gradient through attention computation)
         let grad_input_attention =
@@ -175,7 +252,13 @@ impl Layer for SelfAttention {
         // Forward: residual = attention + input, so gradient flows directly through
         let grad_input = grad_input_attention + grads;
 
-        // Step 7: update weights
+        // Step 7: clip this layer's gradients if requested, then update weights
+        if let Some(scale) = clip_scale(grad_w_q.iter().chain(&grad_w_k).chain(&grad_w_v), max_norm)
+        {
+            grad_w_q *= scale;
+            grad_w_k *= scale;
+            grad_w_v *= scale;
+        }
         self.optimizer_w_q.step(&mut self.w_q, &grad_w_q, lr);
         self.optimizer_w_k.step(&mut self.w_k, &grad_w_k, lr);
         self.optimizer_w_v.step(&mut self.w_v, &grad_w_v, lr);
@@ -186,4 +269,47 @@ impl Layer for SelfAttention {
     fn parameters(&self) -> usize {
         self.w_k.len() + self.w_q.len() + self.w_v.len()
     }
+
+    fn set_causal(&mut self, causal: bool) {
+        self.causal = causal;
+    }
+
+    fn causal(&self) -> Option<bool> {
+        Some(self.causal)
+    }
+
+    fn set_padding_mask(&mut self, mask: Option<&[bool]>) {
+        self.key_padding_mask = mask.map(<[bool]>::to_vec);
+    }
+
+    fn set_document_mask(&mut self, document_ids: Option<&[usize]>) {
+        self.document_ids = document_ids.map(<[usize]>::to_vec);
+    }
+
+    fn weights(&self) -> Vec<&Array2<f32>> {
+        vec![&self.w_q, &self.w_k, &self.w_v]
+    }
+
+    fn weights_mut(&mut self) -> Vec<&mut Array2<f32>> {
+        vec![&mut self.w_q, &mut self.w_k, &mut self.w_v]
+    }
+
+    fn reinitialize(&mut self) {
+        let (causal, residual_scale) = (self.causal, self.residual_scale);
+        *self = SelfAttention::new(self.embedding_dim);
+        self.causal = causal;
+        self.residual_scale = residual_scale;
+    }
+
+    fn input_dim(&self) -> Option<usize> {
+        Some(self.embedding_dim)
+    }
+
+    fn output_dim(&self) -> Option<usize> {
+        Some(self.embedding_dim)
+    }
+
+    fn attention_map(&self, input: &Array2<f32>) -> Option<Array2<f32>> {
+        Some(self.attention_weights(input))
+    }
 }
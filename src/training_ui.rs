@@ -4,6 +4,8 @@
 //! with live loss graphs, progress indicators, and training statistics.
 
 use crate::{
+    dataset_loader::DatasetCursor,
+    llm::TrainingReport,
     visualization::{
         check_user_input, init_terminal, restore_terminal, TrainingVisualizer, VisualizationConfig,
     },
@@ -11,7 +13,6 @@ use crate::{
 };
 use crossterm::event::KeyCode;
 use indicatif::ProgressBar;
-use std::time::Duration;
 
 /// Run training with interactive visualization dashboard
 pub fn train_with_dashboard(
@@ -21,16 +22,30 @@ pub fn train_with_dashboard(
     learning_rate: f32,
     title: &str,
 ) -> crate::Result<()> {
-    // Initialize terminal UI
-    let mut terminal = init_terminal()
-        .map_err(|e| crate::LlmError::Other(format!("Failed to init terminal: {}", e)))?;
-
-    // Create visualizer
     let vis_config = VisualizationConfig {
         max_history: 100,
         update_interval_ms: 100,
         interactive: true,
+        ..Default::default()
     };
+    train_with_dashboard_config(llm, training_data, epochs, learning_rate, title, vis_config)
+}
+
+/// [`train_with_dashboard`] with a custom dashboard configuration, e.g. a zero
+/// [`VisualizationConfig::final_hold`] for scripted runs.
+pub fn train_with_dashboard_config(
+    llm: &mut LLM,
+    training_data: Vec<&str>,
+    epochs: usize,
+    learning_rate: f32,
+    title: &str,
+    vis_config: VisualizationConfig,
+) -> crate::Result<()> {
+    // Initialize terminal UI
+    let mut terminal = init_terminal()
+        .map_err(|e| crate::LlmError::Other(format!("Failed to init terminal: {}", e)))?;
+
+    // Create visualizer
     let mut visualizer = TrainingVisualizer::new(vis_config, epochs);
 
     // Progress bar for actual training (runs in background)
@@ -38,54 +53,28 @@ pub fn train_with_dashboard(
     pb.set_draw_target(indicatif::ProgressDrawTarget::hidden());
 
     // Training loop with dashboard
+    let mut report = TrainingReport::default();
     for epoch in 0..epochs {
-        // Tokenize data once per epoch
+        // Cached after the first epoch
         let tokenized_data: Vec<Vec<usize>> = training_data
             .iter()
-            .map(|input| llm.tokenize(input))
+            .map(|input| llm.tokenize_cached(input))
             .collect();
 
         // Training batch
-        let mut total_loss = 0.0;
-        for training_row in &tokenized_data {
-            if training_row.len() < 2 {
-                continue;
-            }
-
-            let input_ids = &training_row[..training_row.len() - 1];
-            let target_ids = &training_row[1..];
-
-            // Forward pass
-            let mut input = ndarray::Array2::zeros((1, input_ids.len()));
-            input.row_mut(0).assign(
-                &input_ids
-                    .iter()
-                    .map(|&x| x as f32)
-                    .collect::<ndarray::Array1<f32>>(),
-            );
-
-            for layer in &mut llm.network {
-                input = layer.forward(&input);
-            }
-
-            let logits = input;
-            let probs = LLM::softmax(&logits);
-            total_loss += LLM::cross_entropy_loss_step(&probs, target_ids);
-
-            // Backward pass
-            let mut grads_output = LLM::compute_gradients_step(&probs, target_ids);
-            LLM::clip_gradients(&mut grads_output, 5.0);
-
-            for layer in llm.network.iter_mut().rev() {
-                grads_output = layer.backward(&grads_output, learning_rate);
-            }
-        }
-
-        // Calculate average loss
-        let avg_loss = total_loss / tokenized_data.len().max(1) as f32;
+        let stats = llm.run_epoch(
+            &mut DatasetCursor::new(epoch, 0),
+            &training_data,
+            &tokenized_data,
+            learning_rate,
+            &mut report,
+            |_| false,
+        );
 
         // Update visualizer
-        visualizer.record_loss(avg_loss);
+        visualizer.record_loss(stats.loss);
+        visualizer.record_accuracy(stats.accuracy);
+        visualizer.record_gradient(stats.grad_norm);
         visualizer.set_epoch(epoch + 1);
 
         // Render dashboard
@@ -122,8 +111,11 @@ pub fn train_with_dashboard(
         })
         .map_err(|e| crate::LlmError::Other(format!("Failed to draw final frame: {}", e)))?;
 
-    // Hold the dashboard for 2 seconds before restoring
-    std::thread::sleep(Duration::from_secs(2));
+    // Hold the final dashboard before restoring
+    let hold = visualizer.final_hold();
+    if !hold.is_zero() {
+        std::thread::sleep(hold);
+    }
 
     // Restore terminal
     restore_terminal(&mut terminal)
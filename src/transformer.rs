@@ -1,8 +1,12 @@
 use ndarray::Array2;
 
 use crate::{
-    feed_forward::FeedForward, layer_norm::LayerNorm, llm::Layer, self_attention::SelfAttention,
+    feed_forward::{FeedForward, FfnType},
+    layer_norm::LayerNorm,
+    llm::Layer,
+    self_attention::SelfAttention,
 };
+#[derive(Clone)]
 pub struct TransformerBlock {
     attention: SelfAttention,
     feed_forward: FeedForward,
@@ -12,13 +16,26 @@ pub struct TransformerBlock {
 
 impl TransformerBlock {
     pub fn new(embedding_dim: usize, hidden_dim: usize) -> Self {
+        Self::with_ffn(embedding_dim, hidden_dim, FfnType::default())
+    }
+
+    /// Build a block whose feed-forward network uses the given variant.
+    pub fn with_ffn(embedding_dim: usize, hidden_dim: usize, ffn_type: FfnType) -> Self {
         TransformerBlock {
             attention: SelfAttention::new(embedding_dim),
-            feed_forward: FeedForward::new(embedding_dim, hidden_dim),
+            feed_forward: FeedForward::with_type(embedding_dim, hidden_dim, ffn_type),
             norm1: LayerNorm::new(embedding_dim),
             norm2: LayerNorm::new(embedding_dim),
         }
     }
+
+    /// Scale the attention and feed-forward outputs by `residual_scale` before their
+    /// residuals are added (DeepNet-style); 1.0 is the standard block.
+    pub fn with_residual_scale(mut self, residual_scale: f32) -> Self {
+        self.attention.residual_scale = residual_scale;
+        self.feed_forward.residual_scale = residual_scale;
+        self
+    }
 }
 
 impl Layer for TransformerBlock {
@@ -37,18 +54,31 @@ impl Layer for TransformerBlock {
     }
 
     fn backward(&mut self, grads: &Array2<f32>, lr: f32) -> Array2<f32> {
+        self.backward_clipped(grads, lr, None)
+    }
+
+    fn backward_clipped(
+        &mut self,
+        grads: &Array2<f32>,
+        lr: f32,
+        max_norm: Option<f32>,
+    ) -> Array2<f32> {
+        // Each sublayer clips its own parameter gradients
+
         // Backward through second LayerNorm
-        let grad_norm2 = self.norm2.backward(grads, lr);
+        let grad_norm2 = self.norm2.backward_clipped(grads, lr, max_norm);
 
         // Backward through feed-forward (includes residual connection)
-        let grad_ffn = self.feed_forward.backward(&grad_norm2, lr);
+        let grad_ffn = self
+            .feed_forward
+            .backward_clipped(&grad_norm2, lr, max_norm);
 
         // Backward through first LayerNorm
-        let grad_norm1 = self.norm1.backward(&grad_ffn, lr);
+        let grad_norm1 = self.norm1.backward_clipped(&grad_ffn, lr, max_norm);
 
         // Backward through attention (includes residual connection)
 
-        self.attention.backward(&grad_norm1, lr)
+        self.attention.backward_clipped(&grad_norm1, lr, max_norm)
     }
 
     fn parameters(&self) -> usize {
@@ -57,4 +87,63 @@ impl Layer for TransformerBlock {
             + self.norm1.parameters()
             + self.norm2.parameters()
     }
+
+    fn set_causal(&mut self, causal: bool) {
+        self.attention.set_causal(causal);
+    }
+
+    fn causal(&self) -> Option<bool> {
+        self.attention.causal()
+    }
+
+    fn set_padding_mask(&mut self, mask: Option<&[bool]>) {
+        self.attention.set_padding_mask(mask);
+    }
+
+    fn set_document_mask(&mut self, document_ids: Option<&[usize]>) {
+        self.attention.set_document_mask(document_ids);
+    }
+
+    fn weights(&self) -> Vec<&Array2<f32>> {
+        let mut weights = self.attention.weights();
+        weights.extend(self.feed_forward.weights());
+        weights.extend(self.norm1.weights());
+        weights.extend(self.norm2.weights());
+        weights
+    }
+
+    fn weights_mut(&mut self) -> Vec<&mut Array2<f32>> {
+        let mut weights = self.attention.weights_mut();
+        weights.extend(self.feed_forward.weights_mut());
+        weights.extend(self.norm1.weights_mut());
+        weights.extend(self.norm2.weights_mut());
+        weights
+    }
+
+    fn reinitialize(&mut self) {
+        // Same order as `TransformerBlock::new` so seeded initialization matches
+        self.attention.reinitialize();
+        self.feed_forward.reinitialize();
+        self.norm1.reinitialize();
+        self.norm2.reinitialize();
+    }
+
+    fn input_dim(&self) -> Option<usize> {
+        self.attention.input_dim()
+    }
+
+    fn output_dim(&self) -> Option<usize> {
+        self.norm2.output_dim()
+    }
+
+    fn attention_map(&self, input: &Array2<f32>) -> Option<Array2<f32>> {
+        self.attention.attention_map(input)
+    }
+
+    fn hidden_activations(&self, input: &Array2<f32>) -> Option<Array2<f32>> {
+        let mut block = self.clone();
+        let attention_out = block.attention.forward(input);
+        let norm1_out = block.norm1.normalize(&attention_out);
+        self.feed_forward.hidden_activations(&norm1_out)
+    }
 }
@@ -17,6 +17,39 @@ use ratatui::{
 use std::io;
 use std::time::Duration;
 
+use crate::metrics::Metrics;
+
+/// Accent color used for the dashboard's gauges and panels
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AccentColor {
+    #[default]
+    Green,
+    Cyan,
+    Blue,
+    Magenta,
+    Yellow,
+    Red,
+    White,
+}
+
+impl AccentColor {
+    /// The ratatui color for this accent
+    pub fn color(self) -> Color {
+        match self {
+            AccentColor::Green => Color::Green,
+            AccentColor::Cyan => Color::Cyan,
+            AccentColor::Blue => Color::Blue,
+            AccentColor::Magenta => Color::Magenta,
+            AccentColor::Yellow => Color::Yellow,
+            AccentColor::Red => Color::Red,
+            AccentColor::White => Color::White,
+        }
+    }
+}
+
+/// Smallest and largest width (in percent) of the loss panel, so both panels stay visible
+const LOSS_PANEL_PERCENT_RANGE: (u16, u16) = (10, 90);
+
 /// Configuration for the training visualization UI
 #[derive(Clone, Debug)]
 pub struct VisualizationConfig {
@@ -26,6 +59,12 @@ pub struct VisualizationConfig {
     pub update_interval_ms: u64,
     /// Enable interactive mode (allow quit with 'q')
     pub interactive: bool,
+    /// Accent color for gauges and panels
+    pub accent_color: AccentColor,
+    /// Width of the loss panel as a percentage of the dashboard (clamped to 10-90)
+    pub loss_panel_percent: u16,
+    /// How long the final dashboard stays on screen after training; zero skips the hold
+    pub final_hold: Duration,
 }
 
 impl Default for VisualizationConfig {
@@ -34,10 +73,22 @@ impl Default for VisualizationConfig {
             max_history: 100,
             update_interval_ms: 100,
             interactive: true,
+            accent_color: AccentColor::default(),
+            loss_panel_percent: 70,
+            final_hold: Duration::from_secs(2),
         }
     }
 }
 
+impl VisualizationConfig {
+    /// Horizontal split between the loss panel and the statistics panel, in percent.
+    pub fn panel_percentages(&self) -> (u16, u16) {
+        let (min, max) = LOSS_PANEL_PERCENT_RANGE;
+        let loss = self.loss_panel_percent.clamp(min, max);
+        (loss, 100 - loss)
+    }
+}
+
 /// Manages the training visualization UI
 pub struct TrainingVisualizer {
     config: VisualizationConfig,
@@ -61,6 +112,32 @@ impl TrainingVisualizer {
         }
     }
 
+    /// Create a visualizer whose histories continue from previously saved `metrics`, so a
+    /// resumed run extends the earlier curves. The epoch counter still starts at 0; call
+    /// [`TrainingVisualizer::set_epoch`] with the resumed epoch.
+    pub fn from_metrics(
+        config: VisualizationConfig,
+        total_epochs: usize,
+        metrics: &Metrics,
+    ) -> Self {
+        let mut visualizer = Self::new(config, total_epochs);
+        metrics
+            .losses()
+            .for_each(|loss| visualizer.record_loss(loss));
+        metrics
+            .accuracies()
+            .for_each(|accuracy| visualizer.record_accuracy(accuracy));
+        metrics
+            .gradient_norms()
+            .for_each(|norm| visualizer.record_gradient(norm));
+        visualizer
+    }
+
+    /// How long to keep the final dashboard on screen before restoring the terminal.
+    pub fn final_hold(&self) -> Duration {
+        self.config.final_hold
+    }
+
     /// Record a loss value and update the visualization
     pub fn record_loss(&mut self, loss: f32) {
         let loss_u64 = (loss * 10000.0) as u64;
@@ -180,7 +257,7 @@ impl TrainingVisualizer {
                 Block::default()
                     .title(" 📊 Loss Bar Chart ")
                     .borders(Borders::ALL)
-                    .style(Style::default().fg(Color::Green).bold()),
+                    .style(Style::default().fg(self.config.accent_color.color()).bold()),
             )
             .style(Style::default().fg(Color::Cyan))
     }
@@ -189,6 +266,8 @@ impl TrainingVisualizer {
     pub fn render(&self, frame: &mut Frame, title: &str) {
         let size = frame.area();
         let loss_data = &self.loss_history;
+        let accent = self.config.accent_color.color();
+        let (loss_percent, stats_percent) = self.config.panel_percentages();
 
         // Create main layout
         let chunks = Layout::default()
@@ -216,7 +295,7 @@ impl TrainingVisualizer {
                     .title("Epoch Progress")
                     .borders(Borders::ALL),
             )
-            .gauge_style(Style::default().fg(Color::Green).bold())
+            .gauge_style(Style::default().fg(accent).bold())
             .percent(progress as u16)
             .label(format!("{}/{}", self.current_epoch, self.total_epochs));
         frame.render_widget(gauge, chunks[1]);
@@ -224,7 +303,10 @@ impl TrainingVisualizer {
         // Loss graph and stats
         let graph_chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+            .constraints([
+                Constraint::Percentage(loss_percent),
+                Constraint::Percentage(stats_percent),
+            ])
             .split(chunks[2]);
 
         // Line chart for loss visualization
@@ -235,7 +317,7 @@ impl TrainingVisualizer {
         let stats = format!(
             "Current Loss: {:.4}\nAccuracy: {:.2}%\nSamples: {}",
             self.current_loss(),
-            self.current_accuracy(),
+            self.current_accuracy() * 100.0,
             loss_data.len()
         );
         let stats_widget = Paragraph::new(stats)
@@ -243,10 +325,10 @@ impl TrainingVisualizer {
                 Block::default()
                     .title("Statistics")
                     .borders(Borders::ALL)
-                    .style(Style::default().fg(Color::Green)),
+                    .style(Style::default().fg(accent)),
             )
             .alignment(Alignment::Left)
-            .style(Style::default().fg(Color::Green));
+            .style(Style::default().fg(accent));
 
         frame.render_widget(stats_widget, graph_chunks[1]);
 
@@ -317,6 +399,65 @@ mod tests {
         assert_eq!(visualizer.loss_history.len(), 5);
     }
 
+    #[test]
+    fn test_panel_percentages_honor_config() {
+        let config = VisualizationConfig {
+            loss_panel_percent: 60,
+            accent_color: AccentColor::Magenta,
+            ..Default::default()
+        };
+        assert_eq!(config.panel_percentages(), (60, 40));
+        assert_eq!(config.accent_color.color(), Color::Magenta);
+
+        // Out-of-range widths are clamped
+        let too_wide = VisualizationConfig {
+            loss_panel_percent: 150,
+            ..Default::default()
+        };
+        assert_eq!(too_wide.panel_percentages(), (90, 10));
+        let too_narrow = VisualizationConfig {
+            loss_panel_percent: 0,
+            ..Default::default()
+        };
+        assert_eq!(too_narrow.panel_percentages(), (10, 90));
+    }
+
+    #[test]
+    fn test_final_hold_honors_config() {
+        let visualizer = TrainingVisualizer::new(VisualizationConfig::default(), 100);
+        assert_eq!(visualizer.final_hold(), Duration::from_secs(2));
+
+        let config = VisualizationConfig {
+            final_hold: Duration::ZERO,
+            ..Default::default()
+        };
+        let visualizer = TrainingVisualizer::new(config, 100);
+        assert!(visualizer.final_hold().is_zero());
+    }
+
+    #[test]
+    fn test_from_metrics_continues_histories() {
+        let mut metrics = Metrics::new(10);
+        for (loss, accuracy) in [(2.5, 0.1), (1.75, 0.3), (1.25, 0.5)] {
+            metrics.record_loss(loss);
+            metrics.record_accuracy(accuracy);
+            metrics.record_gradient_norm(0.5);
+        }
+
+        let config = VisualizationConfig {
+            max_history: 2,
+            ..Default::default()
+        };
+        let mut visualizer = TrainingVisualizer::from_metrics(config, 10, &metrics);
+        assert_eq!(visualizer.current_loss(), 1.25);
+        assert_eq!(visualizer.current_accuracy(), 0.5);
+        assert_eq!(visualizer.loss_history.len(), 2);
+        assert_eq!(visualizer.gradient_history, vec![5000, 5000]);
+
+        visualizer.record_loss(1.0);
+        assert_eq!(visualizer.loss_history, vec![12500, 10000]);
+    }
+
     #[test]
     fn test_epoch_progress() {
         let mut visualizer = TrainingVisualizer::new(VisualizationConfig::default(), 100);
@@ -5,6 +5,7 @@
 
 use crate::error::{LlmError, Result};
 use bincode::Encode;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::collections::{HashMap, HashSet};
 
 /// Vocabulary for token encoding/decoding.
@@ -86,6 +87,23 @@ impl Vocab {
             .ok_or_else(|| LlmError::token(format!("Unknown token ID: {}", token_id)))
     }
 
+    /// Append a word to the vocabulary, returning its token ID.
+    ///
+    /// Existing words keep their ID. Layers whose shape depends on the vocabulary size
+    /// (embeddings and output projection) must be resized afterwards; use
+    /// [`crate::LLM::expand_vocab`] to grow the vocabulary and the model together.
+    pub fn add_word(&mut self, word: &str) -> usize {
+        if let Some(id) = self.encode(word) {
+            return id;
+        }
+
+        let id = self.words.len();
+        self.encode.insert(word.to_string(), id);
+        self.decode.insert(id, word.to_string());
+        self.words.push(word.to_string());
+        id
+    }
+
     /// Get vocabulary size.
     pub fn size(&self) -> usize {
         self.words.len()
@@ -101,6 +119,37 @@ impl Vocab {
         vec!["hello", "world", "this", "is", "rust", "</s>"]
     }
 
+    /// Split text into the word pieces the tokenizer looks up: whitespace-separated words
+    /// with ASCII punctuation split off as separate pieces, except `</s>`, which is kept
+    /// whole.
+    pub fn split_text(text: &str) -> Vec<String> {
+        let mut pieces = Vec::new();
+
+        for word in text.split_whitespace() {
+            if word == "</s>" {
+                pieces.push(word.to_string());
+                continue;
+            }
+
+            let mut current_word = String::new();
+            for c in word.chars() {
+                if c.is_ascii_punctuation() {
+                    if !current_word.is_empty() {
+                        pieces.push(std::mem::take(&mut current_word));
+                    }
+                    pieces.push(c.to_string());
+                } else {
+                    current_word.push(c);
+                }
+            }
+            if !current_word.is_empty() {
+                pieces.push(current_word);
+            }
+        }
+
+        pieces
+    }
+
     /// Process text data to extract vocabulary words.
     ///
     /// # Arguments
@@ -149,6 +198,64 @@ impl Vocab {
         Self::new(words_refs)
     }
 
+    /// Build a vocabulary from a uniform random sample of `sample_size` texts drawn from
+    /// `iter`, for corpora too large to hold in memory.
+    ///
+    /// The stream is read once with reservoir sampling, so only the sample is kept. The
+    /// same stream and `seed` always select the same samples.
+    pub fn from_stream(iter: impl Iterator<Item = String>, sample_size: usize, seed: u64) -> Self {
+        Self::from_texts(&reservoir_sample(iter, sample_size, seed))
+    }
+
+    /// Export the vocabulary as a tab-separated `id\tword` table.
+    ///
+    /// Rows are sorted by token ID and preceded by an `id\tword` header, which makes
+    /// the output easy to inspect when debugging tokenization.
+    pub fn to_tsv(&self) -> String {
+        let mut tsv = String::from("id\tword\n");
+        for (id, word) in self.words.iter().enumerate() {
+            tsv.push_str(&format!("{}\t{}\n", id, word));
+        }
+        tsv
+    }
+
+    /// Rebuild a vocabulary from the table produced by [`Vocab::to_tsv`].
+    ///
+    /// # Errors
+    /// Returns a vocabulary error if a row is malformed or the IDs are not a
+    /// contiguous range starting at 0.
+    pub fn from_tsv(tsv: &str) -> Result<Self> {
+        let mut entries: Vec<(usize, String)> = Vec::new();
+
+        for (line_no, line) in tsv.lines().enumerate() {
+            if line.is_empty() || (line_no == 0 && line == "id\tword") {
+                continue;
+            }
+
+            let (id, word) = line.split_once('\t').ok_or_else(|| {
+                LlmError::vocabulary(format!("Missing tab separator on line {}", line_no + 1))
+            })?;
+            let id: usize = id.parse().map_err(|_| {
+                LlmError::vocabulary(format!("Invalid token ID '{}' on line {}", id, line_no + 1))
+            })?;
+            entries.push((id, word.to_string()));
+        }
+
+        entries.sort_by_key(|(id, _)| *id);
+        for (expected, (id, _)) in entries.iter().enumerate() {
+            if *id != expected {
+                return Err(LlmError::vocabulary(format!(
+                    "Token IDs must be contiguous from 0: expected {}, found {}",
+                    expected, id
+                )));
+            }
+        }
+
+        Ok(Self::new(
+            entries.iter().map(|(_, word)| word.as_str()).collect(),
+        ))
+    }
+
     /// Get vocabulary statistics.
     pub fn statistics(&self) -> VocabStats {
         VocabStats {
@@ -181,10 +288,63 @@ impl From<Vocab> for String {
     }
 }
 
+/// Pick `k` items uniformly at random from `iter` in one pass (Algorithm R), in stream
+/// order within the reservoir.
+fn reservoir_sample<T>(iter: impl Iterator<Item = T>, k: usize, seed: u64) -> Vec<T> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut reservoir = Vec::with_capacity(k);
+    for (i, item) in iter.enumerate() {
+        if i < k {
+            reservoir.push(item);
+        } else {
+            let j = rng.random_range(0..=i);
+            if j < k {
+                reservoir[j] = item;
+            }
+        }
+    }
+    reservoir
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn numbered_stream() -> impl Iterator<Item = String> {
+        (0..200).map(|i| format!("sample{} shared </s>", i))
+    }
+
+    #[test]
+    fn test_reservoir_sample_is_deterministic() {
+        let first = reservoir_sample(numbered_stream(), 10, 42);
+        let second = reservoir_sample(numbered_stream(), 10, 42);
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 10);
+        assert_ne!(first, reservoir_sample(numbered_stream(), 10, 7));
+
+        // Shorter streams are kept whole
+        assert_eq!(reservoir_sample(0..3, 10, 42), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_vocab_from_stream_uses_sampled_texts() {
+        let sampled = reservoir_sample(numbered_stream(), 5, 3);
+        let vocab = Vocab::from_stream(numbered_stream(), 5, 3);
+
+        assert!(vocab.contains("shared"));
+        for text in &sampled {
+            let word = text.split_whitespace().next().unwrap();
+            assert!(vocab.contains(word), "missing {}", word);
+        }
+        // Unsampled texts contribute nothing
+        let sample_words = vocab
+            .words
+            .iter()
+            .filter(|w| w.starts_with("sample"))
+            .count();
+        assert_eq!(sample_words, 5);
+    }
+
     #[test]
     fn test_vocab_encode_decode() {
         let vocab = Vocab::default();
@@ -212,4 +372,40 @@ mod tests {
         assert!(vocab.contains("hello"));
         assert!(vocab.contains("world"));
     }
+
+    #[test]
+    fn test_vocab_add_word() {
+        let mut vocab = Vocab::default();
+        let size = vocab.size();
+
+        let id = vocab.add_word("crab");
+        assert_eq!(vocab.size(), size + 1);
+        assert_eq!(vocab.encode("crab"), Some(id));
+        assert_eq!(vocab.decode(id), Some(&"crab".to_string()));
+
+        // Adding an existing word is a no-op
+        assert_eq!(vocab.add_word("crab"), id);
+        assert_eq!(vocab.size(), size + 1);
+    }
+
+    #[test]
+    fn test_vocab_tsv_round_trip() {
+        let vocab = Vocab::default();
+        let tsv = vocab.to_tsv();
+        assert!(tsv.starts_with("id\tword\n"));
+        assert!(tsv.contains("0\thello\n"));
+
+        let restored = Vocab::from_tsv(&tsv).unwrap();
+        assert_eq!(restored.size(), vocab.size());
+        for (id, word) in vocab.words.iter().enumerate() {
+            assert_eq!(restored.decode(id), Some(word));
+            assert_eq!(restored.encode(word), Some(id));
+        }
+    }
+
+    #[test]
+    fn test_vocab_from_tsv_rejects_gaps() {
+        assert!(Vocab::from_tsv("id\tword\n0\thello\n2\tworld\n").is_err());
+        assert!(Vocab::from_tsv("id\tword\nhello\n").is_err());
+    }
 }
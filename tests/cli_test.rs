@@ -0,0 +1,75 @@
+// Tests for the command-line binary
+
+use std::process::Command;
+
+#[test]
+fn test_print_config_reflects_cli_overrides() {
+    let dir = tempfile::tempdir().unwrap();
+    let checkpoint_dir = dir.path().join("custom_checkpoints");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_llm"))
+        .args(["--print-config", "--dry-run", "--seed", "42", "--output"])
+        .arg(&checkpoint_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expected = format!("checkpoint_dir = {:?}", checkpoint_dir.to_string_lossy());
+    assert!(stdout.contains(&expected), "{}", stdout);
+    assert!(stdout.contains("seed = 42"), "{}", stdout);
+    assert!(!stdout.contains("./checkpoints"), "{}", stdout);
+
+    // A dry run stops before anything is written
+    assert!(!checkpoint_dir.exists());
+}
+
+#[test]
+fn test_num_heads_override_is_validated() {
+    let run = |num_heads: &str| {
+        Command::new(env!("CARGO_BIN_EXE_llm"))
+            .args(["--print-config", "--dry-run", "--num-heads", num_heads])
+            .output()
+            .unwrap()
+    };
+
+    let output = run("1");
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("num_heads = 1"), "{}", stdout);
+
+    // Attention is single-head, so a head count the model cannot build fails at startup
+    for invalid in ["4", "3", "0"] {
+        let output = run(invalid);
+        assert!(
+            !output.status.success(),
+            "--num-heads {} was accepted",
+            invalid
+        );
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("num_heads must be 1"), "{}", stderr);
+    }
+}
+
+#[test]
+fn test_written_default_config_loads_back_in_every_format() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["config.toml", "config.json", "config.yaml"] {
+        let path = dir.path().join(name);
+        let output = Command::new(env!("CARGO_BIN_EXE_llm"))
+            .arg("--write-default-config")
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+
+        let output = Command::new(env!("CARGO_BIN_EXE_llm"))
+            .args(["--print-config", "--dry-run", "--config"])
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}: {:?}", name, output);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("num_heads = 1"), "{}", stdout);
+    }
+}
@@ -0,0 +1,74 @@
+//! Model builders shared by the integration tests.
+
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+
+use llm::{
+    output_projection::OutputProjection, transformer::TransformerBlock, Embeddings, Layer, Vocab,
+    EMBEDDING_DIM, HIDDEN_DIM, LLM,
+};
+use ndarray::Array2;
+
+/// Embeddings, then the layers from `hidden`, then an output projection over `vocab`.
+///
+/// Layers are built in network order, so a model built after [`llm::rng::seed`] matches
+/// [`LLM::reinitialize`] with the same seed.
+pub fn embedding_llm(vocab: Vocab, hidden: impl FnOnce() -> Vec<Box<dyn Layer>>) -> LLM {
+    let vocab_size = vocab.size();
+    let mut network: Vec<Box<dyn Layer>> = vec![Box::new(Embeddings::new(vocab.clone()))];
+    network.extend(hidden());
+    network.push(Box::new(OutputProjection::new(EMBEDDING_DIM, vocab_size)));
+    LLM::new(vocab, network)
+}
+
+/// Embeddings straight into the output projection, over the default vocabulary.
+pub fn tiny_llm() -> LLM {
+    embedding_llm(Vocab::default(), Vec::new)
+}
+
+/// One transformer block over the default vocabulary, initialized under `seed`.
+pub fn seeded_llm(seed: u64) -> LLM {
+    llm::rng::seed(seed);
+    embedding_llm(Vocab::default(), || {
+        vec![Box::new(TransformerBlock::new(EMBEDDING_DIM, HIDDEN_DIM))]
+    })
+}
+
+/// Passes its input through unchanged, logging the token ids of every forward pass.
+#[derive(Clone)]
+pub struct InputLog {
+    pub inputs: Arc<Mutex<Vec<Vec<usize>>>>,
+}
+
+impl Layer for InputLog {
+    fn layer_type(&self) -> &str {
+        "InputLog"
+    }
+
+    fn forward(&mut self, input: &Array2<f32>) -> Array2<f32> {
+        let ids = input.iter().map(|&id| id as usize).collect();
+        self.inputs.lock().unwrap().push(ids);
+        input.clone()
+    }
+
+    fn backward(&mut self, grads: &Array2<f32>, _lr: f32) -> Array2<f32> {
+        grads.clone()
+    }
+
+    fn parameters(&self) -> usize {
+        0
+    }
+}
+
+/// [`tiny_llm`] with an [`InputLog`] in front of the embeddings, logging into `inputs`.
+pub fn logged_llm(inputs: &Arc<Mutex<Vec<Vec<usize>>>>) -> LLM {
+    let mut llm = tiny_llm();
+    llm.network.insert(
+        0,
+        Box::new(InputLog {
+            inputs: inputs.clone(),
+        }),
+    );
+    llm
+}
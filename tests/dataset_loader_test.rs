@@ -1,6 +1,8 @@
 // Tests for the Dataset struct in dataset_loader.rs
 
-use llm::{Dataset, DatasetType};
+use llm::{AugmentOp, Dataset, DatasetType, Vocab};
+use rand::{rngs::StdRng, SeedableRng};
+use std::collections::HashMap;
 
 #[test]
 fn test_dataset_new_json() {
@@ -62,3 +64,425 @@ fn test_dataset_new_csv() {
     std::fs::remove_file(pretraining_csv).unwrap();
     std::fs::remove_file(chat_csv).unwrap();
 }
+
+#[test]
+fn test_csv_with_headers_skips_the_header_row() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("pretraining.csv");
+    std::fs::write(
+        &path,
+        "text\nwater flows downhill </s>\nthe sun rises </s>\n",
+    )
+    .unwrap();
+
+    let with_headers = Dataset::new(&path, &path, DatasetType::CSVWithHeaders).unwrap();
+    assert_eq!(
+        with_headers.pretraining_data,
+        vec!["water flows downhill </s>", "the sun rises </s>"]
+    );
+    assert_eq!(
+        with_headers.chat_training_data,
+        with_headers.pretraining_data
+    );
+
+    // Without the option the header leaks into the samples
+    let without = Dataset::from_many(&[path], DatasetType::CSV).unwrap();
+    assert_eq!(without[0], "text");
+}
+
+#[test]
+fn test_dataset_from_many_concatenates_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = dir.path().join("part1.json");
+    let second = dir.path().join("part2.json");
+    std::fs::write(&first, r#"["one </s>", "two </s>"]"#).unwrap();
+    std::fs::write(&second, r#"["three </s>"]"#).unwrap();
+
+    let data = Dataset::from_many(&[first.clone(), second.clone()], DatasetType::JSON).unwrap();
+    assert_eq!(data, vec!["one </s>", "two </s>", "three </s>"]);
+
+    let data = Dataset::from_many(&[second, first], DatasetType::JSON).unwrap();
+    assert_eq!(data[0], "three </s>");
+}
+
+#[test]
+fn test_dataset_from_many_names_failing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let good = dir.path().join("good.json");
+    let bad = dir.path().join("bad.json");
+    std::fs::write(&good, r#"["fine </s>"]"#).unwrap();
+    std::fs::write(&bad, "not json").unwrap();
+
+    let err = Dataset::from_many(&[good, bad], DatasetType::JSON).unwrap_err();
+    assert!(err.to_string().contains("bad.json"), "{}", err);
+}
+
+#[test]
+fn test_dataset_loads_gzip_compressed_json() {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let dir = tempfile::tempdir().unwrap();
+    let json = std::fs::read("data/pretraining_data.json").unwrap();
+    let plain = dir.path().join("data.json");
+    let compressed = dir.path().join("data.json.gz");
+    std::fs::write(&plain, &json).unwrap();
+    let mut encoder = GzEncoder::new(
+        std::fs::File::create(&compressed).unwrap(),
+        Compression::default(),
+    );
+    encoder.write_all(&json).unwrap();
+    encoder.finish().unwrap();
+
+    let expected = Dataset::from_many(&[plain], DatasetType::JSON).unwrap();
+    let loaded = Dataset::from_many(&[compressed], DatasetType::JSON).unwrap();
+    assert!(!loaded.is_empty());
+    assert_eq!(loaded, expected);
+}
+
+#[test]
+fn test_truncate_caps_each_dataset_in_order() {
+    let mut dataset = Dataset {
+        pretraining_data: vec!["a </s>".into(), "b </s>".into(), "c </s>".into()],
+        chat_training_data: vec!["x </s>".into()],
+    };
+    dataset.truncate(0);
+    assert_eq!(dataset.total_samples(), 4);
+
+    dataset.truncate(2);
+    assert_eq!(dataset.pretraining_data, vec!["a </s>", "b </s>"]);
+    assert_eq!(dataset.chat_training_data, vec!["x </s>"]);
+}
+
+#[test]
+fn test_integrity_report_flags_each_problem_sample() {
+    let too_long = vec!["hello"; llm::MAX_SEQ_LEN + 1].join(" ");
+    let dataset = Dataset {
+        pretraining_data: vec![
+            "hello world </s>".into(),
+            "   ".into(),
+            too_long,
+            "hello world </s>".into(),
+        ],
+        chat_training_data: vec!["rust\u{7}is </s>".into(), "tab\tand newline\n ok".into()],
+    };
+    let before = dataset.clone();
+
+    let report = dataset.integrity_report(&Vocab::default());
+    let flagged: Vec<(usize, &str)> = report
+        .iter()
+        .map(|(index, reason)| (*index, reason.as_str()))
+        .collect();
+    assert_eq!(
+        flagged,
+        vec![
+            (1, "empty"),
+            (2, "too long: 81 tokens (max 80)"),
+            (3, "duplicate of sample 0"),
+            (4, "contains control characters"),
+        ]
+    );
+    assert_eq!(dataset.pretraining_data, before.pretraining_data);
+}
+
+#[test]
+fn test_split_sentences_yields_one_chunk_per_sentence_within_limit() {
+    let mut dataset = Dataset {
+        pretraining_data: vec![
+            "Mountains form slowly. Rivers carve valleys! Do glaciers move? Yes. </s>".into(),
+            "Version 2.5 of the tool shipped. </s>".into(),
+            "one two three four five six seven eight nine ten </s>".into(),
+        ],
+        chat_training_data: vec!["User: Hi. Assistant: Hello. </s>".into()],
+    };
+
+    let added = dataset.split_sentences(6);
+    assert_eq!(
+        &dataset.pretraining_data[..4],
+        [
+            "Mountains form slowly. </s>",
+            "Rivers carve valleys! </s>",
+            "Do glaciers move? </s>",
+            "Yes. </s>"
+        ]
+    );
+    // A period not followed by a capital does not end the sentence, but the limit does
+    assert!(dataset.pretraining_data[4].starts_with("Version 2.5"));
+    for chunk in &dataset.pretraining_data {
+        assert!(Vocab::split_text(chunk).len() <= 6, "{}", chunk);
+        assert!(chunk.ends_with("</s>"), "{}", chunk);
+    }
+    assert_eq!(added, dataset.pretraining_data.len() - 3);
+    assert_eq!(dataset.chat_training_data.len(), 1);
+}
+
+#[test]
+fn test_mixed_epoch_draws_from_both_splits_at_the_ratio() {
+    let dataset = Dataset {
+        pretraining_data: (0..5000).map(|i| format!("fact {} </s>", i)).collect(),
+        chat_training_data: (0..5000).map(|i| format!("User: {} </s>", i)).collect(),
+    };
+    let mut rng = StdRng::seed_from_u64(7);
+
+    let epoch = dataset.mixed_epoch(0.5, &mut rng);
+    assert_eq!(epoch.len(), dataset.total_samples());
+    let from_pretraining = epoch.iter().filter(|s| s.starts_with("fact")).count();
+    let fraction = from_pretraining as f32 / epoch.len() as f32;
+    assert!((0.47..0.53).contains(&fraction), "fraction {}", fraction);
+
+    // The extremes draw from one split only
+    let all_chat = dataset.mixed_epoch(0.0, &mut rng);
+    assert!(all_chat.iter().all(|s| s.starts_with("User:")));
+    let chat_only = Dataset {
+        pretraining_data: Vec::new(),
+        chat_training_data: vec!["User: hi </s>".into()],
+    };
+    assert_eq!(chat_only.mixed_epoch(1.0, &mut rng), vec!["User: hi </s>"]);
+}
+
+#[test]
+fn test_k_folds_partition_the_samples() {
+    let dataset = Dataset {
+        pretraining_data: (0..13).map(|i| format!("fact {} </s>", i)).collect(),
+        chat_training_data: (0..10).map(|i| format!("User: {} </s>", i)).collect(),
+    };
+    let folds = dataset.k_folds(5, 3);
+    assert_eq!(folds.len(), 5);
+
+    let mut seen: Vec<String> = Vec::new();
+    for (train, validation) in &folds {
+        let size = validation.total_samples();
+        assert!((4..=5).contains(&size), "fold size {}", size);
+        assert_eq!(train.total_samples() + size, dataset.total_samples());
+        assert!(validation
+            .pretraining_data
+            .iter()
+            .all(|s| s.starts_with("fact") && !train.pretraining_data.contains(s)));
+        assert!(validation
+            .chat_training_data
+            .iter()
+            .all(|s| s.starts_with("User:") && !train.chat_training_data.contains(s)));
+        seen.extend(validation.pretraining_data.iter().cloned());
+        seen.extend(validation.chat_training_data.iter().cloned());
+    }
+
+    let mut all: Vec<String> = dataset
+        .pretraining_data
+        .iter()
+        .chain(&dataset.chat_training_data)
+        .cloned()
+        .collect();
+    all.sort();
+    seen.sort();
+    assert_eq!(seen, all);
+
+    // The same seed gives the same folds
+    assert_eq!(
+        dataset.k_folds(5, 3)[0].1.pretraining_data,
+        folds[0].1.pretraining_data
+    );
+    assert!(dataset.k_folds(0, 3).is_empty());
+}
+
+#[test]
+fn test_three_way_split_partitions_by_ratio() {
+    let dataset = Dataset {
+        pretraining_data: (0..100).map(|i| format!("fact {} </s>", i)).collect(),
+        chat_training_data: (0..20).map(|i| format!("User: {} </s>", i)).collect(),
+    };
+    let (train, val, test) = dataset.three_way_split(0.7, 0.2, 11).unwrap();
+    assert_eq!(train.pretraining_data.len(), 70);
+    assert_eq!(val.pretraining_data.len(), 20);
+    assert_eq!(test.pretraining_data.len(), 10);
+    assert_eq!(train.chat_training_data.len(), 14);
+    assert_eq!(val.chat_training_data.len(), 4);
+    assert_eq!(test.chat_training_data.len(), 2);
+
+    let mut all: Vec<String> = [&train, &val, &test]
+        .iter()
+        .flat_map(|split| {
+            split
+                .pretraining_data
+                .iter()
+                .chain(&split.chat_training_data)
+                .cloned()
+        })
+        .collect();
+    let mut expected: Vec<String> = dataset
+        .pretraining_data
+        .iter()
+        .chain(&dataset.chat_training_data)
+        .cloned()
+        .collect();
+    all.sort();
+    expected.sort();
+    assert_eq!(all, expected);
+
+    let (again, _, _) = dataset.three_way_split(0.7, 0.2, 11).unwrap();
+    assert_eq!(again.pretraining_data, train.pretraining_data);
+    let (other, _, _) = dataset.three_way_split(0.7, 0.2, 12).unwrap();
+    assert_ne!(other.pretraining_data, train.pretraining_data);
+
+    assert!(dataset.three_way_split(0.8, 0.3, 11).is_err());
+    assert!(dataset.three_way_split(-0.1, 0.3, 11).is_err());
+}
+
+#[test]
+fn test_iter_batches_groups_samples_lazily() {
+    let dataset = Dataset {
+        pretraining_data: (0..5).map(|i| format!("hello world {} </s>", i)).collect(),
+        chat_training_data: vec!["rust is </s>".into(), "this </s>".into()],
+    };
+    let vocab = Vocab::default();
+
+    let batches: Vec<Vec<Vec<usize>>> = dataset.iter_batches(&vocab, 3).collect();
+    assert_eq!(
+        batches.iter().map(Vec::len).collect::<Vec<_>>(),
+        vec![3, 3, 1]
+    );
+    // Unknown words are dropped and the chat split follows pre-training
+    let eos = vocab.encode("</s>").unwrap();
+    assert_eq!(batches[0][0], vec![0, 1, eos]);
+    assert_eq!(batches[2][0], vec![vocab.encode("this").unwrap(), eos]);
+
+    assert_eq!(dataset.iter_batches(&vocab, 3).next().unwrap(), batches[0]);
+}
+
+#[test]
+fn test_dataset_token_statistics() {
+    let dataset = Dataset {
+        pretraining_data: vec!["hello world </s>".to_string(), "rust, is fun".to_string()],
+        chat_training_data: vec!["hello hello".to_string()],
+    };
+    let vocab = Vocab::default();
+
+    // Pieces: [hello world </s>], [rust , is fun], [hello hello]
+    let stats = dataset.token_statistics(&vocab);
+    assert_eq!(stats.total_tokens, 9);
+    assert_eq!(stats.unique_tokens, 7);
+    assert!((stats.mean_seq_len - 3.0).abs() < 1e-6);
+    assert_eq!(stats.median_seq_len, 3.0);
+    assert_eq!(stats.seq_len_quantiles, [2, 2, 3, 4, 4]);
+    // "," and "fun" are not in the default vocabulary
+    assert!((stats.oov_rate - 2.0 / 9.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_dataset_stats_report() {
+    let dataset = Dataset {
+        pretraining_data: vec!["hello world </s>".to_string(), "rust, is fun".to_string()],
+        chat_training_data: vec!["hello hello".to_string()],
+    };
+
+    let report = dataset.stats_report(&Vocab::default());
+    assert_eq!(
+        report,
+        "Samples: 3 (2 pre-training, 1 chat)\n\
+         Vocabulary size: 6\n\
+         Total tokens: 9\n\
+         Unique tokens: 7\n\
+         OOV rate: 22.22%\n\
+         Sequence length: mean 3.0, median 3.0\n\
+         Sequence length quantiles: min 2, p25 2, p50 3, p75 4, max 4"
+    );
+}
+
+fn augmentation_dataset() -> Dataset {
+    Dataset {
+        pretraining_data: vec![
+            "the sun rises in the east and sets in the west </s>".to_string(),
+            "water flows downhill due to gravity </s>".to_string(),
+        ],
+        chat_training_data: vec![
+            "User: what causes rain ? Assistant: water vapor condenses in clouds </s>".to_string(),
+        ],
+    }
+}
+
+#[test]
+fn test_augment_token_dropout_is_shorter_and_reproducible() {
+    let ops = [AugmentOp::TokenDropout {
+        prob: 0.3,
+        seed: 11,
+    }];
+    let mut dataset = augmentation_dataset();
+    let added = dataset.augment(&ops);
+    assert!(added > 0);
+    assert_eq!(dataset.total_samples(), 3 + added);
+
+    let original = augmentation_dataset();
+    for (source, variant) in original
+        .pretraining_data
+        .iter()
+        .zip(&dataset.pretraining_data[2..])
+    {
+        let words = variant.split_whitespace().count();
+        assert!(words < source.split_whitespace().count(), "{}", variant);
+        assert!(variant.ends_with("</s>"));
+    }
+    if let Some(chat_variant) = dataset.chat_training_data.get(1) {
+        assert!(chat_variant.starts_with("User:"));
+        assert!(chat_variant.contains("Assistant:"));
+    }
+
+    let mut again = augmentation_dataset();
+    again.augment(&ops);
+    assert_eq!(again.pretraining_data, dataset.pretraining_data);
+    assert_eq!(again.chat_training_data, dataset.chat_training_data);
+}
+
+#[test]
+fn test_augment_swaps_and_synonyms() {
+    let mut dataset = augmentation_dataset();
+    dataset.augment(&[
+        AugmentOp::WindowSwap {
+            window: 2,
+            prob: 1.0,
+            seed: 5,
+        },
+        AugmentOp::Synonyms {
+            map: HashMap::from([("water".to_string(), vec!["liquid".to_string()])]),
+            prob: 1.0,
+            seed: 5,
+        },
+    ]);
+
+    // A swap keeps the same words in a different order
+    let source = &dataset.pretraining_data[0];
+    let swapped = &dataset.pretraining_data[2];
+    assert_ne!(swapped, source);
+    let mut a: Vec<&str> = source.split_whitespace().collect();
+    let mut b: Vec<&str> = swapped.split_whitespace().collect();
+    a.sort();
+    b.sort();
+    assert_eq!(a, b);
+
+    assert!(dataset
+        .pretraining_data
+        .contains(&"liquid flows downhill due to gravity </s>".to_string()));
+}
+
+#[test]
+fn test_pack_reduces_sequences_within_limit() {
+    let mut dataset = Dataset {
+        pretraining_data: vec![
+            "the sun is hot </s>".to_string(),
+            "water is wet".to_string(),
+            "rain falls from clouds </s>".to_string(),
+            "a very long sample that does not fit with any other sample here </s>".to_string(),
+        ],
+        chat_training_data: vec!["User: hi Assistant: hello </s>".to_string()],
+    };
+
+    let removed = dataset.pack(12);
+    assert_eq!(removed, 1);
+    assert_eq!(
+        dataset.pretraining_data,
+        vec![
+            "the sun is hot </s> water is wet </s>",
+            "rain falls from clouds </s>",
+            "a very long sample that does not fit with any other sample here </s>",
+        ]
+    );
+    assert_eq!(dataset.chat_training_data.len(), 1);
+}
@@ -97,3 +97,74 @@ fn test_embedding_backwards() {
         post_train_position_embeddings
     );
 }
+
+#[test]
+fn test_load_pretrained_vectors() {
+    use std::collections::HashMap;
+
+    let vocab = Vocab::new(vec!["hello", "world", "test", "</s>"]);
+    let mut embeddings = Embeddings::new(vocab.clone());
+    let before = embeddings.token_embeddings.clone();
+
+    let mut vectors = HashMap::new();
+    vectors.insert("world".to_string(), vec![1.0; EMBEDDING_DIM]);
+    vectors.insert("missing".to_string(), vec![2.0; EMBEDDING_DIM]);
+
+    let matched = embeddings.load_pretrained(&vectors, &vocab).unwrap();
+    assert_eq!(matched, 1);
+
+    let world = vocab.encode("world").unwrap();
+    for id in 0..vocab.size() {
+        if id == world {
+            assert!(embeddings
+                .token_embeddings
+                .row(id)
+                .iter()
+                .all(|&x| x == 1.0));
+        } else {
+            assert_eq!(embeddings.token_embeddings.row(id), before.row(id));
+        }
+    }
+}
+
+#[test]
+fn test_load_pretrained_dimension_mismatch() {
+    use std::collections::HashMap;
+
+    let vocab = Vocab::new(vec!["hello", "world", "test", "</s>"]);
+    let mut embeddings = Embeddings::new(vocab.clone());
+    let before = embeddings.token_embeddings.clone();
+
+    let mut vectors = HashMap::new();
+    vectors.insert("hello".to_string(), vec![1.0; EMBEDDING_DIM - 1]);
+
+    let result = embeddings.load_pretrained(&vectors, &vocab);
+    assert!(matches!(result, Err(llm::LlmError::ShapeMismatch { .. })));
+    assert_eq!(embeddings.token_embeddings, before);
+}
+
+#[test]
+fn test_analogy_resolves_on_hand_built_embeddings() {
+    let vocab = Vocab::new(vec!["man", "woman", "king", "queen", "apple"]);
+    let mut embeddings = Embeddings::new(vocab.clone());
+    embeddings.token_embeddings = ndarray::array![
+        [1.0, 0.0, 0.0],  // man
+        [1.0, 1.0, 0.0],  // woman
+        [1.0, 0.0, 1.0],  // king
+        [1.0, 1.0, 1.0],  // queen
+        [0.0, 0.0, -1.0], // apple
+    ];
+
+    let nearest = embeddings.analogy("man", "woman", "king", &vocab, 2);
+    assert_eq!(nearest.len(), 2);
+    assert_eq!(nearest[0].0, "queen");
+    assert!((nearest[0].1 - 1.0).abs() < 1e-6);
+    assert_eq!(nearest[1].0, "apple");
+
+    // Inputs are never returned, and unknown words yield nothing
+    let all = embeddings.analogy("man", "woman", "king", &vocab, 10);
+    assert_eq!(all.len(), 2);
+    assert!(embeddings
+        .analogy("man", "woman", "prince", &vocab, 3)
+        .is_empty());
+}
@@ -1,4 +1,8 @@
-use llm::{feed_forward::FeedForward, Layer, EMBEDDING_DIM, HIDDEN_DIM};
+use llm::{
+    feed_forward::{FeedForward, FfnType},
+    output_projection::OutputProjection,
+    Embeddings, Layer, Vocab, EMBEDDING_DIM, HIDDEN_DIM, LLM,
+};
 use ndarray::Array2;
 
 #[test]
@@ -53,3 +57,87 @@ fn test_feed_forward_and_backward() {
     // Make sure backward pass modifies the input
     assert_ne!(output, grad_input);
 }
+
+/// `sum(forward(input) * upstream)`, whose gradient with respect to the input is what
+/// `backward(upstream)` returns.
+fn weighted_output(ffn: &mut FeedForward, input: &Array2<f32>, upstream: &Array2<f32>) -> f32 {
+    (ffn.forward(input) * upstream).sum()
+}
+
+#[test]
+fn test_geglu_backward_matches_numerical_gradient() {
+    let (embedding_dim, hidden_dim) = (6, 4);
+    llm::rng::seed(3);
+    let mut ffn = FeedForward::with_type(embedding_dim, hidden_dim, FfnType::GeGlu);
+    assert_eq!(ffn.layer_type(), "FeedForward(GeGLU)");
+    // Value and gate halves each get `hidden_dim` columns
+    assert_eq!(
+        ffn.parameters(),
+        embedding_dim * 2 * hidden_dim
+            + 2 * hidden_dim
+            + hidden_dim * embedding_dim
+            + embedding_dim
+    );
+
+    let input = Array2::from_shape_fn((3, embedding_dim), |(i, j)| {
+        ((i * embedding_dim + j) as f32 * 0.37).sin()
+    });
+    let upstream = Array2::from_shape_fn((3, embedding_dim), |(i, j)| {
+        ((i + 2 * j) as f32 * 0.53).cos()
+    });
+
+    // Zero learning rate so the backward pass leaves the weights in place
+    ffn.forward(&input);
+    let analytic = ffn.backward(&upstream, 0.0);
+
+    let eps = 1e-2;
+    for idx in [(0, 0), (1, 3), (2, 5), (0, 4)] {
+        let mut plus = input.clone();
+        plus[idx] += eps;
+        let mut minus = input.clone();
+        minus[idx] -= eps;
+        let numeric = (weighted_output(&mut ffn, &plus, &upstream)
+            - weighted_output(&mut ffn, &minus, &upstream))
+            / (2.0 * eps);
+        assert!(
+            (numeric - analytic[idx]).abs() < 1e-2 * (1.0 + numeric.abs()),
+            "{:?}: numeric {} vs analytic {}",
+            idx,
+            numeric,
+            analytic[idx]
+        );
+    }
+}
+
+#[test]
+fn test_ffn_activation_stats_flags_dead_unit() {
+    let mut feed_forward = FeedForward::new(EMBEDDING_DIM, HIDDEN_DIM);
+    {
+        // Unit 0 gets no input and a negative bias, so ReLU always zeroes it
+        let mut weights = feed_forward.weights_mut();
+        weights[0].column_mut(0).fill(0.0);
+        weights[1][[0, 0]] = -1.0;
+    }
+    let vocab = Vocab::default();
+    let vocab_size = vocab.size();
+    let llm = LLM::new(
+        vocab,
+        vec![
+            Box::new(Embeddings::default()),
+            Box::new(feed_forward),
+            Box::new(OutputProjection::new(EMBEDDING_DIM, vocab_size)),
+        ],
+    );
+
+    let stats = llm.ffn_activation_stats(&["hello world", "this is rust", "zork"]);
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].layer, 1);
+    assert_eq!(stats[0].activation_fraction.len(), HIDDEN_DIM);
+    assert_eq!(stats[0].activation_fraction[0], 0.0);
+    assert!(stats[0].dead_units().contains(&0));
+    assert!(stats[0]
+        .activation_fraction
+        .iter()
+        .all(|fraction| (0.0..=1.0).contains(fraction)));
+    assert!(stats[0].dead_units().len() < HIDDEN_DIM);
+}
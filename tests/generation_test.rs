@@ -0,0 +1,589 @@
+mod common;
+
+use common::tiny_llm;
+use llm::{
+    length_normalized_score, nucleus_filter, ContextOverflow, GenerationConfig, Layer,
+    SamplingOptions, Vocab, LLM, MAX_SEQ_LEN,
+};
+use ndarray::Array2;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Emits the same logits for every position, whatever the input.
+#[derive(Clone)]
+struct ConstantLogitsLayer {
+    logits: Vec<f32>,
+}
+
+impl Layer for ConstantLogitsLayer {
+    fn layer_type(&self) -> &str {
+        "ConstantLogitsLayer"
+    }
+
+    fn forward(&mut self, input: &Array2<f32>) -> Array2<f32> {
+        let seq_len = input.shape()[1];
+        Array2::from_shape_fn((seq_len, self.logits.len()), |(_, j)| self.logits[j])
+    }
+
+    fn backward(&mut self, grads: &Array2<f32>, _lr: f32) -> Array2<f32> {
+        grads.clone()
+    }
+
+    fn parameters(&self) -> usize {
+        0
+    }
+}
+
+/// Sleeps on every forward pass to simulate an expensive model.
+#[derive(Clone)]
+struct SlowLayer {
+    delay: Duration,
+}
+
+impl Layer for SlowLayer {
+    fn layer_type(&self) -> &str {
+        "SlowLayer"
+    }
+
+    fn forward(&mut self, input: &Array2<f32>) -> Array2<f32> {
+        std::thread::sleep(self.delay);
+        input.clone()
+    }
+
+    fn backward(&mut self, grads: &Array2<f32>, _lr: f32) -> Array2<f32> {
+        grads.clone()
+    }
+
+    fn parameters(&self) -> usize {
+        0
+    }
+}
+
+/// Predicts the first token of its context at every position.
+#[derive(Clone)]
+struct FirstTokenLayer {
+    vocab_size: usize,
+}
+
+impl Layer for FirstTokenLayer {
+    fn layer_type(&self) -> &str {
+        "FirstTokenLayer"
+    }
+
+    fn forward(&mut self, input: &Array2<f32>) -> Array2<f32> {
+        let seq_len = input.shape()[1];
+        assert!(seq_len <= MAX_SEQ_LEN, "context of {} tokens", seq_len);
+        let first = input[[0, 0]] as usize;
+        Array2::from_shape_fn((seq_len, self.vocab_size), |(_, j)| {
+            if j == first {
+                10.0
+            } else {
+                0.0
+            }
+        })
+    }
+
+    fn backward(&mut self, grads: &Array2<f32>, _lr: f32) -> Array2<f32> {
+        grads.clone()
+    }
+
+    fn parameters(&self) -> usize {
+        0
+    }
+}
+
+/// Emits the scripted token after the prompt, one per generated position.
+#[derive(Clone)]
+struct ScriptedLayer {
+    prompt_len: usize,
+    script: Vec<usize>,
+    vocab_size: usize,
+}
+
+impl Layer for ScriptedLayer {
+    fn layer_type(&self) -> &str {
+        "ScriptedLayer"
+    }
+
+    fn forward(&mut self, input: &Array2<f32>) -> Array2<f32> {
+        let seq_len = input.shape()[1];
+        let next = self.script[(seq_len - self.prompt_len).min(self.script.len() - 1)];
+        let mut logits = Array2::zeros((seq_len, self.vocab_size));
+        logits[[seq_len - 1, next]] = 10.0;
+        logits
+    }
+
+    fn backward(&mut self, grads: &Array2<f32>, _lr: f32) -> Array2<f32> {
+        grads.clone()
+    }
+
+    fn parameters(&self) -> usize {
+        0
+    }
+}
+
+fn constant_llm(logits: Vec<f32>) -> LLM {
+    LLM::new(
+        Vocab::default(),
+        vec![Box::new(ConstantLogitsLayer { logits })],
+    )
+}
+
+#[test]
+fn test_generate_respects_max_new_tokens() {
+    // Always prefer "hello" so generation never reaches </s>
+    let mut llm = constant_llm(vec![5.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+    let config = GenerationConfig {
+        max_new_tokens: 3,
+        ..Default::default()
+    };
+    assert_eq!(llm.generate("hello world", &config), "hello hello hello");
+}
+
+#[test]
+fn test_generate_stops_below_min_token_prob() {
+    // Uniform logits: the top token has probability 1/6
+    let mut llm = constant_llm(vec![0.0; 6]);
+    let config = GenerationConfig {
+        min_token_prob: Some(0.5),
+        ..Default::default()
+    };
+    assert_eq!(llm.generate("hello world", &config), "");
+
+    // A threshold below the top probability does not stop generation
+    let config = GenerationConfig {
+        max_new_tokens: 2,
+        min_token_prob: Some(0.1),
+        timeout: None,
+        user_prefix: None,
+        logit_bias: HashMap::new(),
+        context_overflow: ContextOverflow::ClampNewTokens,
+        sampling: None,
+        temperature_schedule: None,
+    };
+    assert!(!llm.generate("hello world", &config).is_empty());
+}
+
+#[test]
+fn test_generate_returns_partial_output_on_timeout() {
+    let mut llm = LLM::new(
+        Vocab::default(),
+        vec![
+            Box::new(SlowLayer {
+                delay: Duration::from_millis(10),
+            }),
+            Box::new(ConstantLogitsLayer {
+                logits: vec![5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            }),
+        ],
+    );
+    let config = GenerationConfig {
+        timeout: Some(Duration::from_millis(25)),
+        ..Default::default()
+    };
+
+    let started = Instant::now();
+    let output = llm.generate("hello", &config);
+    assert!(started.elapsed() < Duration::from_millis(500));
+
+    let generated = output.split_whitespace().count();
+    assert!(generated > 0 && generated < 10);
+
+    // A zero budget produces nothing
+    let config = GenerationConfig {
+        timeout: Some(Duration::ZERO),
+        ..Default::default()
+    };
+    assert_eq!(llm.generate("hello", &config), "");
+}
+
+#[test]
+fn test_nucleus_filter_top_p_one_keeps_everything() {
+    let probs = [0.1, 0.5, 0.15, 0.25];
+    let filtered = nucleus_filter(&probs, 1.0);
+
+    let indices: Vec<usize> = filtered.iter().map(|&(i, _)| i).collect();
+    assert_eq!(indices, vec![1, 3, 2, 0]);
+    for &(i, p) in &filtered {
+        assert!((p - probs[i]).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_nucleus_filter_boundary_at_max_prob() {
+    let probs = [0.1, 0.5, 0.15, 0.25];
+
+    // Reaching exactly the top token's mass keeps only that token
+    assert_eq!(nucleus_filter(&probs, 0.5), vec![(1, 1.0)]);
+    assert_eq!(nucleus_filter(&probs, 0.0), vec![(1, 1.0)]);
+
+    // Any more mass pulls in the runner-up
+    let filtered = nucleus_filter(&probs, 0.51);
+    let indices: Vec<usize> = filtered.iter().map(|&(i, _)| i).collect();
+    assert_eq!(indices, vec![1, 3]);
+}
+
+#[test]
+fn test_nucleus_filter_renormalizes() {
+    let probs = [0.05, 0.4, 0.3, 0.2, 0.05];
+    for top_p in [0.3, 0.6, 0.75, 0.9, 1.0] {
+        let total: f32 = nucleus_filter(&probs, top_p).iter().map(|&(_, p)| p).sum();
+        assert!((total - 1.0).abs() < 1e-5, "top_p {}: sum {}", top_p, total);
+    }
+}
+
+fn scripted_llm(prompt: &str, script: &[&str]) -> LLM {
+    let vocab = Vocab::new(vec!["hello", "world", "rust", "User", ":", "</s>"]);
+    let prompt_len = prompt.split_whitespace().count();
+    let script = script.iter().map(|w| vocab.encode(w).unwrap()).collect();
+    let vocab_size = vocab.size();
+    LLM::new(
+        vocab,
+        vec![Box::new(ScriptedLayer {
+            prompt_len,
+            script,
+            vocab_size,
+        })],
+    )
+}
+
+#[test]
+fn test_generate_stops_before_user_prefix() {
+    let config = GenerationConfig {
+        user_prefix: Some("User:".to_string()),
+        ..Default::default()
+    };
+
+    let script = ["world", "rust", "User", ":", "hello", "</s>"];
+    let mut llm = scripted_llm("hello", &script);
+    assert_eq!(llm.generate("hello", &config), "world rust");
+
+    // Without a user prefix the model runs on into the next turn
+    let mut llm = scripted_llm("hello", &script);
+    assert_eq!(
+        llm.generate("hello", &GenerationConfig::default()),
+        "world rust User : hello </s>"
+    );
+
+    // Only the complete prefix ends the turn
+    let mut llm = scripted_llm("hello", &["User", "rust", "</s>"]);
+    assert_eq!(llm.generate("hello", &config), "User rust </s>");
+}
+
+#[test]
+fn test_generate_applies_logit_bias() {
+    // Unbiased, "hello" (id 0) always wins
+    let mut llm = constant_llm(vec![5.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+    let forbid_hello = GenerationConfig {
+        max_new_tokens: 3,
+        logit_bias: HashMap::from([(0, f32::NEG_INFINITY)]),
+        ..Default::default()
+    };
+    let output = llm.generate("hello", &forbid_hello);
+    assert!(
+        !output.split_whitespace().any(|w| w == "hello"),
+        "{}",
+        output
+    );
+    assert_eq!(output, "world world world");
+
+    // A large positive bias forces the token ("rust", id 4)
+    let force_rust = GenerationConfig {
+        max_new_tokens: 3,
+        logit_bias: HashMap::from([(4, 100.0)]),
+        ..Default::default()
+    };
+    assert_eq!(llm.generate("hello", &force_rust), "rust rust rust");
+}
+
+#[test]
+fn test_generate_with_logprobs_scores_each_token() {
+    let mut llm = constant_llm(vec![2.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+    let config = GenerationConfig {
+        max_new_tokens: 4,
+        ..Default::default()
+    };
+
+    let (text, logprobs) = llm.generate_with_logprobs("hello", &config);
+    assert_eq!(text, llm.generate("hello", &config));
+    assert_eq!(logprobs.len(), text.split_whitespace().count());
+    assert_eq!(logprobs.len(), 4);
+
+    let expected = 2.0
+        - [2.0f32, 1.0, 0.0, 0.0, 0.0, 0.0]
+            .iter()
+            .map(|x| x.exp())
+            .sum::<f32>()
+            .ln();
+    for (word, logprob) in &logprobs {
+        assert_eq!(word, "hello");
+        assert!(*logprob <= 0.0);
+        assert!((logprob - expected).abs() < 1e-5);
+    }
+
+    // Stripped stop tokens take their log-probabilities with them
+    let config = GenerationConfig {
+        user_prefix: Some("User:".to_string()),
+        ..Default::default()
+    };
+    let mut llm = scripted_llm("hello", &["world", "rust", "User", ":", "</s>"]);
+    let (text, logprobs) = llm.generate_with_logprobs("hello", &config);
+    assert_eq!(text, "world rust");
+    let words: Vec<&str> = logprobs.iter().map(|(word, _)| word.as_str()).collect();
+    assert_eq!(words, ["world", "rust"]);
+    assert!(logprobs.iter().all(|(_, logprob)| *logprob <= 0.0));
+}
+
+#[test]
+fn test_length_normalized_score() {
+    assert_eq!(length_normalized_score(-3.0, 7, 0.0), -3.0);
+    // ((5 + 7) / 6)^1 = 2
+    assert_eq!(length_normalized_score(-3.0, 7, 1.0), -1.5);
+}
+
+#[test]
+fn test_generate_beam_length_penalty_favors_longer_hypotheses() {
+    // "hello" (id 0) is most likely at every step, but ending with "</s>" (id 5) right
+    // away has the highest raw log-probability of any finished hypothesis
+    let mut llm = constant_llm(vec![2.0, 0.0, 0.0, 0.0, 0.0, 1.5]);
+
+    let short = llm.generate_beam("hello", 2, 0.0, 2);
+    assert_eq!(short.len(), 2);
+    assert_eq!(short[0].0, "</s>");
+    assert!(short[0].1 >= short[1].1);
+
+    let long = llm.generate_beam("hello", 2, 2.0, 1);
+    assert_eq!(long.len(), 1);
+    let long_words = long[0].0.split_whitespace().count();
+    assert!(long_words > 1, "{}", long[0].0);
+    assert!(long[0].1 > short[0].1);
+}
+
+#[test]
+fn test_generate_contrastive_alpha_zero_is_greedy() {
+    let llm = constant_llm(vec![2.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+    assert_eq!(
+        llm.generate_contrastive("hello", 0.0, 3, 4),
+        "hello hello hello hello"
+    );
+
+    llm::rng::seed(5);
+    let mut llm = tiny_llm();
+    let greedy = GenerationConfig {
+        max_new_tokens: 6,
+        ..Default::default()
+    };
+    assert_eq!(
+        llm.generate_contrastive("hello world", 0.0, 4, 6),
+        llm.generate("hello world", &greedy)
+    );
+}
+
+#[test]
+fn test_generate_contrastive_avoids_repetition() {
+    llm::rng::seed(9);
+    let mut llm = tiny_llm();
+    llm.train(vec!["hello world this is rust </s>"], 30, 0.01);
+
+    let output = llm.generate_contrastive("hello", 0.6, 4, 6);
+    let words: Vec<&str> = output.split_whitespace().collect();
+    assert!(!words.is_empty());
+    assert!(
+        words.windows(2).all(|pair| pair[0] != pair[1]),
+        "degenerate output: {}",
+        output
+    );
+    assert_ne!(words[0], "hello", "{}", output);
+}
+
+#[test]
+fn test_generate_caps_context_window() {
+    let vocab = Vocab::default();
+    let vocab_size = vocab.size();
+    let mut llm = LLM::new(vocab, vec![Box::new(FirstTokenLayer { vocab_size })]);
+    // 78 tokens, starting with "world"
+    let prompt = format!("world{}", " hello".repeat(MAX_SEQ_LEN - 3));
+    let config = |context_overflow| GenerationConfig {
+        max_new_tokens: 5,
+        context_overflow,
+        ..Default::default()
+    };
+
+    // Default: the whole prompt is kept, so only two tokens fit
+    let clamped = llm.generate(&prompt, &config(ContextOverflow::ClampNewTokens));
+    assert_eq!(clamped, "world world");
+
+    // The oldest tokens ("world" first) are dropped to make room for all five
+    let truncated = llm.generate(&prompt, &config(ContextOverflow::TruncateOldest));
+    assert_eq!(truncated, "hello hello hello hello hello");
+
+    // More tokens than the window holds still leaves one prompt token
+    let huge = GenerationConfig {
+        max_new_tokens: 10 * MAX_SEQ_LEN,
+        context_overflow: ContextOverflow::TruncateOldest,
+        ..Default::default()
+    };
+    assert_eq!(
+        llm.generate(&prompt, &huge).split_whitespace().count(),
+        MAX_SEQ_LEN - 1
+    );
+
+    let err = llm
+        .try_generate(&prompt, &config(ContextOverflow::Error))
+        .unwrap_err();
+    assert!(err.to_string().contains("context window of 80"), "{}", err);
+    assert_eq!(llm.generate(&prompt, &config(ContextOverflow::Error)), "");
+    assert_eq!(
+        llm.try_generate("world hello", &config(ContextOverflow::Error))
+            .unwrap(),
+        "world world world world world"
+    );
+}
+
+#[test]
+fn test_zero_temperature_sampling_is_greedy() {
+    let logits = [1.0, 3.0, 3.0, -2.0, f32::NAN, 0.5];
+    let mut rng = rand::rng();
+    for temperature in [0.0, 1e-9, -1.0, f32::NAN] {
+        let options = SamplingOptions {
+            temperature,
+            ..Default::default()
+        };
+        assert!(options.is_greedy());
+        assert_eq!(options.sample(&logits, &mut rng), 1);
+    }
+
+    let mut llm = constant_llm(vec![0.5, 2.0, 0.0, 0.0, 0.0, 0.0]);
+    let greedy = GenerationConfig {
+        max_new_tokens: 4,
+        ..Default::default()
+    };
+    let zero_temperature = GenerationConfig {
+        sampling: Some(SamplingOptions {
+            temperature: 0.0,
+            ..Default::default()
+        }),
+        ..greedy.clone()
+    };
+    let (text, logprobs) = llm.generate_with_logprobs("hello", &zero_temperature);
+    assert_eq!(text, llm.generate("hello", &greedy));
+    assert_eq!(text, "world world world world");
+    assert!(logprobs.iter().all(|(_, logprob)| logprob.is_finite()));
+}
+
+#[test]
+fn test_temperature_schedule_reaches_end_value_on_last_step() {
+    let config = GenerationConfig {
+        max_new_tokens: 5,
+        temperature_schedule: Some((1.5, 0.5)),
+        sampling: Some(SamplingOptions {
+            temperature: 9.0,
+            top_p: 0.9,
+        }),
+        ..Default::default()
+    };
+    assert_eq!(config.temperature_at(0), Some(1.5));
+    assert_eq!(config.temperature_at(2), Some(1.0));
+    assert_eq!(config.temperature_at(4), Some(0.5));
+    let last = config.sampling_at(4).unwrap();
+    assert_eq!(last.temperature, 0.5);
+    assert_eq!(last.top_p, 0.9);
+    assert_eq!(GenerationConfig::default().temperature_at(0), None);
+
+    // Decaying to zero makes the final token greedy
+    llm::rng::seed(5);
+    let mut llm = constant_llm(vec![2.0, 1.9, -50.0, -50.0, -50.0, -50.0]);
+    let config = GenerationConfig {
+        max_new_tokens: 6,
+        temperature_schedule: Some((2.0, 0.0)),
+        ..Default::default()
+    };
+    assert!(config.sampling_at(5).unwrap().is_greedy());
+    let output = llm.generate("hello", &config);
+    let words: Vec<&str> = output.split_whitespace().collect();
+    assert_eq!(words.len(), 6);
+    assert_eq!(words[5], "hello");
+}
+
+#[test]
+fn test_sampling_draws_from_the_nucleus() {
+    llm::rng::seed(3);
+    let mut llm = constant_llm(vec![2.0, 2.0, -50.0, -50.0, -50.0, -50.0]);
+    let config = GenerationConfig {
+        max_new_tokens: 20,
+        sampling: Some(SamplingOptions::default()),
+        ..Default::default()
+    };
+    let output = llm.generate("hello", &config);
+    let words: std::collections::HashSet<&str> = output.split_whitespace().collect();
+    assert_eq!(words, ["hello", "world"].into_iter().collect());
+}
+
+#[test]
+fn test_generate_n_varies_only_when_sampling() {
+    llm::rng::seed(9);
+    let llm = constant_llm(vec![2.0, 2.0, -50.0, -50.0, -50.0, -50.0]);
+    let greedy = GenerationConfig {
+        max_new_tokens: 10,
+        ..Default::default()
+    };
+
+    let repeated = llm.generate_n("hello", 5, &greedy);
+    assert_eq!(repeated.len(), 5);
+    assert!(repeated.iter().all(|text| text == &repeated[0]));
+
+    let sampled = llm.generate_n(
+        "hello",
+        5,
+        &GenerationConfig {
+            sampling: Some(SamplingOptions::default()),
+            ..greedy
+        },
+    );
+    assert_eq!(sampled.len(), 5);
+    assert!(
+        sampled.iter().any(|text| text != &sampled[0]),
+        "{:?}",
+        sampled
+    );
+}
+
+#[test]
+fn test_explain_greedy_chooses_top_alternative() {
+    let llm = scripted_llm("hello", &["world", "rust", ":", "</s>"]);
+    let explanations = llm.explain("hello", &GenerationConfig::default());
+
+    let tokens: Vec<&str> = explanations.iter().map(|e| e.token.as_str()).collect();
+    assert_eq!(tokens, ["world", "rust", ":", "</s>"]);
+    for explanation in &explanations {
+        assert_eq!(explanation.alternatives.len(), 3);
+        assert_eq!(explanation.alternatives[0].0, explanation.token);
+        assert_eq!(explanation.alternatives[0].1, explanation.probability);
+        assert!(explanation
+            .alternatives
+            .windows(2)
+            .all(|pair| pair[0].1 >= pair[1].1));
+    }
+
+    let mut llm = constant_llm(vec![2.0, 1.0, 0.5, 0.0, 0.0, 0.0]);
+    let config = GenerationConfig {
+        max_new_tokens: 2,
+        ..Default::default()
+    };
+    let explanations = llm.explain("hello", &config);
+    assert_eq!(explanations.len(), 2);
+    let alternatives: Vec<&str> = explanations[0]
+        .alternatives
+        .iter()
+        .map(|(word, _)| word.as_str())
+        .collect();
+    assert_eq!(alternatives, ["hello", "world", "this"]);
+    assert_eq!(
+        llm.generate("hello", &config),
+        explanations
+            .iter()
+            .map(|e| e.token.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+}
@@ -1,9 +1,16 @@
+mod common;
+
+use common::{embedding_llm, tiny_llm};
+use std::sync::{Arc, Mutex};
+
 use llm::{
-    output_projection::OutputProjection, transformer::TransformerBlock, Embeddings, Layer, Vocab,
+    output_projection::OutputProjection, transformer::TransformerBlock, Config, Embeddings, Layer,
+    LlmError, SamplingOptions, TextNormalizer, TrainingObjective, TruncationStrategy, Vocab,
     EMBEDDING_DIM, HIDDEN_DIM, LLM, MAX_SEQ_LEN,
 };
 use ndarray::Array2;
 
+#[derive(Clone)]
 struct TestOutputProjectionLayer {
     pub cache_input: Option<Array2<f32>>,
     pub loop_count: usize,
@@ -84,6 +91,92 @@ fn test_llm_tokenize() {
     }
 }
 
+#[test]
+fn test_tokenize_with_stats_reports_oov_rate() {
+    let llm = LLM::default();
+
+    let (tokens, oov_rate) = llm.tokenize_with_stats("zork blorp quux");
+    assert!(tokens.is_empty());
+    assert_eq!(oov_rate, 1.0);
+
+    let (tokens, oov_rate) = llm.tokenize_with_stats("hello zork");
+    assert_eq!(tokens, llm.tokenize("hello zork"));
+    assert_eq!(oov_rate, 0.5);
+
+    assert_eq!(llm.tokenize_with_stats("hello world").1, 0.0);
+    assert_eq!(llm.tokenize_with_stats("").1, 0.0);
+}
+
+#[test]
+fn test_truncation_strategies_keep_expected_tokens() {
+    let tokens: Vec<usize> = (0..10).collect();
+    assert_eq!(
+        TruncationStrategy::Head.apply(tokens.clone(), 4),
+        vec![0, 1, 2, 3]
+    );
+    assert_eq!(
+        TruncationStrategy::Tail.apply(tokens.clone(), 4),
+        vec![6, 7, 8, 9]
+    );
+    assert_eq!(
+        TruncationStrategy::Middle.apply(tokens.clone(), 4),
+        vec![3, 4, 5, 6]
+    );
+    assert_eq!(TruncationStrategy::Middle.apply(tokens.clone(), 20), tokens);
+
+    let words: Vec<String> = (0..MAX_SEQ_LEN + 20).map(|i| format!("w{}", i)).collect();
+    let mut llm = LLM::new(
+        Vocab::new(words.iter().map(String::as_str).collect()),
+        vec![],
+    );
+    let text = words.join(" ");
+    assert_eq!(llm.truncation, TruncationStrategy::Tail);
+    assert_eq!(
+        llm.tokenize(&text),
+        (20..MAX_SEQ_LEN + 20).collect::<Vec<_>>()
+    );
+    llm.truncation = TruncationStrategy::Head;
+    assert_eq!(llm.tokenize(&text), (0..MAX_SEQ_LEN).collect::<Vec<_>>());
+    llm.truncation = TruncationStrategy::Middle;
+    assert_eq!(
+        llm.tokenize(&text),
+        (10..MAX_SEQ_LEN + 10).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_tokenize_with_options_controls_eos() {
+    let llm = LLM::default();
+    let eos = llm.vocab.encode("</s>").unwrap();
+
+    let with_eos = llm.tokenize_with_options("hello world", true);
+    assert_eq!(with_eos.last(), Some(&eos));
+    assert_eq!(with_eos[..with_eos.len() - 1], llm.tokenize("hello world"));
+
+    let without = llm.tokenize_with_options("hello world", false);
+    assert_ne!(without.last(), Some(&eos));
+    assert_eq!(without, llm.tokenize("hello world"));
+
+    // An existing end marker is not doubled
+    assert_eq!(
+        llm.tokenize_with_options("hello </s>", true),
+        llm.tokenize("hello </s>")
+    );
+
+    // The end marker is kept when the text is truncated
+    let long = "hello ".repeat(MAX_SEQ_LEN + 5);
+    let tokens = llm.tokenize_with_options(&long, true);
+    assert_eq!(tokens.len(), MAX_SEQ_LEN);
+    assert_eq!(tokens.last(), Some(&eos));
+
+    // ...including a text that already ends with one
+    let long = format!("{}</s>", "hello ".repeat(MAX_SEQ_LEN + 5));
+    let tokens = llm.tokenize_with_options(&long, true);
+    assert_eq!(tokens.len(), MAX_SEQ_LEN);
+    assert_eq!(tokens.last(), Some(&eos));
+    assert_eq!(tokens.iter().filter(|&&id| id == eos).count(), 1);
+}
+
 #[test]
 fn test_llm_predict() {
     let vocab = Vocab::default();
@@ -170,3 +263,584 @@ fn test_llm_total_parameters() {
                 + expected_output_projection_parameters
     );
 }
+
+#[test]
+fn test_llm_calibration_report() {
+    let vocab = Vocab::default();
+    let vocab_size = vocab.encode.len();
+
+    let embeddings = Box::new(Embeddings::new(vocab.clone()));
+    let output_projection = Box::new(OutputProjection::new(EMBEDDING_DIM, vocab_size));
+    let llm = LLM::new(vocab.clone(), vec![embeddings, output_projection]);
+
+    let texts = vec![
+        "hello world this is rust </s>".to_string(),
+        "rust is hello".to_string(),
+        "world".to_string(), // too short to score
+    ];
+    let scored_positions: usize = texts
+        .iter()
+        .map(|t| llm.tokenize(t).len().saturating_sub(1))
+        .sum();
+
+    let bins = 5;
+    let report = llm.calibration_report(&texts, bins);
+    assert_eq!(report.len(), bins);
+
+    let total: usize = report.iter().map(|(_, _, count)| count).sum();
+    assert_eq!(total, scored_positions);
+
+    for (bin, &(mean_prob, accuracy, count)) in report.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let lower = bin as f32 / bins as f32;
+        let upper = (bin + 1) as f32 / bins as f32;
+        assert!(mean_prob >= lower && mean_prob <= upper);
+        assert!((0.0..=1.0).contains(&accuracy));
+    }
+}
+
+/// Passes its input through unchanged, recording every forward input and backward
+/// gradient.
+#[derive(Clone, Default)]
+struct Probe {
+    inputs: Arc<Mutex<Vec<Array2<f32>>>>,
+    grads: Arc<Mutex<Vec<Array2<f32>>>>,
+}
+
+impl Layer for Probe {
+    fn layer_type(&self) -> &str {
+        "Probe"
+    }
+
+    fn forward(&mut self, input: &Array2<f32>) -> Array2<f32> {
+        self.inputs.lock().unwrap().push(input.clone());
+        input.clone()
+    }
+
+    fn backward(&mut self, grads: &Array2<f32>, _lr: f32) -> Array2<f32> {
+        self.grads.lock().unwrap().push(grads.clone());
+        grads.clone()
+    }
+
+    fn parameters(&self) -> usize {
+        0
+    }
+}
+
+#[test]
+fn test_masked_lm_loss_only_uses_masked_positions() {
+    let vocab = Vocab::new(vec![
+        "hello", "world", "this", "is", "rust", "</s>", "<mask>",
+    ]);
+    let token_probe = Probe::default();
+    let logits_probe = Probe::default();
+    let mut llm = LLM::new(
+        vocab.clone(),
+        vec![
+            Box::new(token_probe.clone()),
+            Box::new(Embeddings::new(vocab.clone())),
+            Box::new(OutputProjection::new(EMBEDDING_DIM, vocab.size())),
+            Box::new(logits_probe.clone()),
+        ],
+    );
+    llm.set_objective(TrainingObjective::MaskedLM { mask_prob: 0.5 })
+        .unwrap();
+    let mask_id = llm.vocab.encode("<mask>").unwrap();
+
+    let row = llm.tokenize("hello world this is rust </s>");
+    llm::rng::seed(3);
+    let stats = llm.train_step(&row, 0.01);
+
+    // The network saw the row with some, but not all, tokens replaced by <mask>
+    let input = token_probe.inputs.lock().unwrap()[0].clone();
+    let masked: Vec<usize> = (0..row.len())
+        .filter(|&i| input[[0, i]] as usize == mask_id)
+        .collect();
+    assert!(
+        !masked.is_empty() && masked.len() < row.len(),
+        "{:?}",
+        masked
+    );
+    assert_eq!(stats.scored, masked.len());
+
+    // The loss is the cross-entropy of the original tokens at the masked positions
+    let probs = LLM::softmax(&logits_probe.inputs.lock().unwrap()[0]);
+    let expected =
+        -masked.iter().map(|&i| probs[[i, row[i]]].ln()).sum::<f32>() / masked.len() as f32;
+    assert!(
+        (stats.loss - expected).abs() < 1e-4,
+        "{} vs {}",
+        stats.loss,
+        expected
+    );
+
+    // Only the masked positions send gradient back into the network
+    let grads = logits_probe.grads.lock().unwrap()[0].clone();
+    for (i, grad_row) in grads.rows().into_iter().enumerate() {
+        assert_eq!(
+            grad_row.iter().any(|&g| g != 0.0),
+            masked.contains(&i),
+            "row {}",
+            i
+        );
+    }
+
+    // The same seed draws the same masks
+    token_probe.inputs.lock().unwrap().clear();
+    llm::rng::seed(3);
+    llm.train_step(&row, 0.01);
+    assert_eq!(token_probe.inputs.lock().unwrap()[0], input);
+}
+
+#[test]
+fn test_mask_tokens_replaces_masked_positions() {
+    let tokens = vec![0, 1, 2, 3, 4];
+    let mask_id = 5;
+    let (input_ids, targets) = LLM::mask_tokens(&tokens, 0.0, mask_id, &mut rand::rng());
+
+    // At least one position is always masked
+    assert_eq!(targets.iter().filter(|t| t.is_some()).count(), 1);
+    for (i, target) in targets.iter().enumerate() {
+        match target {
+            Some(original) => {
+                assert_eq!(*original, tokens[i]);
+                assert_eq!(input_ids[i], mask_id);
+            }
+            None => assert_eq!(input_ids[i], tokens[i]),
+        }
+    }
+}
+
+#[test]
+fn test_masked_lm_objective_requires_mask_token() {
+    let mut llm = LLM::default();
+    assert!(llm.vocab.encode("<mask>").is_none());
+    let err = llm
+        .set_objective(TrainingObjective::MaskedLM { mask_prob: 0.15 })
+        .unwrap_err();
+    assert!(matches!(err, LlmError::VocabularyError(_)), "{}", err);
+    assert_eq!(llm.objective(), TrainingObjective::CausalLM);
+}
+
+#[test]
+fn test_masked_lm_training_end_to_end() {
+    let vocab = Vocab::new(vec![
+        "hello", "world", "this", "is", "rust", "</s>", "<mask>",
+    ]);
+    let mut llm = embedding_llm(vocab, || {
+        vec![Box::new(TransformerBlock::new(EMBEDDING_DIM, HIDDEN_DIM))]
+    });
+    assert!(llm
+        .set_objective(TrainingObjective::MaskedLM { mask_prob: 1.5 })
+        .is_err());
+    llm.set_objective(TrainingObjective::MaskedLM { mask_prob: 0.3 })
+        .unwrap();
+
+    // Bidirectional attention is only on for the masked steps; a model set up
+    // without the causal mask keeps it off
+    llm.set_causal(false);
+    llm::rng::seed(21);
+    let report = llm.train(vec!["hello world this is rust </s>"; 4], 20, 0.01);
+    assert_eq!(report.epochs.len(), 20);
+    assert!(report.epochs.iter().all(|e| e.loss.is_finite()));
+    assert!(report.epochs.last().unwrap().loss < report.epochs[0].loss);
+    assert_eq!(llm.network[1].causal(), Some(false));
+
+    llm.set_causal(true);
+    llm.train(vec!["hello world </s>"], 1, 0.01);
+    assert_eq!(llm.network[1].causal(), Some(true));
+}
+
+#[test]
+fn test_masked_lm_training_is_bidirectional() {
+    let vocab = Vocab::new(vec![
+        "hello", "world", "this", "is", "rust", "</s>", "<mask>",
+    ]);
+    let mut llm = embedding_llm(vocab, || {
+        vec![Box::new(TransformerBlock::new(EMBEDDING_DIM, HIDDEN_DIM))]
+    });
+    llm.set_objective(TrainingObjective::MaskedLM { mask_prob: 0.5 })
+        .unwrap();
+
+    let row = llm.tokenize("hello world this is rust");
+    let stats = llm.train_step(&row, 0.001);
+    assert!(stats.loss.is_finite() && stats.loss > 0.0);
+    assert!(stats.scored > 0 && stats.scored <= row.len());
+
+    // Without the causal mask the first position sees later tokens
+    let mut block = TransformerBlock::new(EMBEDDING_DIM, HIDDEN_DIM);
+    let a = Array2::from_shape_fn((3, EMBEDDING_DIM), |(i, j)| ((i + j) as f32).sin());
+    let mut b = a.clone();
+    b.row_mut(2).fill(1.0);
+
+    let causal_a = block.forward(&a);
+    let causal_b = block.forward(&b);
+    assert_eq!(causal_a.row(0), causal_b.row(0));
+
+    block.set_causal(false);
+    let bidirectional_a = block.forward(&a);
+    let bidirectional_b = block.forward(&b);
+    assert_ne!(bidirectional_a.row(0), bidirectional_b.row(0));
+}
+
+#[test]
+fn test_llm_expand_vocab() {
+    let vocab = Vocab::default();
+    let vocab_size = vocab.size();
+    let embeddings = Box::new(Embeddings::new(vocab.clone()));
+    let output_projection = Box::new(OutputProjection::new(EMBEDDING_DIM, vocab_size));
+    let mut llm = LLM::new(vocab, vec![embeddings, output_projection]);
+    let params_before = llm.total_parameters();
+
+    let id = llm.expand_vocab("crab");
+    assert_eq!(llm.vocab.size(), vocab_size + 1);
+    assert_eq!(llm.tokenize("crab"), vec![id]);
+    assert_eq!(llm.vocab.decode(id).map(|s| s.as_str()), Some("crab"));
+
+    // One new embedding row plus one new output column and bias
+    assert_eq!(
+        llm.total_parameters(),
+        params_before + 2 * EMBEDDING_DIM + 1
+    );
+
+    // The grown model can be trained on the new token
+    llm.train(vec!["hello crab </s>"], 1, 0.01);
+}
+
+#[test]
+fn test_llm_tokenize_with_normalizer() {
+    let vocab = Vocab::new(vec!["hello", "caf\u{e9}", "world", "</s>"]);
+    let vocab_size = vocab.size();
+    let mut llm = LLM::new(
+        vocab,
+        vec![Box::new(TestOutputProjectionLayer::new(3, 1, vocab_size))],
+    );
+    llm.normalizer = Some(TextNormalizer::default());
+
+    // Collapsed double spaces
+    assert_eq!(llm.tokenize("hello  world"), llm.tokenize("hello world"));
+
+    // Composed and decomposed forms map to the same tokens
+    let composed = llm.tokenize("hello caf\u{e9}");
+    let decomposed = llm.tokenize("hello cafe\u{301}");
+    assert_eq!(composed, decomposed);
+    assert_eq!(composed, vec![0, 1]);
+}
+
+#[test]
+fn test_llm_sequence_logprob_prefers_trained_phrase() {
+    llm::rng::seed(7);
+    let mut llm = tiny_llm();
+
+    let phrase = "hello world this is rust </s>";
+    llm.train(vec![phrase], 50, 0.01);
+
+    let in_distribution = llm.sequence_logprob(phrase);
+    let noise = llm.sequence_logprob("rust hello </s> is world this");
+    assert!(in_distribution < 0.0);
+    assert!(
+        in_distribution > noise,
+        "trained phrase {} should outscore noise {}",
+        in_distribution,
+        noise
+    );
+
+    assert_eq!(llm.sequence_logprob("hello"), 0.0);
+}
+
+#[test]
+fn test_greedy_decode_breaks_ties_by_lowest_id() {
+    let probs = Array2::from_shape_vec(
+        (3, 4),
+        vec![
+            0.1,
+            0.4,
+            0.1,
+            0.4, // tie between 1 and 3
+            0.25,
+            0.25,
+            0.25,
+            0.25, // all tied
+            f32::NAN,
+            0.2,
+            0.7,
+            0.7, // NaN first, then a tie
+        ],
+    )
+    .unwrap();
+    assert_eq!(LLM::greedy_decode(&probs), vec![1, 0, 2]);
+
+    // Greedy sampling during generation picks the same tokens
+    let greedy = SamplingOptions {
+        temperature: 0.0,
+        ..Default::default()
+    };
+    for (row, expected) in probs.rows().into_iter().zip([1, 0, 2]) {
+        let row = row.to_vec();
+        assert_eq!(greedy.sample(&row, &mut rand::rng()), expected);
+    }
+}
+
+#[test]
+fn test_load_checkpoint_reconciles_vocab_by_word() {
+    llm::rng::seed(1);
+    let source = embedding_llm(
+        Vocab::new(vec!["<pad>", "</s>", "sun", "moon", "star"]),
+        Vec::new,
+    );
+    let checkpoint = source.to_checkpoint(1, 0.5);
+
+    llm::rng::seed(2);
+    let mut target = embedding_llm(
+        Vocab::new(vec!["<pad>", "</s>", "comet", "star", "sun"]),
+        Vec::new,
+    );
+
+    let reconciliation = target.load_checkpoint(&checkpoint).unwrap();
+    assert_eq!(reconciliation.added, vec!["comet".to_string()]);
+    assert_eq!(reconciliation.dropped, vec!["moon".to_string()]);
+
+    let source_embeddings = source.network[0].weights()[0];
+    let target_embeddings = target.network[0].weights()[0];
+    assert_eq!(target_embeddings.dim(), (5, EMBEDDING_DIM));
+    for word in ["<pad>", "</s>", "sun", "star"] {
+        let old_id = source.vocab.encode(word).unwrap();
+        let new_id = target.vocab.encode(word).unwrap();
+        assert_eq!(target_embeddings.row(new_id), source_embeddings.row(old_id));
+        assert_eq!(
+            target.network[1].weights()[0].column(new_id),
+            source.network[1].weights()[0].column(old_id)
+        );
+    }
+    let comet = target_embeddings.row(target.vocab.encode("comet").unwrap());
+    assert!(comet.iter().all(|x| x.is_finite()));
+    assert!(source_embeddings.rows().into_iter().all(|row| row != comet));
+
+    // Positional embeddings do not depend on the vocabulary and load unchanged
+    assert_eq!(
+        target.network[0].weights()[1],
+        source.network[0].weights()[1]
+    );
+}
+
+#[test]
+fn test_load_checkpoint_rejects_other_architecture() {
+    let source = embedding_llm(Vocab::new(vec!["<pad>", "</s>", "sun"]), Vec::new);
+    let mut checkpoint = source.to_checkpoint(1, 0.5);
+    checkpoint.parameters.pop();
+
+    let mut target = embedding_llm(Vocab::new(vec!["<pad>", "</s>", "sun"]), Vec::new);
+    let before = target.network[0].weights()[0].clone();
+    assert!(target.load_checkpoint(&checkpoint).is_err());
+    assert_eq!(target.network[0].weights()[0], &before);
+}
+
+#[test]
+fn test_per_position_loss_covers_every_prediction() {
+    llm::rng::seed(3);
+    let llm = embedding_llm(
+        Vocab::new(vec!["<pad>", "</s>", "the", "sun", "is", "hot"]),
+        Vec::new,
+    );
+
+    let text = "the sun is hot </s>";
+    let token_count = llm.tokenize(text).len();
+    let losses = llm.per_position_loss(text);
+    assert_eq!(losses.len(), token_count - 1);
+    assert!(losses.iter().all(|loss| loss.is_finite() && *loss >= 0.0));
+
+    let total: f32 = losses.iter().sum();
+    assert!((llm.sequence_logprob(text) + total).abs() < 1e-4);
+    assert!(llm.per_position_loss("sun").is_empty());
+}
+
+#[test]
+fn test_from_pretrained_matches_saved_model() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("model.bin");
+
+    let mut config = Config::default();
+    config.model.num_blocks = 1;
+    config.model.hidden_dim = 32;
+    llm::rng::seed(11);
+    let mut model = LLM::from_config(&config, Vocab::default()).unwrap();
+    model.train(vec!["hello world this is rust </s>"], 5, 0.01);
+    model.save_pretrained(&path, &config).unwrap();
+
+    let mut loaded = LLM::from_pretrained(&path).unwrap();
+    assert_eq!(loaded.network_description(), model.network_description());
+    assert_eq!(loaded.vocab.words, model.vocab.words);
+    assert_eq!(loaded.predict("hello world"), model.predict("hello world"));
+}
+
+#[test]
+fn test_from_pretrained_requires_embedded_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("weights.bin");
+    let model = embedding_llm(Vocab::new(vec!["<pad>", "</s>", "sun"]), Vec::new);
+    model.to_checkpoint(1, 0.5).save(&path).unwrap();
+
+    assert!(matches!(
+        LLM::from_pretrained(&path),
+        Err(LlmError::ConfigError(_))
+    ));
+}
+
+#[test]
+fn test_tokenize_cached_reuses_and_invalidates() {
+    let mut llm = embedding_llm(Vocab::new(vec!["<pad>", "</s>", "the", "sun"]), Vec::new);
+
+    let first = llm.tokenize_cached("the sun rises");
+    assert_eq!(first, llm.tokenize("the sun rises"));
+    assert_eq!(llm.token_cache_len(), 1);
+    assert_eq!(llm.tokenize_cached("the sun rises"), first);
+    assert_eq!(llm.token_cache_len(), 1);
+
+    // Growing the vocabulary invalidates entries that dropped the new word
+    let rises = llm.expand_vocab("rises");
+    let updated = llm.tokenize_cached("the sun rises");
+    assert_eq!(updated.last(), Some(&rises));
+    assert_eq!(llm.token_cache_len(), 1);
+
+    // So does swapping in a different vocabulary of the same size
+    llm.vocab = Vocab::new(vec!["<pad>", "</s>", "rises", "sun", "the"]);
+    assert_eq!(llm.tokenize_cached("the sun rises"), vec![4, 3, 2]);
+
+    llm.token_cache_capacity = 0;
+    llm.clear_token_cache();
+    llm.tokenize_cached("the sun");
+    assert_eq!(llm.token_cache_len(), 0);
+}
+
+#[test]
+fn test_to_dot_describes_each_layer() {
+    let llm = LLM::default();
+    let dot = llm.to_dot();
+
+    assert!(dot.starts_with("digraph LLM {"));
+    assert_eq!(dot.matches('{').count(), dot.matches('}').count());
+    assert_eq!(dot.matches('"').count() % 2, 0);
+    for (idx, layer) in llm.network.iter().enumerate() {
+        let node = format!("layer{} [label=\"{}: {}", idx, idx, layer.layer_type());
+        assert!(dot.contains(&node), "missing {} in\n{}", node, dot);
+        assert!(dot.contains(&format!("params: {}", layer.parameters())));
+    }
+    assert_eq!(dot.matches(" -> ").count(), llm.network.len() - 1);
+    assert!(dot.contains(&format!(
+        "weights: {}x{}",
+        Vocab::default().size(),
+        EMBEDDING_DIM
+    )));
+}
+
+#[test]
+fn test_self_check_rejects_nan_weights() {
+    let mut llm = embedding_llm(Vocab::new(vec!["hello", "world", "</s>"]), Vec::new);
+    llm.self_check().unwrap();
+
+    llm.network[0].weights_mut()[0].fill(f32::NAN);
+    let err = llm.self_check().unwrap_err();
+    assert!(matches!(err, LlmError::TrainingError(_)), "{}", err);
+    assert!(err.to_string().contains("not finite"), "{}", err);
+}
+
+#[test]
+fn test_inference_bundle_round_trip_is_compact() {
+    llm::rng::seed(13);
+    let words = vec!["hello", "world", "this", "is", "rust", "</s>"];
+    let mut source = embedding_llm(Vocab::new(words.clone()), Vec::new);
+    source.train(vec!["hello world this is rust </s>"], 5, 0.01);
+
+    let dir = tempfile::tempdir().unwrap();
+    let bundle_path = dir.path().join("model.bundle");
+    let checkpoint_path = dir.path().join("model.ckpt");
+    source.export_inference_bundle(&bundle_path).unwrap();
+    source.to_checkpoint(5, 0.1).save(&checkpoint_path).unwrap();
+
+    let bundle_size = std::fs::metadata(&bundle_path).unwrap().len();
+    let checkpoint_size = std::fs::metadata(&checkpoint_path).unwrap().len();
+    assert!(
+        bundle_size < checkpoint_size,
+        "{} >= {}",
+        bundle_size,
+        checkpoint_size
+    );
+
+    let mut target = embedding_llm(Vocab::new(words), Vec::new);
+    assert!(target
+        .load_inference_bundle(&bundle_path)
+        .unwrap()
+        .is_identity());
+    assert_eq!(target.predict("hello world"), source.predict("hello world"));
+}
+
+#[test]
+fn test_try_new_rejects_mismatched_layer_dims() {
+    let vocab = Vocab::default();
+    let vocab_size = vocab.size();
+
+    let mismatched: Vec<Box<dyn Layer>> = vec![
+        Box::new(Embeddings::new(vocab.clone())),
+        Box::new(TransformerBlock::new(EMBEDDING_DIM / 2, HIDDEN_DIM)),
+        Box::new(OutputProjection::new(EMBEDDING_DIM / 2, vocab_size)),
+    ];
+    match LLM::try_new(vocab.clone(), mismatched) {
+        Err(LlmError::ArchitectureError(msg)) => {
+            assert!(msg.contains("layer 0 (Embeddings)"), "{}", msg);
+            assert!(msg.contains("layer 1 (TransformerBlock)"), "{}", msg);
+        }
+        Err(other) => panic!("unexpected error: {}", other),
+        Ok(_) => panic!("mismatched dimensions were accepted"),
+    }
+
+    let matched: Vec<Box<dyn Layer>> = vec![
+        Box::new(Embeddings::new(vocab.clone())),
+        Box::new(TransformerBlock::new(EMBEDDING_DIM, HIDDEN_DIM)),
+        Box::new(OutputProjection::new(EMBEDDING_DIM, vocab_size)),
+    ];
+    assert!(LLM::try_new(vocab, matched).is_ok());
+}
+
+#[test]
+fn test_evaluate_reports_finite_perplexity_and_accuracy() {
+    let mut llm = LLM::default();
+    llm.train(vec!["hello world this is rust </s>"], 3, 0.01);
+
+    let test_data = vec![
+        "hello world this is rust </s>".to_string(),
+        "rust is </s>".to_string(),
+        "zork".to_string(),
+    ];
+    let report = llm.evaluate(&test_data);
+    assert_eq!(report.tokens, 5 + 2);
+    assert!(report.loss.is_finite() && report.loss > 0.0);
+    assert!(report.perplexity.is_finite());
+    assert!((report.perplexity - report.loss.exp()).abs() < 1e-3);
+    assert!((0.0..=1.0).contains(&report.accuracy));
+    assert!(report.to_string().contains("Perplexity:"));
+
+    let empty = llm.evaluate(&[]);
+    assert_eq!((empty.tokens, empty.perplexity), (0, 1.0));
+}
+
+#[test]
+fn test_forward_capture_returns_layer_outputs() {
+    let llm = LLM::default();
+
+    let embedded = llm.forward_capture("hello world", 0).unwrap();
+    assert_eq!(embedded.dim(), (2, EMBEDDING_DIM));
+
+    let last = llm.network.len() - 1;
+    let logits = llm.forward_capture("hello world", last).unwrap();
+    assert_eq!(logits.dim(), (2, llm.vocab.size()));
+
+    match llm.forward_capture("hello world", llm.network.len()) {
+        Err(LlmError::ValidationError(msg)) => assert!(msg.contains("out of range"), "{}", msg),
+        other => panic!(
+            "expected a validation error, got {:?}",
+            other.map(|a| a.dim())
+        ),
+    }
+}
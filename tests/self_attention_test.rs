@@ -1,4 +1,4 @@
-use llm::{self_attention::SelfAttention, Layer, EMBEDDING_DIM};
+use llm::{self_attention::SelfAttention, Layer, EMBEDDING_DIM, LLM};
 use ndarray::Array2;
 
 #[test]
@@ -33,3 +33,89 @@ fn test_self_attention_with_different_sequence_lengths() {
         assert_eq!(output.shape(), [seq_len, EMBEDDING_DIM]);
     }
 }
+
+#[test]
+fn test_self_attention_ignores_pad_keys() {
+    let mut self_attention = SelfAttention::new(EMBEDDING_DIM);
+    let input = Array2::from_shape_fn((4, EMBEDDING_DIM), |(i, j)| ((i * 7 + j) % 5) as f32 * 0.1);
+    let mask = [false, true, false, true];
+
+    for causal in [true, false] {
+        self_attention.set_causal(causal);
+        self_attention.set_padding_mask(Some(&mask));
+        let weights = self_attention.attention_weights(&input);
+
+        for row in weights.rows() {
+            assert_eq!(row[1], 0.0);
+            assert_eq!(row[3], 0.0);
+            assert!((row.sum() - 1.0).abs() < 1e-5);
+        }
+        let output = self_attention.forward(&input);
+        assert!(output.iter().all(|x| x.is_finite()));
+    }
+
+    // Clearing the mask lets keys be attended again
+    self_attention.set_padding_mask(None);
+    let weights = self_attention.attention_weights(&input);
+    assert!(weights[[0, 1]] > 0.0);
+}
+
+#[test]
+fn test_self_attention_fully_masked_query_attends_nothing() {
+    let mut self_attention = SelfAttention::new(EMBEDDING_DIM);
+    let input = Array2::ones((3, EMBEDDING_DIM));
+
+    // With causal masking the first query can only see the first key, which is padding
+    self_attention.set_padding_mask(Some(&[true, false, false]));
+    let weights = self_attention.attention_weights(&input);
+    assert!(weights.row(0).iter().all(|&w| w == 0.0));
+    assert!(weights.iter().all(|w| w.is_finite()));
+
+    self_attention.forward(&input);
+    let grads = self_attention.backward(&Array2::ones((3, EMBEDDING_DIM)), 0.01);
+    assert!(grads.iter().all(|g| g.is_finite()));
+}
+
+#[test]
+fn test_self_attention_document_mask_blocks_cross_attention() {
+    let mut self_attention = SelfAttention::new(EMBEDDING_DIM);
+    let input = Array2::from_shape_fn((5, EMBEDDING_DIM), |(i, j)| ((i * 3 + j) % 7) as f32 * 0.1);
+    let documents = [0, 0, 1, 1, 1];
+
+    self_attention.set_causal(false);
+    self_attention.set_document_mask(Some(&documents));
+    let weights = self_attention.attention_weights(&input);
+    for ((i, j), &weight) in weights.indexed_iter() {
+        if documents[i] == documents[j] {
+            assert!(weight > 0.0);
+        } else {
+            assert_eq!(weight, 0.0);
+        }
+    }
+
+    self_attention.set_document_mask(None);
+    let weights = self_attention.attention_weights(&input);
+    assert!(weights[[0, 4]] > 0.0);
+}
+
+#[test]
+fn test_attention_entropy_of_uniform_and_one_hot_weights() {
+    let seq_len = 6;
+    let uniform = Array2::from_elem((seq_len, seq_len), 1.0 / seq_len as f32);
+    let entropy = SelfAttention::mean_entropy(&uniform);
+    assert!(
+        (entropy - (seq_len as f32).ln()).abs() < 1e-5,
+        "{}",
+        entropy
+    );
+
+    let one_hot = Array2::from_shape_fn((seq_len, seq_len), |(i, j)| (i == j) as u8 as f32);
+    assert!(SelfAttention::mean_entropy(&one_hot).abs() < 1e-6);
+
+    // One value per attention layer, each between the one-hot and uniform extremes
+    let llm = LLM::default();
+    let entropies = llm.attention_entropy("hello world this is rust");
+    assert_eq!(entropies.len(), 1);
+    assert!(entropies[0] >= 0.0 && entropies[0] <= (5.0f32).ln() + 1e-5);
+    assert!(llm.attention_entropy("zork").is_empty());
+}
@@ -0,0 +1,688 @@
+mod common;
+
+use common::{embedding_llm, logged_llm, seeded_llm, tiny_llm};
+use std::sync::{Arc, Mutex};
+
+use llm::{
+    Checkpoint, CurriculumStrategy, DatasetCursor, EpochContext, EpochMetrics, EpochStats,
+    GradientClip, Layer as ModelLayer, ProgressReporter, TrainingReport, Vocab, LLM,
+};
+use ndarray::Array2;
+use tracing::field::{Field, Visit};
+use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+/// A field recorded from a tracing event.
+#[derive(Debug, Clone, PartialEq)]
+enum FieldValue {
+    Float(f64),
+    Unsigned(u64),
+    Text(String),
+}
+
+type EventFields = Vec<(String, FieldValue)>;
+
+/// Records the fields of every event it sees.
+#[derive(Clone, Default)]
+struct RecordingLayer {
+    events: Arc<Mutex<Vec<EventFields>>>,
+}
+
+struct FieldRecorder(EventFields);
+
+impl Visit for FieldRecorder {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0
+            .push((field.name().to_string(), FieldValue::Float(value)));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0
+            .push((field.name().to_string(), FieldValue::Unsigned(value)));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.push((
+            field.name().to_string(),
+            FieldValue::Text(format!("{:?}", value)),
+        ));
+    }
+}
+
+impl<S: tracing::Subscriber> Layer<S> for RecordingLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let mut recorder = FieldRecorder(Vec::new());
+        event.record(&mut recorder);
+        self.events.lock().unwrap().push(recorder.0);
+    }
+}
+
+#[test]
+fn test_training_emits_structured_epoch_metrics() {
+    let recording = RecordingLayer::default();
+    let subscriber = tracing_subscriber::registry().with(recording.clone());
+
+    let mut llm = tiny_llm();
+    tracing::subscriber::with_default(subscriber, || {
+        llm.train(vec!["hello world this is rust </s>"], 3, 0.01);
+    });
+
+    let events = recording.events.lock().unwrap();
+    let epoch_events: Vec<_> = events
+        .iter()
+        .filter(|fields| fields.iter().any(|(name, _)| name == "loss"))
+        .collect();
+    assert_eq!(epoch_events.len(), 3);
+
+    for (i, fields) in epoch_events.iter().enumerate() {
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.clone())
+                .unwrap_or_else(|| panic!("missing field {}", name))
+        };
+
+        match field("loss") {
+            FieldValue::Float(loss) => assert!(loss.is_finite() && loss > 0.0),
+            other => panic!("loss should be numeric, got {:?}", other),
+        }
+        assert!(matches!(field("accuracy"), FieldValue::Float(a) if (0.0..=1.0).contains(&a)));
+        assert!(matches!(field("grad_norm"), FieldValue::Float(g) if g.is_finite()));
+        assert!(matches!(field("lr"), FieldValue::Float(lr) if (lr - 0.01).abs() < 1e-6));
+        assert_eq!(field("epoch"), FieldValue::Unsigned(i as u64 + 1));
+    }
+}
+
+#[test]
+fn test_profile_step_reports_each_layer() {
+    let mut llm = tiny_llm();
+    let profile = llm.profile_step("hello world this is rust </s>", 0.01);
+
+    assert_eq!(profile.len(), llm.network.len());
+    let names: Vec<&str> = profile.iter().map(|(name, _, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["Embeddings", "OutputProjection"]);
+    for (_, forward, backward) in &profile {
+        assert!(*forward >= std::time::Duration::ZERO);
+        assert!(*backward >= std::time::Duration::ZERO);
+    }
+
+    // Inputs too short to train on produce no profile
+    assert!(llm.profile_step("hello", 0.01).is_empty());
+}
+
+fn all_weights(llm: &LLM) -> Vec<Array2<f32>> {
+    llm.network
+        .iter()
+        .flat_map(|layer| layer.weights())
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn test_reinitialize_matches_fresh_model() {
+    let fresh = seeded_llm(7);
+
+    let mut model = seeded_llm(99);
+    model.train(vec!["hello world this is rust </s>"], 2, 0.01);
+    assert_ne!(all_weights(&model), all_weights(&fresh));
+
+    model.reinitialize(7);
+    assert_eq!(all_weights(&model), all_weights(&fresh));
+
+    // Optimizer state is reset too: one more step lands on identical weights
+    let mut fresh = fresh;
+    fresh.train(vec!["hello world this is rust </s>"], 1, 0.01);
+    model.train(vec!["hello world this is rust </s>"], 1, 0.01);
+    assert_eq!(all_weights(&model), all_weights(&fresh));
+}
+
+#[test]
+fn test_training_report_ranks_examples_by_loss() {
+    let data = ["a", "b", "c", "d", "e"];
+    let losses = [(0, 1.5), (1, 0.2), (2, 3.0), (3, 0.9), (4, 2.1)];
+
+    let mut report = TrainingReport::default();
+    report.record_epoch(EpochStats::default(), &data, &losses, 2);
+
+    let hardest: Vec<(usize, &str)> = report
+        .hardest_examples(1)
+        .iter()
+        .map(|e| (e.index, e.text.as_str()))
+        .collect();
+    assert_eq!(hardest, vec![(2, "c"), (4, "e")]);
+
+    let easiest: Vec<(usize, f32)> = report
+        .easiest_examples(1)
+        .iter()
+        .map(|e| (e.index, e.loss))
+        .collect();
+    assert_eq!(easiest, vec![(1, 0.2), (3, 0.9)]);
+
+    assert!(report.hardest_examples(0).is_empty());
+    assert!(report.hardest_examples(2).is_empty());
+}
+
+#[test]
+fn test_train_tracks_examples_when_enabled() {
+    let mut llm = tiny_llm();
+    let data = vec!["hello world </s>", "the sun is hot </s>", "hello"];
+
+    let report = llm.train(data.clone(), 1, 0.001);
+    assert_eq!(report.epochs.len(), 1);
+    assert!(report.hardest_examples(1).is_empty());
+
+    llm.track_examples = 1;
+    let report = llm.train(data, 2, 0.001);
+    assert_eq!(report.epochs.len(), 2);
+    let hardest = &report.hardest_examples(2)[0];
+    let easiest = &report.easiest_examples(2)[0];
+    assert!(hardest.loss >= easiest.loss);
+    // The single-token row cannot be scored and is never ranked
+    assert_ne!(hardest.index, 2);
+    assert_ne!(easiest.index, 2);
+}
+
+#[test]
+fn test_resume_from_checkpointed_cursor() {
+    let inputs = Arc::new(Mutex::new(Vec::new()));
+    let mut llm = logged_llm(&inputs);
+    let data = vec![
+        "hello world </s>",
+        "the sun is hot </s>",
+        "rust is fast </s>",
+    ];
+    // Each step feeds all but the last token of its row
+    let expected: Vec<Vec<usize>> = data
+        .iter()
+        .map(|text| {
+            let tokens = llm.tokenize(text);
+            tokens[..tokens.len() - 1].to_vec()
+        })
+        .collect();
+
+    // Stop after the second sample of the second epoch
+    let mut cursor = DatasetCursor::default();
+    llm.train_resumable(data.clone(), 3, 0.001, &mut cursor, |c| {
+        *c == DatasetCursor::new(1, 2)
+    });
+    assert_eq!(inputs.lock().unwrap().len(), 5);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("resume.bin");
+    let mut checkpoint = Checkpoint::new(cursor.epoch, 0.0, "test_config");
+    checkpoint.cursor = cursor;
+    checkpoint.save(&path).unwrap();
+
+    let mut restored = Checkpoint::load(&path).unwrap().cursor;
+    assert_eq!(restored, DatasetCursor::new(1, 2));
+
+    inputs.lock().unwrap().clear();
+    let report = llm.train_resumable(data, 3, 0.001, &mut restored, |_| false);
+    assert_eq!(restored, DatasetCursor::new(3, 0));
+
+    // The rest of epoch 2, then all of epoch 3; nothing already trained is replayed
+    let seen = inputs.lock().unwrap().clone();
+    assert_eq!(
+        seen,
+        vec![
+            expected[2].clone(),
+            expected[0].clone(),
+            expected[1].clone(),
+            expected[2].clone()
+        ]
+    );
+    let epochs: Vec<usize> = report.epochs.iter().map(|e| e.epoch).collect();
+    assert_eq!(epochs, vec![2, 3]);
+}
+
+#[test]
+fn test_resume_with_cursor_past_dataset_end_starts_next_epoch() {
+    let inputs = Arc::new(Mutex::new(Vec::new()));
+    let mut llm = logged_llm(&inputs);
+    let data = vec!["hello world </s>", "rust is fast </s>"];
+
+    // Saved against a larger dataset, part-way through epoch 1
+    let mut cursor = DatasetCursor::new(0, 5);
+    let report = llm.train_resumable(data, 2, 0.001, &mut cursor, |_| false);
+    assert_eq!(cursor, DatasetCursor::new(2, 0));
+    assert_eq!(inputs.lock().unwrap().len(), 2);
+    let epochs: Vec<usize> = report.epochs.iter().map(|e| e.epoch).collect();
+    assert_eq!(epochs, vec![2]);
+}
+
+#[test]
+fn test_resume_follows_the_shuffled_epoch_order() {
+    let data = vec![
+        "hello world </s>",
+        "the sun is hot </s>",
+        "rust is fast </s>",
+        "this is rust </s>",
+    ];
+
+    let uninterrupted = Arc::new(Mutex::new(Vec::new()));
+    let mut llm = logged_llm(&uninterrupted);
+    llm.curriculum = Some(CurriculumStrategy::Shuffle { seed: 9 });
+    llm.train(data.clone(), 2, 0.001);
+
+    let resumed = Arc::new(Mutex::new(Vec::new()));
+    let mut llm = logged_llm(&resumed);
+    llm.curriculum = Some(CurriculumStrategy::Shuffle { seed: 9 });
+    let mut cursor = DatasetCursor::default();
+    llm.train_resumable(data.clone(), 2, 0.001, &mut cursor, |c| {
+        *c == DatasetCursor::new(0, 2)
+    });
+    llm.train_resumable(data, 2, 0.001, &mut cursor, |_| false);
+    assert_eq!(cursor, DatasetCursor::new(2, 0));
+
+    // Same samples in the same shuffled order, none skipped or replayed
+    let uninterrupted = uninterrupted.lock().unwrap().clone();
+    assert_eq!(*resumed.lock().unwrap(), uninterrupted);
+    assert_ne!(uninterrupted[..4], uninterrupted[4..]);
+}
+
+#[test]
+fn test_lr_range_test_leaves_model_untouched() {
+    let llm = seeded_llm(11);
+    let before = all_weights(&llm);
+
+    let data = [
+        "hello world this is rust </s>",
+        "rust is hello world </s>",
+        "hello",
+    ];
+    let curve = llm.lr_range_test(&data, 1e-5, 1e-1, 8);
+
+    assert_eq!(curve.len(), 8);
+    assert!((curve[0].0 - 1e-5).abs() < 1e-9);
+    assert!((curve[7].0 - 1e-1).abs() < 1e-4);
+    assert!(curve.windows(2).all(|pair| pair[1].0 > pair[0].0));
+    assert!(curve
+        .iter()
+        .all(|&(_, loss)| loss.is_finite() && loss > 0.0));
+
+    // Bounds that cannot be swept upward on a log scale give no curve
+    assert!(llm.lr_range_test(&data, 0.0, 1e-1, 8).is_empty());
+    assert!(llm.lr_range_test(&data, -1e-3, 1e-1, 8).is_empty());
+    assert!(llm.lr_range_test(&data, 1e-1, 1e-5, 8).is_empty());
+
+    assert_eq!(all_weights(&llm), before);
+}
+
+/// Scales activations up on the way forward but passes gradients back unchanged, so the
+/// layer above sees exploding parameter gradients while the layers below do not.
+#[derive(Clone)]
+struct LoudActivations;
+
+impl ModelLayer for LoudActivations {
+    fn layer_type(&self) -> &str {
+        "LoudActivations"
+    }
+
+    fn forward(&mut self, input: &Array2<f32>) -> Array2<f32> {
+        input * 1e4
+    }
+
+    fn backward(&mut self, grads: &Array2<f32>, _lr: f32) -> Array2<f32> {
+        grads.clone()
+    }
+
+    fn parameters(&self) -> usize {
+        0
+    }
+}
+
+/// Weights before and after one training step of the same seeded model.
+fn weights_around_step(clip: GradientClip) -> (Vec<Array2<f32>>, Vec<Array2<f32>>) {
+    llm::rng::seed(17);
+    let mut llm = embedding_llm(Vocab::default(), || vec![Box::new(LoudActivations)]);
+    llm.gradient_clip = clip;
+    let before = all_weights(&llm);
+    llm.train(vec!["hello world this is rust </s>"], 1, 0.01);
+    (before, all_weights(&llm))
+}
+
+#[test]
+fn test_per_layer_clipping_scales_only_exploding_layer() {
+    // Weights are token and positional embeddings, then output weights and bias
+    let (before, raw) = weights_around_step(GradientClip::Global(f32::INFINITY));
+    let (_, clipped) = weights_around_step(GradientClip::PerLayer(10.0));
+
+    // The embeddings' gradient is within bounds, so their update is untouched
+    assert_eq!(clipped[..2], raw[..2]);
+
+    // The output bias takes a plain SGD step, so its update shows the gradient scale:
+    // every entry shrinks by the same factor
+    let raw_step = &before[3] - &raw[3];
+    let clipped_step = &before[3] - &clipped[3];
+    let norm = |step: &Array2<f32>| step.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let scale = norm(&clipped_step) / norm(&raw_step);
+    assert!(scale > 0.0 && scale < 0.1, "scale {}", scale);
+    for (clipped, raw) in clipped_step.iter().zip(raw_step.iter()) {
+        assert!((clipped - raw * scale).abs() <= 1e-6 + raw.abs() * 1e-3);
+    }
+
+    // Global clipping only sees the well-behaved output gradient and lets it through
+    let (_, global) = weights_around_step(GradientClip::Global(10.0));
+    assert_eq!(global, raw);
+}
+
+#[test]
+fn test_full_teacher_forcing_feeds_ground_truth() {
+    let inputs = Arc::new(Mutex::new(Vec::new()));
+    llm::rng::seed(5);
+    let mut llm = logged_llm(&inputs);
+    assert_eq!(llm.teacher_forcing_ratio, 1.0);
+    let mut fresh = llm.clone();
+
+    let data = vec!["hello world this is rust </s>", "the sun is hot </s>"];
+    let report = llm.train(data.clone(), 2, 0.01);
+
+    // One forward pass per step over exactly the ground-truth prefix
+    let expected: Vec<Vec<usize>> = data
+        .iter()
+        .map(|text| {
+            let tokens = llm.tokenize(text);
+            tokens[..tokens.len() - 1].to_vec()
+        })
+        .collect();
+    let logged = inputs.lock().unwrap().clone();
+    assert_eq!(logged.len(), 4);
+    assert_eq!(logged[..2], expected[..]);
+    assert_eq!(logged[2..], expected[..]);
+
+    assert_eq!(report.epochs.len(), 2);
+
+    // The step's loss is that of a plain forward pass over the ground truth
+    let tokens = fresh.tokenize(data[0]);
+    let (input_ids, target_ids) = (&tokens[..tokens.len() - 1], &tokens[1..]);
+    let mut logits = Array2::from_shape_fn((1, input_ids.len()), |(_, i)| input_ids[i] as f32);
+    for layer in fresh.clone().network.iter_mut() {
+        logits = layer.forward(&logits);
+    }
+    let expected = LLM::cross_entropy_from_logits(&logits, target_ids);
+    let stats = fresh.train_step(&tokens, 0.01);
+    assert!(
+        (stats.loss - expected).abs() < 1e-6,
+        "{} vs {}",
+        stats.loss,
+        expected
+    );
+}
+
+#[test]
+fn test_scheduled_sampling_feeds_predictions() {
+    let inputs = Arc::new(Mutex::new(Vec::new()));
+    let mut llm = logged_llm(&inputs);
+    llm.teacher_forcing_ratio = 0.0;
+
+    let text = "hello world this is rust </s>";
+    let tokens = llm.tokenize(text);
+    llm.train(vec![text], 1, 0.01);
+
+    // A ground-truth pass to get predictions, then a pass over them
+    let logged = inputs.lock().unwrap().clone();
+    assert_eq!(logged.len(), 2);
+    assert_eq!(logged[0], tokens[..tokens.len() - 1]);
+    assert_eq!(logged[1].len(), logged[0].len());
+    assert_eq!(logged[1][0], tokens[0]);
+}
+
+#[test]
+fn test_scheduled_sampling_is_reproducible_under_a_seed() {
+    let run = || {
+        let inputs = Arc::new(Mutex::new(Vec::new()));
+        llm::rng::seed(4);
+        let mut llm = logged_llm(&inputs);
+        llm.teacher_forcing_ratio = 0.5;
+        llm.train(vec!["hello world this is rust </s>"; 3], 2, 0.01);
+        let logged = inputs.lock().unwrap().clone();
+        logged
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn test_train_with_callback_fires_each_epoch_and_stops() {
+    let mut llm = tiny_llm();
+    let data = vec!["hello world this is rust </s>"];
+
+    let mut seen = Vec::new();
+    let report = llm.train_with_callback(data.clone(), 3, 0.01, |ctx: EpochContext| {
+        seen.push((ctx.epoch, ctx.avg_loss));
+    });
+    assert_eq!(report.epochs.len(), 3);
+    assert_eq!(
+        seen.iter().map(|&(epoch, _)| epoch).collect::<Vec<_>>(),
+        vec![0, 1, 2]
+    );
+    assert_eq!(seen[0].1, None);
+    assert_eq!(seen[1].1, Some(report.epochs[0].loss));
+    assert_eq!(seen[2].1, Some(report.epochs[1].loss));
+
+    let mut calls = 0;
+    let report = llm.train_with_callback(data, 10, 0.01, |ctx| {
+        calls += 1;
+        if ctx.epoch == 2 {
+            *ctx.stop = true;
+        }
+    });
+    assert_eq!(calls, 3);
+    assert_eq!(report.epochs.len(), 2);
+
+    // A learning rate set by the callback applies from that epoch on
+    let report = llm.train_with_callback(vec!["rust is </s>"], 3, 0.01, |ctx| {
+        if ctx.epoch == 1 {
+            *ctx.lr = 0.002;
+        }
+    });
+    let lrs: Vec<f32> = report.epochs.iter().map(|stats| stats.lr).collect();
+    assert_eq!(lrs, vec![0.01, 0.002, 0.002]);
+}
+
+#[derive(Default)]
+struct RecordingReporter {
+    started: Vec<usize>,
+    ended: Vec<(usize, f32)>,
+    finished: usize,
+}
+
+impl ProgressReporter for RecordingReporter {
+    fn on_epoch_start(&mut self, epoch: usize) {
+        self.started.push(epoch);
+    }
+
+    fn on_epoch_end(&mut self, epoch: usize, loss: f32) {
+        self.ended.push((epoch, loss));
+    }
+
+    fn finish(&mut self) {
+        self.finished += 1;
+    }
+}
+
+#[test]
+fn test_train_with_progress_reports_each_epoch() {
+    let mut llm = tiny_llm();
+    let mut reporter = RecordingReporter::default();
+    let report = llm.train_with_progress(
+        vec!["hello world this is rust </s>"],
+        3,
+        0.01,
+        &mut reporter,
+    );
+
+    assert_eq!(reporter.started, vec![1, 2, 3]);
+    let expected: Vec<(usize, f32)> = report
+        .epochs
+        .iter()
+        .enumerate()
+        .map(|(i, stats)| (i + 1, stats.loss))
+        .collect();
+    assert_eq!(reporter.ended, expected);
+    assert_eq!(reporter.finished, 1);
+}
+
+#[test]
+fn test_train_with_channel_sends_one_message_per_epoch() {
+    let mut llm = tiny_llm();
+    let (tx, rx) = std::sync::mpsc::channel::<EpochMetrics>();
+    let report = llm.train_with_channel(vec!["hello world this is rust </s>"], 3, 0.01, tx);
+
+    let metrics: Vec<EpochMetrics> = rx.iter().collect();
+    assert_eq!(metrics.len(), 3);
+    for (i, m) in metrics.iter().enumerate() {
+        assert_eq!(m.epoch, i + 1);
+        assert_eq!(m.loss, report.epochs[i].loss);
+        assert!(m.loss.is_finite() && m.accuracy.is_finite() && m.grad_norm.is_finite());
+    }
+
+    // A dropped receiver does not stop training
+    let (tx, rx) = std::sync::mpsc::channel();
+    drop(rx);
+    let report = llm.train_with_channel(vec!["rust is </s>"], 2, 0.01, tx);
+    assert_eq!(report.epochs.len(), 2);
+}
+
+#[test]
+fn test_sample_prompts_are_completed_every_interval() {
+    let mut llm = tiny_llm();
+    llm.sample_prompts = vec!["hello".to_string()];
+    llm.sample_interval = 5;
+
+    let report = llm.train_with_progress(
+        vec!["hello world this is rust </s>"],
+        10,
+        0.01,
+        &mut llm::NoProgress,
+    );
+    let epochs: Vec<usize> = report.samples.iter().map(|sample| sample.epoch).collect();
+    assert_eq!(epochs, vec![5, 10]);
+    assert!(report.samples.iter().all(|sample| sample.prompt == "hello"));
+
+    llm.sample_interval = 0;
+    assert!(llm.train(vec!["rust is </s>"], 5, 0.01).samples.is_empty());
+}
+
+#[test]
+fn test_by_length_curriculum_visits_short_samples_first() {
+    let inputs = Arc::new(Mutex::new(Vec::new()));
+    llm::rng::seed(2);
+    let mut llm = logged_llm(&inputs);
+    llm.curriculum = Some(CurriculumStrategy::ByLength { epochs: 1 });
+
+    let data = vec![
+        "hello world this is rust </s>",
+        "rust </s>",
+        "this is rust </s>",
+        "hello world </s>",
+    ];
+    let report = llm.train(data.clone(), 2, 0.01);
+    assert_eq!(report.epochs.len(), 2);
+
+    let logged = inputs.lock().unwrap().clone();
+    assert_eq!(logged.len(), 2 * data.len());
+    let first_epoch: Vec<usize> = logged[..data.len()].iter().map(Vec::len).collect();
+    assert!(
+        first_epoch.windows(2).all(|pair| pair[0] <= pair[1]),
+        "{:?}",
+        first_epoch
+    );
+    // Later epochs still visit every sample exactly once
+    let mut second_epoch: Vec<usize> = logged[data.len()..].iter().map(Vec::len).collect();
+    second_epoch.sort_unstable();
+    assert_eq!(second_epoch, first_epoch);
+
+    assert_eq!(
+        "by_length:3".parse::<CurriculumStrategy>(),
+        Ok(CurriculumStrategy::ByLength { epochs: 3 })
+    );
+    assert!("by_size:3".parse::<CurriculumStrategy>().is_err());
+}
+
+#[test]
+fn test_shuffle_curriculum_visits_each_sample_once_per_epoch() {
+    let mut llm = LLM::default();
+    llm.curriculum = Some(CurriculumStrategy::Shuffle { seed: 11 });
+    let rows: Vec<Vec<usize>> = (0..50).map(|i| vec![i % 7; i % 5 + 1]).collect();
+
+    let first = llm.epoch_order(0, &rows);
+    let mut covered = first.clone();
+    covered.sort_unstable();
+    assert_eq!(covered, (0..rows.len()).collect::<Vec<_>>());
+    assert_ne!(first, covered, "the permutation should not be the identity");
+
+    // Each epoch gets its own order, reproducible from the seed
+    let second = llm.epoch_order(1, &rows);
+    assert_ne!(first, second);
+    assert_eq!(llm.epoch_order(0, &rows), first);
+
+    // Seed and epoch are not simply added: (s, e + 1) and (s + 1, e) differ
+    llm.curriculum = Some(CurriculumStrategy::Shuffle { seed: 12 });
+    assert_ne!(llm.epoch_order(0, &rows), second);
+
+    assert_eq!(
+        "shuffle:11".parse::<CurriculumStrategy>(),
+        Ok(CurriculumStrategy::Shuffle { seed: 11 })
+    );
+}
+
+#[test]
+fn test_gradient_norm_is_exact_and_leaves_input_unchanged() {
+    let grads = Array2::from_shape_vec((2, 2), vec![3.0, 4.0, 12.0, 0.0]).unwrap();
+    let before = grads.clone();
+    assert_eq!(LLM::gradient_norm(&grads), 13.0);
+    assert_eq!(grads, before);
+
+    // Clipping still scales down to the same norm computation
+    let mut clipped = grads.clone();
+    LLM::clip_gradients(&mut clipped, 6.5);
+    assert!((LLM::gradient_norm(&clipped) - 6.5).abs() < 1e-5);
+    assert_eq!(LLM::gradient_norm(&Array2::zeros((3, 3))), 0.0);
+}
+
+#[test]
+fn test_training_a_clone_leaves_the_original_untouched() {
+    let mut original = seeded_llm(5);
+    let weights = all_weights(&original);
+    let prediction = original.predict("hello world");
+
+    let mut twin = original.clone();
+    let mut experiment = original.clone();
+    experiment.train(vec!["hello world this is rust </s>"], 3, 0.01);
+    assert_ne!(all_weights(&experiment), weights);
+
+    assert_eq!(all_weights(&original), weights);
+    assert_eq!(original.predict("hello world"), prediction);
+
+    // Optimizer state is not shared either: the original and an untouched clone still
+    // train identically
+    original.train(vec!["rust is </s>"], 2, 0.01);
+    twin.train(vec!["rust is </s>"], 2, 0.01);
+    assert_eq!(all_weights(&original), all_weights(&twin));
+}
+
+#[test]
+fn test_embeddings_stay_frozen_during_warmup() {
+    let embeddings = |llm: &LLM| llm.network[0].weights()[0].clone();
+    let data = vec!["hello world this is rust </s>"];
+    let mut warmup = seeded_llm(4);
+    warmup.freeze_embeddings_epochs = 2;
+    let mut longer = warmup.clone();
+    let initial = embeddings(&warmup);
+    let initial_block = warmup.network[1].weights()[0].clone();
+
+    // The rest of the network trains while the embeddings are frozen
+    warmup.train(data.clone(), 2, 0.01);
+    assert_eq!(embeddings(&warmup), initial);
+    assert_ne!(warmup.network[1].weights()[0], &initial_block);
+    assert!(!warmup.is_layer_trainable(0));
+
+    // From the epoch after the warm-up the embeddings train again
+    longer.train(data, 3, 0.01);
+    assert_ne!(embeddings(&longer), initial);
+    assert!(longer.is_layer_trainable(0));
+
+    assert!(longer
+        .set_layer_trainable(longer.network.len(), false)
+        .is_err());
+}
@@ -1,4 +1,4 @@
-use llm::{transformer::TransformerBlock, Layer, EMBEDDING_DIM, HIDDEN_DIM};
+use llm::{layer_norm::LayerNorm, transformer::TransformerBlock, Layer, EMBEDDING_DIM, HIDDEN_DIM};
 use ndarray::Array2;
 
 #[test]
@@ -14,3 +14,21 @@ fn test_transformer_block() {
     // Check output shape
     assert_eq!(output.shape(), [1, EMBEDDING_DIM]);
 }
+
+#[test]
+fn test_zero_residual_scale_is_identity_modulo_norm() {
+    let mut block = TransformerBlock::new(EMBEDDING_DIM, HIDDEN_DIM).with_residual_scale(0.0);
+    let input = Array2::from_shape_fn((3, EMBEDDING_DIM), |(i, j)| ((i * 7 + j) as f32).sin());
+
+    let output = block.forward(&input);
+    let mut norm = LayerNorm::new(EMBEDDING_DIM);
+    let normalized = norm.normalize(&input);
+    let expected = norm.normalize(&normalized);
+    for (a, b) in output.iter().zip(expected.iter()) {
+        assert!((a - b).abs() < 1e-5, "{} vs {}", a, b);
+    }
+
+    // Gradients still flow through the residual path
+    let grads = block.backward(&Array2::ones((3, EMBEDDING_DIM)), 0.01);
+    assert!(grads.iter().all(|g| g.is_finite()));
+}
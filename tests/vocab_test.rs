@@ -1,4 +1,4 @@
-use llm::Vocab;
+use llm::{BpeTokenizer, LlmError, Vocab};
 
 #[test]
 fn test_vocab_encode_decode() {
@@ -25,3 +25,40 @@ fn test_vocab_default() {
     assert!(vocab.encode("world").is_some());
     assert!(vocab.encode("</s>").is_some());
 }
+
+#[test]
+fn test_bpe_from_gpt2_files_encodes_known_ids() {
+    let dir = tempfile::tempdir().unwrap();
+    let vocab_json = dir.path().join("vocab.json");
+    let merges_txt = dir.path().join("merges.txt");
+    std::fs::write(
+        &vocab_json,
+        r#"{"h": 0, "e": 1, "l": 2, "o": 3, "\u0120": 4, "w": 5, "r": 6, "d": 7,
+            "he": 8, "ll": 9, "hell": 10, "hello": 11, "\u0120w": 12, "or": 13,
+            "\u0120wor": 14}"#,
+    )
+    .unwrap();
+    std::fs::write(
+        &merges_txt,
+        "#version: 0.2\nh e\nl l\nhe ll\nhell o\n\u{120} w\no r\n\u{120}w or\n",
+    )
+    .unwrap();
+
+    let bpe = BpeTokenizer::from_gpt2_files(&vocab_json, &merges_txt).unwrap();
+    assert_eq!(bpe.vocab_size(), 15);
+    assert_eq!(bpe.num_merges(), 7);
+
+    let ids = bpe.encode("hello world").unwrap();
+    assert_eq!(ids, vec![11, 14, 2, 7]);
+    assert_eq!(bpe.decode(&ids).unwrap(), "hello world");
+
+    // Malformed merge lines are reported with their line number
+    std::fs::write(&merges_txt, "#version: 0.2\nh e\nl l l\n").unwrap();
+    let err = BpeTokenizer::from_gpt2_files(&vocab_json, &merges_txt).unwrap_err();
+    assert!(matches!(err, LlmError::VocabularyError(_)));
+    assert!(err.to_string().contains("line 3"), "{}", err);
+
+    std::fs::write(&vocab_json, "not json").unwrap();
+    let err = BpeTokenizer::from_gpt2_files(&vocab_json, &merges_txt).unwrap_err();
+    assert!(err.to_string().contains("Invalid vocab.json"), "{}", err);
+}